@@ -0,0 +1,96 @@
+//! Expansion of long-running recurring events, as done for every redraw of the calendar.
+//!
+//! Run with `cargo bench --bench recurrence`.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jackal::provider::{OccurrenceRule, TimeSpan};
+use rrule::RRuleSet;
+use std::time::Instant;
+
+/// Number of events shown at once, e.g., in several month panes.
+const EVENTS: usize = 200;
+
+fn daily_since_2015() -> OccurrenceRule<Utc> {
+    let rrule: RRuleSet = "DTSTART:20150101T090000Z\nRRULE:FREQ=DAILY"
+        .parse()
+        .unwrap();
+    let begin = Utc.with_ymd_and_hms(2015, 1, 1, 9, 0, 0).unwrap();
+
+    OccurrenceRule::Onetime(TimeSpan::from_start_and_duration(begin, Duration::hours(1)))
+        .with_recurring(rrule)
+}
+
+/// Occurrences of all `rules` in the month beginning at `begin`, expanding each recurrence rule
+/// from its first instance.
+fn walk(rules: &[OccurrenceRule<Utc>], begin: DateTime<Utc>) -> usize {
+    let end = begin + Duration::days(31);
+    rules
+        .iter()
+        .flat_map(|rule| {
+            let rrule = match rule {
+                OccurrenceRule::Recurring(_, rrule, _) => rrule,
+                OccurrenceRule::Onetime(_) => unreachable!(),
+            };
+            rrule
+                .into_iter()
+                .map(|dt| dt.with_timezone(&Utc))
+                .skip_while(move |dt| *dt < begin)
+                .take_while(move |dt| *dt < end)
+        })
+        .count()
+}
+
+/// Occurrences of all `rules` in the month beginning at `begin`, seeking to it directly.
+fn seek(rules: &[OccurrenceRule<Utc>], begin: DateTime<Utc>) -> usize {
+    let end = begin + Duration::days(31);
+    rules
+        .iter()
+        .flat_map(|rule| rule.iter_from(begin).take_while(move |ts| ts.begin() < end))
+        .count()
+}
+
+fn bench(name: &str, rounds: u32, mut f: impl FnMut() -> usize) {
+    let start = Instant::now();
+    let mut occurrences = 0;
+    for _ in 0..rounds {
+        occurrences += f();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<32} {:>10.3?} per round ({} occurrences)",
+        name,
+        elapsed / rounds,
+        occurrences / rounds as usize
+    );
+}
+
+fn main() {
+    let month = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+    let fresh = || (0..EVENTS).map(|_| daily_since_2015()).collect::<Vec<_>>();
+
+    // Rules remember the instances expanded once, so the first queries get fresh ones
+    let uncached = fresh();
+    bench("first query", 1, || seek(&uncached, month));
+    let uncached = fresh();
+    bench("first query in 20 years", 1, || {
+        seek(&uncached, month + Duration::days(20 * 365))
+    });
+
+    let rules = fresh();
+    bench("walk from first instance", 3, || walk(&rules, month));
+    bench("seek to window", 100, || seek(&rules, month));
+    bench("seek to next year", 100, || {
+        seek(&rules, month + Duration::days(365))
+    });
+
+    // Several panes showing different months query them in turn on every redraw
+    let rules = fresh();
+    let panes = [0, 365, 31, 3650];
+    bench("alternate between windows", 100, || {
+        panes
+            .iter()
+            .map(|days| seek(&rules, month + Duration::days(*days)))
+            .sum()
+    });
+}
@@ -1,12 +1,32 @@
-use chrono::{Datelike, Duration, Month, NaiveDate, NaiveDateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, Local, Month, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use log;
 use num_traits::FromPrimitive;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 
 use crate::config::Config;
+use crate::conflicts::Conflicts;
+use crate::exchange::freebusy::blocks_time;
 use crate::provider::datetime::days_of_month;
 use crate::provider::ical;
-use crate::provider::{EventFilter, MutCalendarlike, Occurrence, ProviderCalendar, Result};
+use crate::provider::merge::BoxedOccurrences;
+use crate::provider::{
+    local_midnight, EventFilter, Eventlike, MergedOccurrences, MutCalendarlike, Occurrence,
+    ProviderCalendar, Result,
+};
+use crate::search::SearchQuery;
+
+fn utc_bounds(
+    range: impl std::ops::RangeBounds<NaiveDateTime>,
+) -> (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>) {
+    let to_utc = |bound: Bound<&NaiveDateTime>| match bound {
+        Bound::Included(dt) => Bound::Included(Utc.from_utc_datetime(dt)),
+        Bound::Excluded(dt) => Bound::Excluded(Utc.from_utc_datetime(dt)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+
+    (to_utc(range.start_bound()), to_utc(range.end_bound()))
+}
 
 pub struct Agenda {
     calendars: BTreeMap<String, ProviderCalendar>,
@@ -47,17 +67,91 @@ impl Agenda {
         Ok(Agenda { calendars })
     }
 
-    /// Note, even though events are sorted within one calendar, they are not sorted in the
-    /// resulting iterator since multiple calendars are merged
+    /// All occurrences overlapping `range` of all calendars, lazily merged and sorted by their
+    /// begin. The range may be unbounded, e.g., `now..` for all upcoming occurrences.
     pub fn events_in<'a>(
         &'a self,
-        range: impl std::ops::RangeBounds<NaiveDateTime> + 'a + Clone,
+        range: impl std::ops::RangeBounds<NaiveDateTime>,
+    ) -> MergedOccurrences<'a> {
+        let (begin, end) = utc_bounds(range);
+
+        MergedOccurrences::new(self.calendars.values().map(
+            move |calendar| -> BoxedOccurrences<'a> {
+                Box::new(calendar.as_calendar().occurrences_in(begin, end))
+            },
+        ))
+    }
+
+    /// All occurrences overlapping `range` of the events matching `query`, sorted by their
+    /// begin.
+    pub fn search<'a>(
+        &'a self,
+        query: &SearchQuery,
+        range: impl std::ops::RangeBounds<NaiveDateTime>,
+    ) -> MergedOccurrences<'a> {
+        let (begin, end) = utc_bounds(range);
+
+        MergedOccurrences::new(self.calendars.values().map(
+            move |calendar| -> BoxedOccurrences<'a> {
+                Box::new(calendar.as_calendar().occurrences_where(
+                    begin,
+                    end,
+                    &|event: &dyn Eventlike| query.matches(event),
+                ))
+            },
+        ))
+    }
+
+    /// All occurrences matching `filter`, lazily and sorted by their begin. Without an end, the
+    /// filter only searches a few years ahead, see `EventFilter::query_bounds`.
+    pub fn filter<'a>(
+        &'a self,
+        filter: &'a EventFilter,
     ) -> impl Iterator<Item = Occurrence<'a>> + 'a {
-        self.calendars.values().flat_map(move |calendar| {
-            calendar
-                .as_calendar()
-                .filter_events(EventFilter::default().datetime_range(range.clone()))
-        })
+        let (begin, end) = filter.query_bounds();
+
+        MergedOccurrences::new(self.calendars.values().map(
+            move |calendar| -> BoxedOccurrences<'a> {
+                let calendar = calendar.as_calendar();
+                let name = calendar.name();
+                Box::new(
+                    calendar.occurrences_where(begin, end, &|event: &dyn Eventlike| {
+                        filter.matches_event(event, name) != Some(false)
+                    }),
+                )
+            },
+        ))
+        .filter(move |occurrence| filter.matches(occurrence))
+    }
+
+    /// All occurrences overlapping `range` of the events that block time, i.e., that are
+    /// neither transparent nor cancelled, nor declined by the calendar user owning one of
+    /// `addresses`, sorted by their begin.
+    pub fn busy_in<'a>(
+        &'a self,
+        range: impl std::ops::RangeBounds<NaiveDateTime>,
+        addresses: &[String],
+    ) -> MergedOccurrences<'a> {
+        let (begin, end) = utc_bounds(range);
+
+        MergedOccurrences::new(self.calendars.values().map(
+            move |calendar| -> BoxedOccurrences<'a> {
+                Box::new(calendar.as_calendar().occurrences_where(
+                    begin,
+                    end,
+                    &|event: &dyn Eventlike| blocks_time(event, addresses),
+                ))
+            },
+        ))
+    }
+
+    /// All conflicts between the occurrences of `busy_in` the `range`, i.e., double bookings.
+    pub fn conflicts_in<'a>(
+        &'a self,
+        range: impl std::ops::RangeBounds<NaiveDateTime>,
+        addresses: &[String],
+    ) -> Conflicts<'a, MergedOccurrences<'a>> {
+        Conflicts::new(self.busy_in(range, addresses))
     }
 
     pub fn _events_of_month<'a>(
@@ -71,7 +165,7 @@ impl Agenda {
             .unwrap();
         let end = begin + Duration::days(days_of_month(&month, year) as i64);
 
-        self.events_in(begin..=end)
+        self.events_in(begin..end)
     }
 
     pub fn _events_of_current_month<'a>(&'a self) -> impl Iterator<Item = Occurrence<'a>> + 'a {
@@ -82,26 +176,60 @@ impl Agenda {
         self._events_of_month(curr_month, curr_year)
     }
 
-    pub fn events_of_day<'a>(
-        &'a self,
-        date: &NaiveDate,
-    ) -> impl Iterator<Item = Occurrence<'a>> + 'a {
-        let begin = date.and_hms_opt(0, 0, 0).unwrap();
-        let end = begin + Duration::days(1);
+    /// All occurrences overlapping the local day `date`, including those that began on an
+    /// earlier day.
+    pub fn events_of_day<'a>(&'a self, date: &NaiveDate) -> MergedOccurrences<'a> {
+        let begin = local_midnight(*date);
+        let end = local_midnight(date.succ_opt().unwrap());
 
-        self.calendars.values().flat_map(move |calendar| {
-            calendar
-                .as_calendar()
-                .filter_events(EventFilter::default().datetime_range(begin..=end))
-        })
+        self.events_in(begin..end)
     }
 
     pub fn _events_of_current_day<'a>(&'a self) -> impl Iterator<Item = Occurrence<'a>> + 'a {
-        let today = Utc::now().date_naive();
+        let today = Local::now().date_naive();
 
         self.events_of_day(&today)
     }
 
+    /// The first occurrence of every event, including modified instances of recurring events,
+    /// e.g., to look at what does not depend on the occurrence.
+    pub fn first_occurrences<'a>(&'a self) -> impl Iterator<Item = Occurrence<'a>> + 'a {
+        self.calendars.values().flat_map(|calendar| {
+            let name = calendar.name();
+            let events = match calendar {
+                ProviderCalendar::Ical(c) => c.events(),
+            };
+
+            events
+                .into_iter()
+                .flat_map(|event| std::iter::once(event).chain(event.overrides()))
+                .map(move |event| Occurrence {
+                    span: event.occurrence_rule().first().with_tz(&Utc),
+                    event,
+                    calendar: name,
+                })
+        })
+    }
+
+    pub fn calendars(&self) -> impl Iterator<Item = &ProviderCalendar> {
+        self.calendars.values()
+    }
+
+    pub fn calendar_by_name(&self, name: &str) -> Option<&ProviderCalendar> {
+        self.calendars.get(name)
+    }
+
+    pub fn provider_calendar_by_name_mut(&mut self, name: &str) -> Option<&mut ProviderCalendar> {
+        self.calendars.get_mut(name)
+    }
+
+    /// The calendar containing the event with the specified uid.
+    pub fn calendar_of_event(&self, uid: &str) -> Option<&ProviderCalendar> {
+        self.calendars.values().find(|cal| match cal {
+            ProviderCalendar::Ical(c) => c.contains(uid),
+        })
+    }
+
     pub fn calendar_by_name_mut(&mut self, name: &str) -> Option<&mut dyn MutCalendarlike> {
         self.calendars.get_mut(name).and_then(|cal| match cal {
             ProviderCalendar::Ical(c) => Some(c as &mut dyn MutCalendarlike),
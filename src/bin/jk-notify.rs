@@ -1,12 +1,26 @@
 extern crate jackal as lib;
 
-use chrono::{DateTime, Duration, Local, Utc};
+use chrono::{DateTime, Duration, Local, NaiveTime, Utc};
 use flexi_logger::{Duplicate, FileSpec, Logger};
-use lib::{agenda::Agenda, provider::Occurrence};
+use lib::{
+    agenda::Agenda,
+    notification::{
+        backend,
+        control::{self, Reply, Request, ScheduledEntry},
+        digest::next_digest_time,
+        schedule::{due_reminders, max_alarm_offset},
+        Backend, Digest, DndRules, DueReminder, NotificationState, OccurrenceKey, Reminder,
+        Response, SnoozeOption,
+    },
+};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
 };
 use structopt::StructOpt;
 
@@ -28,89 +42,128 @@ pub struct Args {
 
     #[structopt(long = "log-file", help = "path to log file", parse(from_os_str))]
     pub log_file: Option<PathBuf>,
+
+    #[structopt(long = "digest", help = "print a digest of today's events and exit")]
+    pub digest: bool,
+
+    #[structopt(
+        long = "next",
+        name = "N",
+        help = "print the next N occurrences and exit"
+    )]
+    pub next: Option<usize>,
+
+    #[structopt(
+        long = "socket",
+        help = "path of the control socket",
+        parse(from_os_str)
+    )]
+    pub socket: Option<PathBuf>,
+
+    #[structopt(
+        long = "ignore-dnd",
+        help = "deliver all reminders regardless of quiet hours and focus blocks"
+    )]
+    pub ignore_dnd: bool,
 }
 
-fn open_url(url: &str) {
-    let open_prog = "xdg-open";
-    let _join_handle = std::process::Command::new(open_prog)
-        .arg(url)
-        .spawn()
-        .unwrap();
+/// Something jk-notify does at a specific time.
+enum Scheduled<'a> {
+    Reminder(DueReminder<'a>),
+    Digest,
 }
 
-fn notify(
-    title: String,
-    body: String,
-    begin: DateTime<Utc>,
-    end: DateTime<Utc>,
-    url: Option<String>,
-    _guard: NotificationGuard,
-) {
-    let mut dismissed = false;
+/// Longest time slept without checking the wall clock. Sleeping is based on a monotonic clock
+/// that stops while the system is suspended and does not follow changes of the wall clock.
+const RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
 
-    while !dismissed {
-        let now = Utc::now().naive_utc();
+/// Number of upcoming occurrences a scheduling window covers.
+const UPCOMING_OCCURRENCES: usize = 32;
 
-        let mut n = notify_rust::Notification::new();
+/// Shortest scheduling window, so that jk-notify progresses even if many occurrences begin at
+/// the same time.
+fn min_window() -> Duration {
+    Duration::minutes(1)
+}
 
-        n.action("dismiss", "Dismiss");
-        if url.is_some() {
-            n.action("open_url", "Open URL");
-        }
+/// Longest scheduling window, so that calendars are checked regularly even without upcoming
+/// occurrences.
+fn max_window() -> Duration {
+    Duration::weeks(1)
+}
 
-        let timeout;
-        let summary;
-        if now < begin.naive_utc() {
-            timeout = begin.naive_utc() - now;
-            summary = format!("Upcoming event '{}'", title);
-            n.action("snooze", "Snooze");
-        } else if now < end.naive_utc() {
-            timeout = end.naive_utc() - now;
-            summary = format!("Current event '{}'", title);
-        } else {
-            return;
-        }
+/// Reminders delivered later than this after their trigger time are marked as missed.
+fn missed_grace() -> Duration {
+    Duration::minutes(1)
+}
+
+/// Apply `change` to the notification state and persist it.
+fn update_state(state: &Mutex<NotificationState>, change: impl FnOnce(&mut NotificationState)) {
+    let mut state = state.lock().unwrap();
+    change(&mut state);
+    if let Err(e) = state.save() {
+        log::error!("Could not save notification state: {}", e);
+    }
+}
 
-        n.summary(&summary)
-            .body(&body)
-            .timeout(notify_rust::Timeout::Milliseconds(
-                timeout.num_milliseconds() as u32,
-            ))
-            .hint(notify_rust::Hint::Resident(true));
-
-        n.show().unwrap().wait_for_action(|action| match action {
-            "dismiss" => dismissed = true,
-            "snooze" => {
-                let now = Utc::now().naive_utc();
-                let to_sleep = begin.naive_utc() - now;
-                log::info!("Sleeping {} until begin notification time", to_sleep,);
-                std::thread::sleep(to_sleep.to_std().unwrap_or(std::time::Duration::ZERO));
+fn notify(
+    reminder: Reminder,
+    backend: Arc<dyn Backend>,
+    state: Arc<Mutex<NotificationState>>,
+    snoozes: Vec<SnoozeOption>,
+    tx: Sender<lib::events::Event>,
+    _guard: NotificationGuard,
+) {
+    // Missed reminders are delivered once even if their event is over already
+    let mut delivered = false;
+    while !reminder.is_over(Utc::now()) || (reminder.missed && !delivered) {
+        delivered = true;
+        match backend.notify(&reminder, &snoozes) {
+            Ok(Response::Done) => {
+                update_state(&state, |state| state.dismiss(reminder.key.clone()));
+                return;
             }
-            "open_url" => open_url(url.as_ref().unwrap()),
-            "__closed" => dismissed = true,
-            _ => {}
-        });
+            Ok(Response::Snoozed(until)) => {
+                update_state(&state, |state| state.snooze(reminder.key.clone(), until));
+                log::info!("Snoozing '{}' until {}", reminder.title, until);
+
+                // The main loop schedules the reminder again for when the snooze ends
+                if let Err(e) = tx.send(lib::events::Event::ExternalModification) {
+                    log::error!("Could not trigger rescheduling: {}", e);
+                }
+                return;
+            }
+            Ok(Response::Again) => {}
+            Ok(Response::Delivered | Response::Closed) => return,
+            Err(e) => {
+                log::error!("Could not deliver reminder of '{}': {}", reminder.title, e);
+                return;
+            }
+        }
     }
 }
 
+/// A reminder, given by its occurrence and the time it is due
+type ReminderKey = (OccurrenceKey, DateTime<Utc>);
+
 struct NotificationGuard {
-    map: Arc<Mutex<HashSet<String>>>,
-    uid: String,
+    map: Arc<Mutex<HashSet<ReminderKey>>>,
+    key: ReminderKey,
 }
 
 impl NotificationGuard {
-    fn new(uid: String, map: &Arc<Mutex<HashSet<String>>>) -> Option<Self> {
+    fn new(key: ReminderKey, map: &Arc<Mutex<HashSet<ReminderKey>>>) -> Option<Self> {
         {
             let mut r = map.lock().unwrap();
-            if r.contains(&uid) {
+            if r.contains(&key) {
                 return None;
             }
-            r.insert(uid.clone());
+            r.insert(key.clone());
         }
 
         Some(NotificationGuard {
             map: map.clone(),
-            uid,
+            key,
         })
     }
 }
@@ -118,59 +171,143 @@ impl NotificationGuard {
 impl Drop for NotificationGuard {
     fn drop(&mut self) {
         let mut r = self.map.lock().unwrap();
-        r.remove(&self.uid);
+        r.remove(&self.key);
     }
 }
 
-fn spawn_notify(occurence: Occurrence, running_notifications: &Arc<Mutex<HashSet<String>>>) {
-    use linkify::{LinkFinder, LinkKind};
-    let guard = if let Some(guard) =
-        NotificationGuard::new(occurence.event.uid().to_owned(), running_notifications)
-    {
+fn spawn_notify(
+    due: DueReminder,
+    missed: bool,
+    backend: &Arc<dyn Backend>,
+    state: &Arc<Mutex<NotificationState>>,
+    snoozes: &[SnoozeOption],
+    tx: &Sender<lib::events::Event>,
+    running_notifications: &Arc<Mutex<HashSet<ReminderKey>>>,
+) {
+    let guard = if let Some(guard) = NotificationGuard::new(due.key(), running_notifications) {
         guard
     } else {
         log::info!(
             "Not rescheduling running notification for event {}",
-            occurence.event.title()
+            due.occurrence.event.title()
         );
         return;
     };
 
-    let begin = occurence.span.begin();
-    let end = occurence.span.end();
+    let mut reminder = Reminder::new(&due.occurrence, due.action);
+    reminder.missed = missed;
+    let backend = backend.clone();
+    let state = state.clone();
+    let snoozes = snoozes.to_vec();
+    let tx = tx.clone();
 
-    let begin_display = begin.with_timezone(&Local);
-    let end_display = end.with_timezone(&Local);
+    let _ = std::thread::Builder::new()
+        .name("jackal-notify-notification".to_owned())
+        .spawn(move || notify(reminder, backend, state, snoozes, tx, guard))
+        .unwrap();
+}
 
-    let with_dates = begin_display.date_naive() != end_display.date_naive();
-    let time_str = if with_dates {
-        format!("{}-{}", begin_display, end_display)
-    } else {
-        format!("{}-{}", begin_display.time(), end_display.time())
-    };
-    let mut body = time_str;
-    if let Some(description) = occurence.event.description() {
-        body += "\n";
-        body += description;
+/// Answers requests on the control socket.
+struct Control {
+    /// Everything scheduled in the current window
+    scheduled: Arc<Mutex<Vec<ScheduledEntry>>>,
+    state: Arc<Mutex<NotificationState>>,
+    reload: Arc<AtomicBool>,
+    tx: Sender<lib::events::Event>,
+}
+
+impl Control {
+    /// The occurrence of the event with `uid` beginning at `begin` or, if not given, the
+    /// scheduled one closest to now.
+    fn occurrence(&self, uid: String, begin: Option<DateTime<Utc>>) -> Option<OccurrenceKey> {
+        if let Some(begin) = begin {
+            return Some(OccurrenceKey { uid, begin });
+        }
+
+        let now = Utc::now();
+        self.scheduled
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|entry| match entry {
+                ScheduledEntry::Reminder { uid: u, begin, .. } if *u == uid => Some(*begin),
+                _ => None,
+            })
+            .min_by_key(|begin| (*begin - now).num_seconds().abs())
+            .map(|begin| OccurrenceKey { uid, begin })
     }
-    let title = occurence.event.title().to_owned();
 
-    // TODO: We probably want to look for urls in other fields like location or URL, too.
-    let url = occurence.event.description().and_then(|description| {
-        let mut finder = LinkFinder::new();
-        let mut links = finder.kinds(&[LinkKind::Url]).links(description);
-        links.next().map(|l| l.as_str().to_owned())
-    });
+    /// Let the main loop pick up changes.
+    fn reschedule(&self) {
+        if let Err(e) = self.tx.send(lib::events::Event::ExternalModification) {
+            log::error!("Could not trigger rescheduling: {}", e);
+        }
+    }
 
-    let _ = std::thread::Builder::new()
-        .name("jackal-notify-notification".to_owned())
-        .spawn(move || notify(title, body, begin, end, url, guard))
-        .unwrap();
+    fn handle(&self, request: Request) -> Reply {
+        match request {
+            Request::ListScheduled => Reply::Scheduled {
+                entries: self.scheduled.lock().unwrap().clone(),
+            },
+            Request::Snooze { uid, begin, snooze } => {
+                let snooze = match snooze.parse::<SnoozeOption>() {
+                    Ok(snooze) => snooze,
+                    Err(e) => {
+                        return Reply::Error {
+                            message: e.to_string(),
+                        }
+                    }
+                };
+                let key = match self.occurrence(uid.clone(), begin) {
+                    Some(key) => key,
+                    None => {
+                        return Reply::Error {
+                            message: format!("No reminder of event '{}' is scheduled", uid),
+                        }
+                    }
+                };
+
+                let until = snooze.until(Utc::now(), key.begin);
+                log::info!("Snoozing '{}' until {} on request", key.uid, until);
+                update_state(&self.state, |state| state.snooze(key, until));
+                self.reschedule();
+                Reply::Ok {
+                    message: format!("Snoozed until {}", until.with_timezone(&Local)),
+                }
+            }
+            Request::Dismiss { uid, begin } => {
+                let key = match self.occurrence(uid.clone(), begin) {
+                    Some(key) => key,
+                    None => {
+                        return Reply::Error {
+                            message: format!("No reminder of event '{}' is scheduled", uid),
+                        }
+                    }
+                };
+
+                log::info!("Dismissing '{}' on request", key.uid);
+                update_state(&self.state, |state| state.dismiss(key));
+                self.reschedule();
+                Reply::Ok {
+                    message: "Dismissed".to_owned(),
+                }
+            }
+            Request::Reload => {
+                self.reload.store(true, Ordering::SeqCst);
+                self.reschedule();
+                Reply::Ok {
+                    message: "Reloading calendars".to_owned(),
+                }
+            }
+        }
+    }
 }
 
 enum ControlFlow {
     Continue,
     Restart,
+    /// The wall clock jumped, e.g., because the system was suspended
+    ClockJump,
 }
 
 fn wait(
@@ -178,22 +315,35 @@ fn wait(
     until: DateTime<Utc>,
     info: &str,
 ) -> ControlFlow {
+    log::info!("Sleeping {} {}", until - Utc::now(), info);
+
     loop {
-        let now = Utc::now().naive_utc();
-        let to_sleep = until.naive_utc() - now;
+        let wall_start = Utc::now();
+        let monotonic_start = std::time::Instant::now();
 
-        log::info!("Sleeping {} {}", to_sleep, info);
+        let to_sleep = match (until - wall_start).to_std() {
+            Ok(to_sleep) => to_sleep.min(RECHECK_INTERVAL),
+            Err(_) => return ControlFlow::Continue,
+        };
 
-        match events.recv_timeout(to_sleep.to_std().unwrap_or(std::time::Duration::ZERO)) {
+        match events.recv_timeout(to_sleep) {
             Ok(lib::events::Event::ExternalModification) => return ControlFlow::Restart,
             Ok(lib::events::Event::Update | lib::events::Event::Input(_)) => {
                 panic!("No dispatcher was started so where do those come from?!")
             }
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return ControlFlow::Continue,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
             Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
                 panic!("Event senders are disconnected")
             }
         }
+
+        let wall_elapsed = Utc::now() - wall_start;
+        let monotonic_elapsed = Duration::from_std(monotonic_start.elapsed()).unwrap();
+        let jump = wall_elapsed - monotonic_elapsed;
+        if jump.num_seconds().abs() > RECHECK_INTERVAL.as_secs() as i64 {
+            log::warn!("Wall clock jumped by {}, rescheduling", jump);
+            return ControlFlow::ClockJump;
+        }
     }
 }
 
@@ -213,45 +363,231 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = lib::config::load_suitable_config(args.configfile.as_deref())?;
 
     let (tx, mod_rx) = std::sync::mpsc::channel();
+
+    if args.digest {
+        let agenda = Agenda::from_config(&config, &tx)?;
+        print!("{}", Digest::for_day(&agenda, &Local::now().date_naive()));
+        return Ok(());
+    }
+
+    if let Some(count) = args.next {
+        let agenda = Agenda::from_config(&config, &tx)?;
+        for occurrence in agenda
+            .events_in(Utc::now().naive_utc()..)
+            .filter(|occurrence| !occurrence.event().is_cancelled())
+            .take(count)
+        {
+            println!(
+                "{}  {} ({})",
+                occurrence
+                    .begin()
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M"),
+                occurrence.event().title(),
+                occurrence.calendar
+            );
+        }
+        return Ok(());
+    }
+
     let headsup_time = Duration::minutes(config.notification_headsup_minutes.into());
-    let check_window = Duration::days(1);
+    // Reminders missed for longer than this, e.g., while the system was suspended, are dropped
+    let max_catch_up = Duration::days(1);
+
+    // Also covers reminders snoozed for a while
+    let min_alarm_lookaround = Duration::weeks(1);
     assert!(
-        check_window > headsup_time,
-        "Check window is too small for headsup time"
+        min_alarm_lookaround > headsup_time,
+        "Alarm lookaround is too small for headsup time"
     );
 
+    let snoozes = config
+        .notify
+        .snoozes
+        .iter()
+        .map(|snooze| snooze.parse::<SnoozeOption>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let digest_time = config
+        .notify
+        .digest_time
+        .as_deref()
+        .map(|time| NaiveTime::parse_from_str(time, "%H:%M"))
+        .transpose()?;
+
+    let dnd = if args.ignore_dnd {
+        DndRules::default()
+    } else {
+        DndRules::from_config(&config)?
+    };
+
+    let default_backend: Arc<dyn Backend> = backend::from_config(&config.notify.backend).into();
+    let backends: HashMap<String, Arc<dyn Backend>> = config
+        .collections
+        .iter()
+        .flat_map(|collection| collection.calendars.iter())
+        .filter_map(|calendar| {
+            let backend: Arc<dyn Backend> = backend::from_config(calendar.notify.as_ref()?).into();
+            Some((calendar.name.clone(), backend))
+        })
+        .collect();
+
     let running_notifications = Arc::new(Mutex::new(HashSet::new()));
+    let state = Arc::new(Mutex::new(match NotificationState::default_path() {
+        Some(path) => NotificationState::load(&path),
+        None => {
+            log::warn!("No state directory found, dismissals will not be remembered");
+            NotificationState::default()
+        }
+    }));
+
+    let scheduled = Arc::new(Mutex::new(Vec::new()));
+    let reload = Arc::new(AtomicBool::new(false));
+    match args.socket.or_else(control::default_path) {
+        Some(path) => {
+            let control = Control {
+                scheduled: scheduled.clone(),
+                state: state.clone(),
+                reload: reload.clone(),
+                tx: tx.clone(),
+            };
+            control::serve(&path, move |request| control.handle(request))?;
+            log::info!("Listening on '{}'", path.display());
+        }
+        None => log::warn!("No runtime directory found, control socket is disabled"),
+    }
+
+    // Everything scheduled before this time has been handled
+    let mut handled_until = Utc::now();
 
     let mut calendar = Agenda::from_config(&config, &tx)?;
     'outer: loop {
+        if reload.swap(false, Ordering::SeqCst) {
+            match Agenda::from_config(&config, &tx) {
+                Ok(agenda) => calendar = agenda,
+                Err(e) => log::error!("Could not reload calendars: {}", e),
+            }
+        }
         calendar.process_external_modifications();
 
-        loop {
-            let begin = Utc::now();
-            let end = begin + check_window;
-
-            let mut next_events = calendar
-                .events_in(begin.naive_utc()..end.naive_utc())
-                .collect::<Vec<_>>();
-            next_events.sort_unstable_by_key(|occurrence| occurrence.begin());
-
-            for occurrence in next_events {
-                let begin_utc = occurrence.begin();
-                let headsup_begin = begin_utc - headsup_time;
+        // Relative alarms may go off long before or after their occurrence
+        let alarm_lookaround = max_alarm_offset(&calendar).max(min_alarm_lookaround);
+
+        'window: loop {
+            // Start where the last window ended, so that reminders missed while the system was
+            // suspended are delivered late instead of being skipped
+            let begin = handled_until.max(Utc::now() - max_catch_up);
+            // The window adapts to how busy the calendars are by ending with the next few
+            // upcoming occurrences
+            let end = calendar
+                .events_in(begin.naive_utc()..)
+                .filter(|occurrence| !occurrence.event().is_cancelled())
+                .nth(UPCOMING_OCCURRENCES - 1)
+                .map_or(begin + max_window(), |occurrence| {
+                    occurrence.begin().min(begin + max_window())
+                })
+                .max(begin + min_window());
+
+            // Occurrences this old cannot trigger any more reminders
+            {
+                let mut state = state.lock().unwrap();
+                if state.prune(begin - alarm_lookaround) {
+                    if let Err(e) = state.save() {
+                        log::error!("Could not save notification state: {}", e);
+                    }
+                }
+            }
 
-                match wait(&mod_rx, headsup_begin, "until headsup time of next event") {
+            let mut reminders = {
+                let state = state.lock().unwrap();
+                due_reminders(
+                    &calendar,
+                    &state,
+                    headsup_time,
+                    alarm_lookaround,
+                    begin..end,
+                )
+                .into_iter()
+                .map(|due| (due.time, Scheduled::Reminder(due)))
+                .collect::<Vec<_>>()
+            };
+
+            if let Some(digest_time) = digest_time {
+                let mut due = next_digest_time(begin, digest_time);
+                while due < end {
+                    reminders.push((due, Scheduled::Digest));
+                    due = next_digest_time(due, digest_time);
+                }
+            }
+            reminders.sort_by_key(|(time, _)| *time);
+
+            *scheduled.lock().unwrap() = reminders
+                .iter()
+                .map(|(time, scheduled)| match scheduled {
+                    Scheduled::Reminder(due) => ScheduledEntry::Reminder {
+                        time: *time,
+                        uid: due.occurrence.event.uid().to_owned(),
+                        begin: due.occurrence.begin(),
+                        title: due.occurrence.event.title().to_owned(),
+                        calendar: due.occurrence.calendar.to_owned(),
+                    },
+                    Scheduled::Digest => ScheduledEntry::Digest { time: *time },
+                })
+                .collect();
+
+            for (time, scheduled) in reminders {
+                match wait(&mod_rx, time, "until next reminder") {
                     ControlFlow::Restart => continue 'outer,
+                    ControlFlow::ClockJump => continue 'window,
                     ControlFlow::Continue => {}
                 }
-
-                spawn_notify(occurrence, &running_notifications);
+                // The next window starts right after this reminder
+                handled_until = time + Duration::nanoseconds(1);
+
+                match scheduled {
+                    Scheduled::Reminder(due) => {
+                        let occurrence = &due.occurrence;
+                        if let Some(reason) = dnd.suppression(&calendar, occurrence, Utc::now()) {
+                            log::info!(
+                                "Suppressing reminder of '{}' due to {}",
+                                occurrence.event.title(),
+                                reason
+                            );
+                            continue;
+                        }
+
+                        let backend = backends
+                            .get(occurrence.calendar)
+                            .unwrap_or(&default_backend);
+                        spawn_notify(
+                            due,
+                            time < Utc::now() - missed_grace(),
+                            backend,
+                            &state,
+                            &snoozes,
+                            &tx,
+                            &running_notifications,
+                        );
+                    }
+                    Scheduled::Digest => {
+                        let digest =
+                            Digest::for_day(&calendar, &time.with_timezone(&Local).date_naive());
+                        let backend = default_backend.clone();
+                        let _ = std::thread::Builder::new()
+                            .name("jackal-notify-digest".to_owned())
+                            .spawn(move || {
+                                if let Err(e) = backend.digest(&digest) {
+                                    log::error!("Could not deliver digest: {}", e);
+                                }
+                            })
+                            .unwrap();
+                    }
+                }
             }
 
-            let end = end - headsup_time;
-
             match wait(&mod_rx, end, " until end of window. No more events!") {
                 ControlFlow::Restart => continue 'outer,
-                ControlFlow::Continue => {}
+                ControlFlow::ClockJump => continue 'window,
+                ControlFlow::Continue => handled_until = end,
             }
         }
     }
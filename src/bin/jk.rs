@@ -1,10 +1,22 @@
 extern crate jackal as lib;
 
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use flexi_logger::{Duplicate, FileSpec, Logger};
 use lib::agenda::Agenda;
+use lib::config::Config;
 use lib::events::Dispatcher;
+use lib::exchange::csv::{ColumnMapping, CsvOptions};
+use lib::exchange::freebusy::{self, FreeBusy, FreeBusyFormat, Period};
+use lib::exchange::{self, Format};
+use lib::notification::control::{self, Reply, Request, ScheduledEntry};
+use lib::provider::ical::itip;
+use lib::provider::{local_midnight, month_of, parse_duration, EventFilter, Occurrence};
+use lib::report::{Grouping, Report, ReportFormat};
+use lib::search::SearchQuery;
+use lib::slots::{free_slots, slot_event, SlotOptions, WorkingHours};
 use lib::ui::app::App;
-use std::io::stdout;
+use std::io::{stdout, Read};
+use std::ops::Bound;
 use std::path::PathBuf;
 use structopt::StructOpt;
 use unsegen::base::Terminal;
@@ -34,6 +46,745 @@ pub struct Args {
 
     #[structopt(long = "log-file", help = "path to log file", parse(from_os_str))]
     pub log_file: Option<PathBuf>,
+
+    #[structopt(long = "date", help = "start on this day (YYYY-MM-DD)")]
+    pub date: Option<NaiveDate>,
+
+    #[structopt(long = "event", help = "select the event with this uid")]
+    pub event: Option<String>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    #[structopt(about = "Export events into a single file")]
+    Export {
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "ics",
+            help = "output format (ics, xcal, jscalendar)"
+        )]
+        format: Format,
+
+        #[structopt(
+            long = "calendar",
+            help = "only export events of this calendar (may be repeated)"
+        )]
+        calendars: Vec<String>,
+
+        #[structopt(
+            short = "o",
+            long = "output",
+            help = "write to file instead of stdout",
+            parse(from_os_str)
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Import events into a calendar")]
+    Import {
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "ics",
+            help = "input format (ics, xcal, jscalendar)"
+        )]
+        format: Format,
+
+        #[structopt(name = "CALENDAR", help = "name of the calendar to import into")]
+        calendar: String,
+
+        #[structopt(
+            name = "FILE",
+            help = "file to import, stdin if omitted",
+            parse(from_os_str)
+        )]
+        input: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Import events from a CSV file")]
+    ImportCsv {
+        #[structopt(
+            long = "calendar",
+            help = "calendar for rows without a calendar column"
+        )]
+        calendar: Option<String>,
+
+        #[structopt(
+            short = "m",
+            long = "map",
+            help = "map event fields to columns, e.g. 'title=Shift,start=From,end=3'"
+        )]
+        mapping: Option<String>,
+
+        #[structopt(
+            long = "datetime-format",
+            help = "strftime format of dates with time (may be repeated)"
+        )]
+        datetime_formats: Vec<String>,
+
+        #[structopt(
+            long = "date-format",
+            help = "strftime format of dates without time (may be repeated)"
+        )]
+        date_formats: Vec<String>,
+
+        #[structopt(long = "timezone", help = "time zone of all times in the file")]
+        timezone: Option<String>,
+
+        #[structopt(short = "d", long = "delimiter", help = "column delimiter")]
+        delimiter: Option<char>,
+
+        #[structopt(long = "no-headers", help = "the file has no header row")]
+        no_headers: bool,
+
+        #[structopt(
+            name = "FILE",
+            help = "file to import, stdin if omitted",
+            parse(from_os_str)
+        )]
+        input: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Search events by title, description, location and categories")]
+    Search {
+        #[structopt(name = "PATTERN", help = "text to look for, case-insensitive")]
+        pattern: String,
+
+        #[structopt(
+            short = "r",
+            long = "regex",
+            help = "the pattern is a regular expression"
+        )]
+        regex: bool,
+
+        #[structopt(
+            long = "from",
+            help = "first day to search, all past events if omitted"
+        )]
+        from: Option<NaiveDate>,
+
+        #[structopt(long = "to", help = "last day to search, all future events if omitted")]
+        to: Option<NaiveDate>,
+
+        #[structopt(
+            short = "n",
+            long = "limit",
+            help = "print at most this many hits, 100 by default without --to"
+        )]
+        limit: Option<usize>,
+    },
+
+    #[structopt(
+        about = "List events matching a filter, e.g. 'calendar:work not status:cancelled'"
+    )]
+    Filter {
+        #[structopt(name = "QUERY", help = "filter query, '@name' refers to saved filters")]
+        query: String,
+
+        #[structopt(long = "from", help = "first day to list, all past events if omitted")]
+        from: Option<NaiveDate>,
+
+        #[structopt(
+            long = "to",
+            help = "last day to list, events of the next five years if omitted"
+        )]
+        to: Option<NaiveDate>,
+
+        #[structopt(
+            short = "n",
+            long = "limit",
+            help = "print at most this many events, 100 by default without --to"
+        )]
+        limit: Option<usize>,
+    },
+
+    #[structopt(about = "Show when you are busy without showing the events")]
+    Freebusy {
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "text",
+            help = "output format (text, ics, json)"
+        )]
+        format: FreeBusyFormat,
+
+        #[structopt(
+            long = "calendar",
+            help = "only consider events of this calendar (may be repeated)"
+        )]
+        calendars: Vec<String>,
+
+        #[structopt(long = "from", help = "first day, today if omitted")]
+        from: Option<NaiveDate>,
+
+        #[structopt(long = "to", help = "last day, a week after the first one if omitted")]
+        to: Option<NaiveDate>,
+
+        #[structopt(
+            short = "o",
+            long = "output",
+            help = "write to file instead of stdout",
+            parse(from_os_str)
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Find free slots for a meeting")]
+    Slots {
+        #[structopt(
+            short = "d",
+            long = "duration",
+            help = "length of the meeting, e.g. '45m'",
+            parse(try_from_str = parse_duration)
+        )]
+        duration: Duration,
+
+        #[structopt(
+            long = "within",
+            help = "days to search like '2024-05-06..2024-05-10', the next week if omitted",
+            parse(try_from_str = parse_day_range)
+        )]
+        within: Option<(NaiveDate, NaiveDate)>,
+
+        #[structopt(long = "hours", help = "working hours like '09:00-17:00'")]
+        hours: Option<WorkingHours>,
+
+        #[structopt(
+            long = "buffer",
+            help = "free time to keep around other events, e.g. '10m'",
+            parse(try_from_str = parse_duration)
+        )]
+        buffer: Option<Duration>,
+
+        #[structopt(long = "weekends", help = "also find slots on weekends")]
+        weekends: bool,
+
+        #[structopt(
+            long = "calendar",
+            help = "only consider events of this calendar (may be repeated)"
+        )]
+        calendars: Vec<String>,
+
+        #[structopt(
+            long = "include",
+            help = "also consider the busy time of a published free/busy file (may be repeated)",
+            parse(from_os_str)
+        )]
+        include: Vec<PathBuf>,
+
+        #[structopt(
+            long = "book",
+            help = "add a meeting at the start of the slot with this number"
+        )]
+        book: Option<usize>,
+
+        #[structopt(long = "into", help = "calendar to add the booked meeting to")]
+        into: Option<String>,
+
+        #[structopt(long = "title", help = "title of the booked meeting")]
+        title: Option<String>,
+    },
+
+    #[structopt(about = "List events that overlap although both block time")]
+    Conflicts {
+        #[structopt(long = "from", help = "first day, today if omitted")]
+        from: Option<NaiveDate>,
+
+        #[structopt(long = "to", help = "last day, a week after the first one if omitted")]
+        to: Option<NaiveDate>,
+    },
+
+    #[structopt(about = "Sum up the time spent in events")]
+    Report {
+        #[structopt(
+            short = "b",
+            long = "by",
+            default_value = "calendar",
+            help = "group by calendar, category, week or month"
+        )]
+        grouping: Grouping,
+
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "text",
+            help = "output format (text, csv, json)"
+        )]
+        format: ReportFormat,
+
+        #[structopt(
+            long = "calendar",
+            help = "only consider events of this calendar (may be repeated)"
+        )]
+        calendars: Vec<String>,
+
+        #[structopt(long = "from", help = "first day, the first of this month if omitted")]
+        from: Option<NaiveDate>,
+
+        #[structopt(
+            long = "to",
+            help = "last day, the end of the first day's month if omitted"
+        )]
+        to: Option<NaiveDate>,
+
+        #[structopt(
+            short = "o",
+            long = "output",
+            help = "write to file instead of stdout",
+            parse(from_os_str)
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Process invitations and respond to them")]
+    Invite(InviteCommand),
+
+    #[structopt(about = "Control a running jk-notify")]
+    Notify(NotifyCommand),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum InviteCommand {
+    #[structopt(about = "Apply invitations, updates, cancellations and replies")]
+    Process {
+        #[structopt(
+            name = "CALENDAR",
+            help = "name of the calendar new invitations are added to"
+        )]
+        calendar: String,
+
+        #[structopt(
+            name = "FILE",
+            help = "iCalendar file to process, stdin if omitted",
+            parse(from_os_str)
+        )]
+        input: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Respond to an invitation")]
+    Reply {
+        #[structopt(name = "UID", help = "uid of the event")]
+        uid: String,
+
+        #[structopt(name = "RESPONSE", help = "accept, decline or tentative")]
+        response: String,
+
+        #[structopt(
+            short = "o",
+            long = "output",
+            help = "write the reply to file instead of stdout",
+            parse(from_os_str)
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct NotifyCommand {
+    #[structopt(
+        long = "socket",
+        help = "path of the control socket of jk-notify",
+        parse(from_os_str)
+    )]
+    socket: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    request: NotifyRequest,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum NotifyRequest {
+    #[structopt(about = "List the scheduled reminders")]
+    List,
+
+    #[structopt(about = "Snooze the reminders of an event")]
+    Snooze {
+        #[structopt(name = "UID", help = "uid of the event")]
+        uid: String,
+
+        #[structopt(name = "SNOOZE", help = "duration like '10m', '5m before' or 'start'")]
+        snooze: String,
+
+        #[structopt(
+            long = "begin",
+            help = "begin of the occurrence (RFC 3339), the closest one if omitted"
+        )]
+        begin: Option<DateTime<Utc>>,
+    },
+
+    #[structopt(about = "Dismiss the reminders of an event")]
+    Dismiss {
+        #[structopt(name = "UID", help = "uid of the event")]
+        uid: String,
+
+        #[structopt(
+            long = "begin",
+            help = "begin of the occurrence (RFC 3339), the closest one if omitted"
+        )]
+        begin: Option<DateTime<Utc>>,
+    },
+
+    #[structopt(about = "Reload all calendars")]
+    Reload,
+}
+
+fn run_notify_command(command: NotifyCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let path = command
+        .socket
+        .or_else(control::default_path)
+        .ok_or("No runtime directory found, specify the socket with --socket")?;
+
+    let request = match command.request {
+        NotifyRequest::List => Request::ListScheduled,
+        NotifyRequest::Snooze { uid, snooze, begin } => Request::Snooze { uid, begin, snooze },
+        NotifyRequest::Dismiss { uid, begin } => Request::Dismiss { uid, begin },
+        NotifyRequest::Reload => Request::Reload,
+    };
+
+    let reply = control::send(&path, &request)
+        .map_err(|e| format!("Could not reach jk-notify at '{}': {}", path.display(), e))?;
+
+    match reply {
+        Reply::Ok { message } => println!("{}", message),
+        Reply::Scheduled { entries } => {
+            for entry in entries {
+                let time = entry.time().with_timezone(&Local).format("%Y-%m-%d %H:%M");
+                match entry {
+                    ScheduledEntry::Reminder {
+                        uid,
+                        title,
+                        calendar,
+                        ..
+                    } => println!("{}  {} ({}) [{}]", time, title, calendar, uid),
+                    ScheduledEntry::Digest { .. } => println!("{}  Daily digest", time),
+                }
+            }
+        }
+        Reply::Error { message } => return Err(message.into()),
+    }
+
+    Ok(())
+}
+
+/// The UTC range of the local days `from` until `to`, open-ended where these are omitted.
+fn day_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> (Bound<NaiveDateTime>, Bound<NaiveDateTime>) {
+    let begin = from.map_or(Bound::Unbounded, |date| {
+        Bound::Included(local_midnight(date))
+    });
+    let end = to.map_or(Bound::Unbounded, |date| {
+        Bound::Excluded(local_midnight(date.succ_opt().unwrap()))
+    });
+    (begin, end)
+}
+
+/// Parse a range of days like `2024-05-06..2024-05-10`, which includes its last day.
+fn parse_day_range(s: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let (from, to) = s.split_once("..").ok_or_else(|| {
+        format!(
+            "Invalid range '{}', expected e.g. '2024-05-06..2024-05-10'",
+            s
+        )
+    })?;
+    let parse = |date: &str| date.trim().parse::<NaiveDate>().map_err(|e| e.to_string());
+
+    Ok((parse(from)?, parse(to)?))
+}
+
+/// Maximum number of occurrences to list. Recurring events without end would never stop
+/// matching, so the number is limited unless the last day `to` is given.
+fn default_limit(limit: Option<usize>, to: Option<NaiveDate>) -> usize {
+    match (limit, to) {
+        (Some(limit), _) => limit,
+        (None, Some(_)) => usize::MAX,
+        (None, None) => 100,
+    }
+}
+
+fn print_occurrence(occurrence: &Occurrence) {
+    println!(
+        "{}  {} ({}) [{}]",
+        occurrence
+            .begin()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M"),
+        occurrence.event().title(),
+        occurrence.calendar,
+        occurrence.event().uid()
+    );
+}
+
+fn read_input(input: Option<PathBuf>) -> std::io::Result<String> {
+    if let Some(path) = input {
+        std::fs::read_to_string(path)
+    } else {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    }
+}
+
+fn run_command(command: Command, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    // Calendars want to report modifications, but nobody listens for them here
+    let (tx, _rx) = std::sync::mpsc::channel();
+    let mut agenda = Agenda::from_config(config, &tx)?;
+
+    match command {
+        Command::Export {
+            format,
+            calendars,
+            output,
+        } => {
+            let exported = exchange::export(&agenda, &calendars, format)?;
+            if let Some(path) = output {
+                std::fs::write(path, exported)?;
+            } else {
+                print!("{}", exported);
+            }
+        }
+        Command::Import {
+            format,
+            calendar,
+            input,
+        } => {
+            let content = read_input(input)?;
+            let imported = exchange::import(&mut agenda, &calendar, format, &content)?;
+            println!("Imported {} event(s) into '{}'", imported, calendar);
+        }
+        Command::ImportCsv {
+            calendar,
+            mapping,
+            datetime_formats,
+            date_formats,
+            timezone,
+            delimiter,
+            no_headers,
+            input,
+        } => {
+            let mut options = CsvOptions::from_config(&config.csv_import)?;
+            if let Some(mapping) = mapping {
+                options.mapping.extend(ColumnMapping::parse(&mapping)?);
+            }
+            if !datetime_formats.is_empty() {
+                options.datetime_formats = datetime_formats;
+            }
+            if !date_formats.is_empty() {
+                options.date_formats = date_formats;
+            }
+            if let Some(timezone) = timezone {
+                options.set_timezone(&timezone)?;
+            }
+            if let Some(delimiter) = delimiter {
+                options.set_delimiter(delimiter)?;
+            }
+            if no_headers {
+                options.has_headers = false;
+            }
+            if calendar.is_some() {
+                options.calendar = calendar;
+            }
+
+            let content = read_input(input)?;
+            let result = exchange::csv::import(&mut agenda, &options, &content)?;
+
+            for error in result.errors.iter() {
+                eprintln!("Skipped {}", error);
+            }
+            println!(
+                "Imported {} event(s), skipped {} row(s)",
+                result.imported,
+                result.errors.len()
+            );
+        }
+        Command::Search {
+            pattern,
+            regex,
+            from,
+            to,
+            limit,
+        } => {
+            let query = if regex {
+                SearchQuery::regex(&pattern)?
+            } else {
+                SearchQuery::substring(&pattern)
+            };
+            let hits = agenda.search(&query, day_range(from, to));
+            for occurrence in hits.take(default_limit(limit, to)) {
+                print_occurrence(&occurrence);
+            }
+        }
+        Command::Filter {
+            query,
+            from,
+            to,
+            limit,
+        } => {
+            let filter =
+                EventFilter::parse(&query, &config.filters)?.datetime_range(day_range(from, to));
+
+            for occurrence in agenda.filter(&filter).take(default_limit(limit, to)) {
+                print_occurrence(&occurrence);
+            }
+        }
+        Command::Freebusy {
+            format,
+            calendars,
+            from,
+            to,
+            output,
+        } => {
+            let from = from.unwrap_or_else(|| Local::now().date_naive());
+            let to = to.unwrap_or(from + Duration::days(6));
+            let freebusy = FreeBusy::compute(
+                &agenda,
+                &calendars,
+                Utc.from_utc_datetime(&local_midnight(from)),
+                Utc.from_utc_datetime(&local_midnight(to.succ_opt().unwrap())),
+                &config.addresses,
+            )?;
+
+            let rendered = freebusy.render(format, config.addresses.first().map(String::as_str))?;
+            if let Some(path) = output {
+                std::fs::write(path, rendered)?;
+            } else {
+                print!("{}", rendered);
+            }
+        }
+        Command::Slots {
+            duration,
+            within,
+            hours,
+            buffer,
+            weekends,
+            calendars,
+            include,
+            book,
+            into,
+            title,
+        } => {
+            let mut options = SlotOptions::from_config(&config.slots, duration)?;
+            if let Some(hours) = hours {
+                options.hours = hours;
+            }
+            if let Some(buffer) = buffer {
+                options.buffer = buffer;
+            }
+            options.weekends |= weekends;
+
+            let today = Local::now().date_naive();
+            let (from, to) = within.unwrap_or((today, today + Duration::days(6)));
+            let start = Utc.from_utc_datetime(&local_midnight(from)).max(Utc::now());
+            let end = Utc.from_utc_datetime(&local_midnight(to.succ_opt().unwrap()));
+
+            let mut freebusy =
+                FreeBusy::compute(&agenda, &calendars, start, end, &config.addresses)?;
+            for path in include {
+                freebusy.add_busy(freebusy::parse_busy(&std::fs::read_to_string(path)?)?);
+            }
+            let slots = free_slots(&freebusy, &options);
+
+            if let Some(number) = book {
+                let slot = number
+                    .checked_sub(1)
+                    .and_then(|index| slots.get(index))
+                    .ok_or_else(|| format!("No slot number {}", number))?;
+                let name = into.ok_or("Specify the calendar of the meeting with --into")?;
+
+                let mut event = slot_event(slot, duration);
+                if let Some(title) = title {
+                    event.set_title(&title);
+                }
+                agenda
+                    .calendar_by_name_mut(&name)
+                    .ok_or_else(|| format!("Calendar '{}' not found", name))?
+                    .add_event(event)?;
+                let booked = Period {
+                    start: slot.start,
+                    end: slot.start + duration,
+                };
+                println!("Booked {}", booked);
+            } else if slots.is_empty() {
+                println!("No free slots");
+            } else {
+                for (number, slot) in slots.iter().enumerate() {
+                    println!("{:>3}  {}", number + 1, slot);
+                }
+            }
+        }
+        Command::Conflicts { from, to } => {
+            let from = from.unwrap_or_else(|| Local::now().date_naive());
+            let to = to.unwrap_or(from + Duration::days(6));
+
+            let mut found = false;
+            for conflict in agenda.conflicts_in(day_range(Some(from), Some(to)), &config.addresses)
+            {
+                found = true;
+                println!(
+                    "{}  '{}' ({}) overlaps '{}' ({})",
+                    conflict.overlap(),
+                    conflict.first.event().title(),
+                    conflict.first.calendar,
+                    conflict.second.event().title(),
+                    conflict.second.calendar
+                );
+            }
+            if !found {
+                println!("No conflicts");
+            }
+        }
+        Command::Report {
+            grouping,
+            format,
+            calendars,
+            from,
+            to,
+            output,
+        } => {
+            let from = from.unwrap_or_else(|| month_of(Local::now().date_naive()).0);
+            let to = to.unwrap_or_else(|| month_of(from).1);
+            let report =
+                Report::compute(&agenda, &calendars, grouping, from, to, &config.addresses)?;
+
+            let rendered = report.render(format)?;
+            if let Some(path) = output {
+                std::fs::write(path, rendered)?;
+            } else {
+                print!("{}", rendered);
+            }
+        }
+        Command::Invite(InviteCommand::Process { calendar, input }) => {
+            let content = read_input(input)?;
+            for outcome in exchange::process_invitations(&mut agenda, &calendar, &content)? {
+                println!("{}", outcome);
+            }
+        }
+        Command::Invite(InviteCommand::Reply {
+            uid,
+            response,
+            output,
+        }) => {
+            if config.addresses.is_empty() {
+                return Err("No own addresses configured, set 'addresses' in the config".into());
+            }
+
+            let status = itip::parse_response(&response)?;
+            let reply = exchange::reply(&mut agenda, &uid, &config.addresses, &status)?;
+            if let Some(path) = output {
+                std::fs::write(path, reply)?;
+            } else {
+                print!("{}", reply);
+            }
+        }
+        Command::Notify(_) => unreachable!("Handled without loading the agenda"),
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -51,6 +802,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let config = lib::config::load_suitable_config(args.configfile.as_deref())?;
 
+    match args.command {
+        Some(Command::Notify(command)) => return run_notify_command(command),
+        Some(command) => return run_command(command, &config),
+        None => {}
+    }
+
     let dispatcher = Dispatcher::from_config(&config);
     // Setup unsegen terminal
     let stdout = stdout();
@@ -59,6 +816,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let calendar = Agenda::from_config(&config, dispatcher.event_sink())?;
 
     let mut app = App::new(&config, calendar);
+    if let Some(date) = args.date {
+        app.focus(date, args.event.as_deref());
+    }
 
     app.run(dispatcher, term)
 }
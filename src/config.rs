@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io;
@@ -36,6 +37,10 @@ fn find_configfile() -> io::Result<PathBuf> {
 pub struct CalendarConfig {
     pub id: String,
     pub name: String,
+    /// Where jk-notify delivers reminders of this calendar, overriding `notify.backend`
+    pub notify: Option<BackendConfig>,
+    /// Quiet hours of this calendar, overriding `notify.quiet_hours`
+    pub quiet_hours: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +51,158 @@ pub struct CollectionConfig {
     pub calendars: Vec<CalendarConfig>,
 }
 
+/// Defaults for importing events from CSV files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CsvImportConfig {
+    /// Maps event fields (title, start, end, duration, allday, description, location,
+    /// calendar) to column names or 1-based column indices
+    #[serde(default)]
+    pub columns: HashMap<String, String>,
+    #[serde(default)]
+    pub datetime_formats: Vec<String>,
+    #[serde(default)]
+    pub date_formats: Vec<String>,
+    pub timezone: Option<String>,
+    pub delimiter: Option<char>,
+    #[serde(default)]
+    pub no_headers: bool,
+    pub calendar: Option<String>,
+}
+
+/// How events the user declined are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeclinedEvents {
+    Show,
+    Dim,
+    Hide,
+}
+
+impl Default for DeclinedEvents {
+    fn default() -> Self {
+        DeclinedEvents::Dim
+    }
+}
+
+impl DeclinedEvents {
+    pub fn next(self) -> Self {
+        match self {
+            DeclinedEvents::Show => DeclinedEvents::Dim,
+            DeclinedEvents::Dim => DeclinedEvents::Hide,
+            DeclinedEvents::Hide => DeclinedEvents::Show,
+        }
+    }
+}
+
+fn default_snoozes() -> Vec<String> {
+    ["5m", "10m", "1m before", "start"]
+        .iter()
+        .map(|snooze| snooze.to_string())
+        .collect()
+}
+
+/// A way of delivering reminders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BackendConfig {
+    /// Desktop notifications via D-Bus. Their "Open in jk" action runs `open_command` with the
+    /// day and uid of the event in `JK_DATE` and `JK_UID`. Without it, jk is opened in
+    /// `$TERMINAL`, and the action is only offered if that is set.
+    Desktop { open_command: Option<String> },
+    /// Run a shell command with the event fields in `JK_*` environment variables
+    Command { command: String },
+    /// Write one JSON object per line to stdout or to a file, e.g., a FIFO
+    Json { path: Option<PathBuf> },
+    /// Ring the terminal bell and print the reminder
+    Bell,
+    /// Show the reminder with `tmux display-message`
+    Tmux,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig::Desktop { open_command: None }
+    }
+}
+
+/// Defaults for finding free slots for meetings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotsConfig {
+    /// Local time of day during which meetings may take place, e.g. "09:00-17:00"
+    #[serde(default = "default_working_hours")]
+    pub hours: String,
+
+    /// Free time kept between meetings and other events, e.g. "10m"
+    #[serde(default = "default_buffer")]
+    pub buffer: String,
+
+    /// Whether meetings may take place on weekends
+    #[serde(default)]
+    pub weekends: bool,
+}
+
+impl Default for SlotsConfig {
+    fn default() -> Self {
+        SlotsConfig {
+            hours: default_working_hours(),
+            buffer: default_buffer(),
+            weekends: false,
+        }
+    }
+}
+
+fn default_working_hours() -> String {
+    "09:00-17:00".to_owned()
+}
+
+fn default_buffer() -> String {
+    "0m".to_owned()
+}
+
+/// Settings of the notification daemon jk-notify.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    /// Snooze options offered on notifications: a duration like "5m" or "10 minutes", a
+    /// duration before the start of the event like "1 minute before", or "start"
+    #[serde(default = "default_snoozes")]
+    pub snoozes: Vec<String>,
+
+    /// Backend of calendars without their own, also used for the daily digest
+    #[serde(default)]
+    pub backend: BackendConfig,
+
+    /// Local time of day, e.g. "08:30", at which a digest of the day's events is sent
+    pub digest_time: Option<String>,
+
+    /// Daily local time windows like "22:00-07:00" without reminders of non-urgent events
+    #[serde(default)]
+    pub quiet_hours: Vec<String>,
+
+    /// Calendar whose busy events, e.g. focus blocks, suppress all reminders while they last
+    pub focus_calendar: Option<String>,
+
+    /// Events with a priority of this or higher (i.e., a lower number) ignore quiet hours
+    #[serde(default = "default_urgent_priority")]
+    pub urgent_priority: u8,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            snoozes: default_snoozes(),
+            backend: BackendConfig::default(),
+            digest_time: None,
+            quiet_hours: Vec::new(),
+            focus_calendar: None,
+            urgent_priority: default_urgent_priority(),
+        }
+    }
+}
+
+fn default_urgent_priority() -> u8 {
+    1
+}
+
 fn default_tick_rate() -> Duration {
     Duration::from_secs(60)
 }
@@ -77,6 +234,26 @@ pub struct Config {
     pub notification_headsup_minutes: u32,
 
     pub collections: Vec<CollectionConfig>,
+
+    /// The user's own calendar addresses, used to find their participation in events
+    #[serde(default)]
+    pub addresses: Vec<String>,
+
+    #[serde(default)]
+    pub declined_events: DeclinedEvents,
+
+    #[serde(default)]
+    pub csv_import: CsvImportConfig,
+
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    /// Saved filter queries by name, used as `@name` in other filters
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+
+    #[serde(default)]
+    pub slots: SlotsConfig,
 }
 
 impl Default for Config {
@@ -90,6 +267,12 @@ impl Default for Config {
             tick_rate: Duration::from_secs(60),
             notification_headsup_minutes: default_notification_headsup_minutes(),
             collections: Vec::new(),
+            addresses: Vec::new(),
+            declined_events: DeclinedEvents::default(),
+            csv_import: CsvImportConfig::default(),
+            notify: NotifyConfig::default(),
+            filters: HashMap::new(),
+            slots: SlotsConfig::default(),
         }
     }
 }
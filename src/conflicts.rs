@@ -0,0 +1,90 @@
+//! Conflicts between events, i.e., occurrences that overlap although both block time.
+
+use chrono::{Local, NaiveDate};
+use std::collections::BTreeSet;
+
+use crate::exchange::freebusy::Period;
+use crate::provider::Occurrence;
+
+/// Two overlapping occurrences, the first one beginning no later than the second one.
+#[derive(Clone)]
+pub struct Conflict<'a> {
+    pub first: Occurrence<'a>,
+    pub second: Occurrence<'a>,
+}
+
+impl Conflict<'_> {
+    /// The time during which both occurrences take place.
+    pub fn overlap(&self) -> Period {
+        Period {
+            start: self.second.begin(),
+            end: self.first.end().min(self.second.end()),
+        }
+    }
+
+    /// Whether `occurrence` is one of the conflicting ones.
+    pub fn involves(&self, occurrence: &Occurrence) -> bool {
+        [&self.first, &self.second].iter().any(|conflicting| {
+            conflicting.event.uid() == occurrence.event.uid()
+                && conflicting.calendar == occurrence.calendar
+                && conflicting.begin() == occurrence.begin()
+        })
+    }
+
+    /// The local days on which both occurrences take place.
+    pub fn days(&self) -> BTreeSet<NaiveDate> {
+        let overlap = self.overlap();
+        let first = overlap.start.with_timezone(&Local).date_naive();
+        // The end is not part of the overlap
+        let last = (overlap.end - chrono::Duration::nanoseconds(1))
+            .with_timezone(&Local)
+            .date_naive();
+
+        first.iter_days().take_while(|day| *day <= last).collect()
+    }
+}
+
+/// Lazily finds the conflicts among occurrences sorted by their begin, ordered by the begin of
+/// the later occurrence. Occurrences without duration never conflict.
+pub struct Conflicts<'a, I> {
+    occurrences: I,
+    /// Occurrences that have not ended at the begin of the last one
+    ongoing: Vec<Occurrence<'a>>,
+    /// Conflicts of the last occurrence that have not been returned yet
+    pending: Vec<Conflict<'a>>,
+}
+
+impl<'a, I: Iterator<Item = Occurrence<'a>>> Conflicts<'a, I> {
+    pub fn new(occurrences: I) -> Self {
+        Conflicts {
+            occurrences,
+            ongoing: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = Occurrence<'a>>> Iterator for Conflicts<'a, I> {
+    type Item = Conflict<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let occurrence = self.occurrences.next()?;
+            let begin = occurrence.begin();
+            if begin >= occurrence.end() {
+                continue;
+            }
+
+            self.ongoing.retain(|ongoing| ongoing.end() > begin);
+            // Reversed, so that popping returns them in the order they began
+            self.pending
+                .extend(self.ongoing.iter().rev().map(|ongoing| Conflict {
+                    first: ongoing.clone(),
+                    second: occurrence.clone(),
+                }));
+            self.ongoing.push(occurrence);
+        }
+
+        self.pending.pop()
+    }
+}
@@ -0,0 +1,469 @@
+//! Import of events from CSV files, e.g., rotations planned in a spreadsheet.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::agenda::Agenda;
+use crate::config::CsvImportConfig;
+use crate::provider::{parse_duration, Error, ErrorKind, NewEvent, Result};
+
+const DEFAULT_DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+];
+
+const DEFAULT_DATE_FORMATS: &[&str] = &["%Y-%m-%d"];
+
+/// Event fields a column can be mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Title,
+    Start,
+    End,
+    Duration,
+    Allday,
+    Description,
+    Location,
+    Calendar,
+}
+
+impl Field {
+    const ALL: [Field; 8] = [
+        Field::Title,
+        Field::Start,
+        Field::End,
+        Field::Duration,
+        Field::Allday,
+        Field::Description,
+        Field::Location,
+        Field::Calendar,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::Start => "start",
+            Field::End => "end",
+            Field::Duration => "duration",
+            Field::Allday => "allday",
+            Field::Description => "description",
+            Field::Location => "location",
+            Field::Calendar => "calendar",
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let normalized = s
+            .trim()
+            .to_ascii_lowercase()
+            .replace(&['-', '_', ' '][..], "");
+        Field::ALL
+            .iter()
+            .find(|field| field.name() == normalized)
+            .copied()
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::ParseError,
+                    &format!("Unknown event field '{}'", s),
+                )
+            })
+    }
+}
+
+/// A column of the CSV file, either referred to by its header or by its 1-based index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.parse::<usize>() {
+            Ok(0) => Err(Error::new(
+                ErrorKind::ParseError,
+                "Column indices start at 1",
+            )),
+            Ok(idx) => Ok(Column::Index(idx)),
+            Err(_) => Ok(Column::Name(s.to_owned())),
+        }
+    }
+}
+
+/// Maps event fields to columns. Fields without an explicit mapping are looked up by their
+/// name in the header.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnMapping {
+    columns: HashMap<Field, Column>,
+}
+
+impl ColumnMapping {
+    /// Parse a mapping like `title=Shift,start=From,end=3`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut mapping = ColumnMapping::default();
+
+        for entry in spec.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (field, column) = entry.split_once('=').ok_or_else(|| {
+                Error::new(
+                    ErrorKind::ParseError,
+                    &format!("Invalid column mapping '{}', expected FIELD=COLUMN", entry),
+                )
+            })?;
+            mapping.set(field.parse()?, column.parse()?);
+        }
+
+        Ok(mapping)
+    }
+
+    pub fn from_config(columns: &HashMap<String, String>) -> Result<Self> {
+        let mut mapping = ColumnMapping::default();
+        for (field, column) in columns {
+            mapping.set(field.parse()?, column.parse()?);
+        }
+
+        Ok(mapping)
+    }
+
+    pub fn set(&mut self, field: Field, column: Column) {
+        self.columns.insert(field, column);
+    }
+
+    /// Add all mappings of `other`, overriding existing ones.
+    pub fn extend(&mut self, other: ColumnMapping) {
+        self.columns.extend(other.columns);
+    }
+
+    /// Resolve the mapping to 0-based column indices.
+    fn resolve(&self, headers: Option<&csv::StringRecord>) -> Result<HashMap<Field, usize>> {
+        let find_header = |name: &str| {
+            headers.and_then(|headers| {
+                headers
+                    .iter()
+                    .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+            })
+        };
+
+        let mut resolved = HashMap::new();
+        for field in Field::ALL.iter() {
+            let idx = match self.columns.get(field) {
+                Some(Column::Index(idx)) => Some(idx - 1),
+                Some(Column::Name(name)) => Some(find_header(name).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::ParseError,
+                        &format!("Column '{}' for {} not found", name, field.name()),
+                    )
+                })?),
+                None => find_header(field.name()),
+            };
+
+            if let Some(idx) = idx {
+                resolved.insert(*field, idx);
+            }
+        }
+
+        if !resolved.contains_key(&Field::Start) {
+            return Err(Error::new(
+                ErrorKind::ParseError,
+                "No column is mapped to the start of events",
+            ));
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// Options controlling how rows are turned into events.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub mapping: ColumnMapping,
+    pub datetime_formats: Vec<String>,
+    pub date_formats: Vec<String>,
+    /// Time zone of all times in the file. Defaults to the time zone of the target calendar.
+    pub tz: Option<Tz>,
+    pub delimiter: u8,
+    pub has_headers: bool,
+    /// Calendar for rows without a calendar column
+    pub calendar: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            mapping: ColumnMapping::default(),
+            datetime_formats: DEFAULT_DATETIME_FORMATS
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+            date_formats: DEFAULT_DATE_FORMATS.iter().map(|f| f.to_string()).collect(),
+            tz: None,
+            delimiter: b',',
+            has_headers: true,
+            calendar: None,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn from_config(config: &CsvImportConfig) -> Result<Self> {
+        let mut options = CsvOptions {
+            mapping: ColumnMapping::from_config(&config.columns)?,
+            has_headers: !config.no_headers,
+            calendar: config.calendar.clone(),
+            ..Default::default()
+        };
+
+        if !config.datetime_formats.is_empty() {
+            options.datetime_formats = config.datetime_formats.clone();
+        }
+        if !config.date_formats.is_empty() {
+            options.date_formats = config.date_formats.clone();
+        }
+        if let Some(tz) = &config.timezone {
+            options.set_timezone(tz)?;
+        }
+        if let Some(delimiter) = config.delimiter {
+            options.set_delimiter(delimiter)?;
+        }
+
+        Ok(options)
+    }
+
+    pub fn set_timezone(&mut self, tz: &str) -> Result<()> {
+        self.tz = Some(
+            tz.parse::<Tz>()
+                .map_err(|err| Error::new(ErrorKind::ParseError, &err))?,
+        );
+        Ok(())
+    }
+
+    pub fn set_delimiter(&mut self, delimiter: char) -> Result<()> {
+        if !delimiter.is_ascii() {
+            return Err(Error::new(
+                ErrorKind::ParseError,
+                "The delimiter must be an ASCII character",
+            ));
+        }
+        self.delimiter = delimiter as u8;
+        Ok(())
+    }
+
+    fn parse_datetime(&self, value: &str) -> Option<NaiveDateTime> {
+        self.datetime_formats
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    }
+
+    fn parse_date(&self, value: &str) -> Option<NaiveDate> {
+        self.date_formats
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+    }
+}
+
+/// An error in a single row of the imported file.
+#[derive(Debug)]
+pub struct RowError {
+    /// Line of the row in the file, starting at 1
+    pub line: u64,
+    pub error: Error,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+/// Result of a CSV import: the number of created events and all rows that were skipped.
+#[derive(Debug, Default)]
+pub struct CsvImport {
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Date or datetime found in a cell.
+enum Moment {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+fn parse_flag(value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "" | "0" | "false" | "no" | "n" => Ok(false),
+        "1" | "true" | "yes" | "y" | "x" => Ok(true),
+        _ => Err(Error::new(
+            ErrorKind::ParseError,
+            &format!("Invalid all-day flag '{}'", value),
+        )),
+    }
+}
+
+/// Turn a single row into a new event with all times in the time zone `tz`.
+fn parse_row(
+    record: &csv::StringRecord,
+    columns: &HashMap<Field, usize>,
+    options: &CsvOptions,
+    tz: Tz,
+) -> Result<NewEvent<Tz>> {
+    let cell = |field| {
+        columns
+            .get(&field)
+            .and_then(|idx| record.get(*idx))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+
+    let moment = |value: &str| {
+        options
+            .parse_datetime(value)
+            .map(Moment::DateTime)
+            .or_else(|| options.parse_date(value).map(Moment::Date))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::DateParse,
+                    &format!("'{}' matches none of the configured formats", value),
+                )
+            })
+    };
+
+    let localize = |dt: NaiveDateTime| {
+        tz.from_local_datetime(&dt).earliest().ok_or_else(|| {
+            Error::new(
+                ErrorKind::TimeParse,
+                &format!("{} does not exist in time zone {:?}", dt, tz),
+            )
+        })
+    };
+
+    let start_value = cell(Field::Start)
+        .ok_or_else(|| Error::new(ErrorKind::EventMissingKey, "Missing start"))?;
+    let start = moment(start_value)?;
+
+    let allday = match cell(Field::Allday) {
+        Some(flag) => parse_flag(flag)?,
+        None => matches!(start, Moment::Date(_)),
+    };
+
+    let start = match start {
+        Moment::Date(date) => date.and_hms_opt(0, 0, 0).unwrap(),
+        Moment::DateTime(dt) => dt,
+    };
+
+    let mut event = NewEvent::new(localize(start)?);
+    event.set_allday(allday);
+
+    if let Some(end) = cell(Field::End) {
+        let end = match moment(end)? {
+            // End dates of all-day events are inclusive, as usual in spreadsheets
+            Moment::Date(date) if allday => date.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            Moment::Date(date) => date.and_hms_opt(0, 0, 0).unwrap(),
+            Moment::DateTime(dt) => dt,
+        };
+
+        if end < start {
+            return Err(Error::new(
+                ErrorKind::EventParse,
+                "The event ends before it starts",
+            ));
+        }
+        event.end = Some(localize(end)?);
+    } else if let Some(duration) = cell(Field::Duration) {
+        let duration = parse_duration(duration)?;
+        if duration < Duration::zero() {
+            return Err(Error::new(
+                ErrorKind::DurationParse,
+                "Durations must not be negative",
+            ));
+        }
+        event.set_duration(duration);
+    }
+
+    if let Some(title) = cell(Field::Title) {
+        event.set_title(title);
+    }
+    if let Some(description) = cell(Field::Description) {
+        event.set_description(description);
+    }
+    if let Some(location) = cell(Field::Location) {
+        event.set_location(location);
+    }
+
+    Ok(event)
+}
+
+/// Import all rows of `input` as events. Rows are added to the calendar named in their calendar
+/// column or, if they have none, to `options.calendar`. Invalid rows are reported in the result
+/// and do not abort the import.
+pub fn import(agenda: &mut Agenda, options: &CsvOptions, input: &str) -> Result<CsvImport> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(options.has_headers)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+
+    let headers = if options.has_headers {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let columns = options.mapping.resolve(headers.as_ref())?;
+
+    let mut result = CsvImport::default();
+
+    for record in reader.records() {
+        let (line, outcome) = match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |pos| pos.line());
+                let calendar_name = columns
+                    .get(&Field::Calendar)
+                    .and_then(|idx| record.get(*idx))
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_owned)
+                    .or_else(|| options.calendar.clone());
+
+                let outcome = calendar_name
+                    .ok_or_else(|| {
+                        Error::new(ErrorKind::CalendarParse, "No calendar given for the event")
+                    })
+                    .and_then(|name| {
+                        agenda.calendar_by_name_mut(&name).ok_or_else(|| {
+                            Error::new(
+                                ErrorKind::CalendarParse,
+                                &format!("Calendar '{}' not found", name),
+                            )
+                        })
+                    })
+                    .and_then(|calendar| {
+                        let tz = options.tz.unwrap_or(*calendar.tz());
+                        calendar.add_event(parse_row(&record, &columns, options, tz)?)
+                    });
+
+                (line, outcome)
+            }
+            Err(e) => (
+                e.position().map_or(0, |pos| pos.line()),
+                Err(Error::from(e)),
+            ),
+        };
+
+        match outcome {
+            Ok(()) => result.imported += 1,
+            Err(error) => result.errors.push(RowError { line, error }),
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,294 @@
+//! Free/busy time of calendars, which shares availability without sharing details of events.
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
+use ical::parser::ical::component::{IcalCalendar, IcalFreeBusy};
+use ical::property::Property;
+use serde::Serialize;
+use std::fmt;
+use std::ops::Bound;
+use std::str::FromStr;
+
+use super::selected_calendars;
+use crate::agenda::Agenda;
+use crate::provider::ical::datetime::{generate_timestamp, IcalDuration};
+use crate::provider::ical::itip::Method;
+use crate::provider::ical::value::param_value;
+use crate::provider::ical::{calendar_properties, parse_calendars, ser};
+use crate::provider::merge::BoxedOccurrences;
+use crate::provider::{Error, ErrorKind, Eventlike, MergedOccurrences, Result, Transparency};
+
+const ICAL_UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Formats free/busy time can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreeBusyFormat {
+    Text,
+    Ical,
+    Json,
+}
+
+impl FromStr for FreeBusyFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(FreeBusyFormat::Text),
+            "ics" | "ical" | "icalendar" => Ok(FreeBusyFormat::Ical),
+            "json" => Ok(FreeBusyFormat::Json),
+            _ => Err(Error::new(
+                ErrorKind::ParseError,
+                &format!("Unknown format '{}'", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FreeBusyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            FreeBusyFormat::Text => "text",
+            FreeBusyFormat::Ical => "ics",
+            FreeBusyFormat::Json => "json",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// A period of time, which does not contain its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Period {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = (
+            self.start.with_timezone(&Local),
+            self.end.with_timezone(&Local),
+        );
+        let end_format = if start.date_naive() == end.date_naive() {
+            "%H:%M"
+        } else {
+            "%Y-%m-%d %H:%M"
+        };
+
+        write!(
+            f,
+            "{} - {}",
+            start.format("%Y-%m-%d %H:%M"),
+            end.format(end_format)
+        )
+    }
+}
+
+/// Parse a period of a FREEBUSY property, given by its start and either its end or duration.
+fn parse_period(value: &str) -> Result<Period> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::ParseError,
+            &format!("Invalid free/busy period '{}'", value),
+        )
+    };
+
+    let (start, end) = value.split_once('/').ok_or_else(invalid)?;
+    let start = Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(start, ICAL_UTC_FORMAT)?);
+    let end = match NaiveDateTime::parse_from_str(end, ICAL_UTC_FORMAT) {
+        Ok(end) => Utc.from_utc_datetime(&end),
+        Err(_) => start + Duration::from(end.parse::<IcalDuration>().map_err(|_| invalid())?),
+    };
+
+    Ok(Period { start, end })
+}
+
+/// The busy periods of all VFREEBUSY components of the iCalendar document `input`, e.g., as
+/// published by other people. Periods marked free are left out.
+pub fn parse_busy(input: &str) -> Result<Vec<Period>> {
+    let mut busy = Vec::new();
+
+    for calendar in parse_calendars(input)? {
+        for property in calendar
+            .free_busys
+            .iter()
+            .flat_map(|free_busy| free_busy.properties.iter())
+            .filter(|property| property.name == "FREEBUSY")
+        {
+            let free = param_value(property, "FBTYPE")
+                .map_or(false, |fbtype| fbtype.eq_ignore_ascii_case("FREE"));
+            if free {
+                continue;
+            }
+
+            for period in property.value.iter().flat_map(|value| value.split(',')) {
+                busy.push(parse_period(period.trim())?);
+            }
+        }
+    }
+
+    Ok(busy)
+}
+
+/// Whether the event blocks time, i.e., it is neither transparent nor cancelled, nor declined
+/// by the calendar user owning one of `addresses`.
+pub fn blocks_time(event: &dyn Eventlike, addresses: &[String]) -> bool {
+    event.transparency() == Transparency::Opaque
+        && !event.is_cancelled()
+        && !event.is_declined_by(addresses)
+}
+
+/// The busy time of calendars between `start` and `end`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FreeBusy {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Sorted periods, which neither overlap nor touch each other
+    pub busy: Vec<Period>,
+}
+
+impl FreeBusy {
+    /// The busy time between `start` and `end` of the calendars named `calendars`, or of all
+    /// calendars if none is named. Events declined by the calendar user owning one of
+    /// `addresses` do not count.
+    pub fn compute<'a>(
+        agenda: &'a Agenda,
+        calendars: &[String],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        addresses: &[String],
+    ) -> Result<Self> {
+        let occurrences =
+            MergedOccurrences::new(selected_calendars(agenda, calendars)?.into_iter().map(
+                |calendar| -> BoxedOccurrences<'a> {
+                    Box::new(calendar.as_calendar().occurrences_where(
+                        Bound::Included(start),
+                        Bound::Excluded(end),
+                        &|event: &dyn Eventlike| blocks_time(event, addresses),
+                    ))
+                },
+            ));
+
+        let mut freebusy = FreeBusy {
+            start,
+            end,
+            busy: Vec::new(),
+        };
+        freebusy.add_busy(occurrences.map(|occurrence| Period {
+            start: occurrence.begin(),
+            end: occurrence.end(),
+        }));
+
+        Ok(freebusy)
+    }
+
+    /// Add busy periods, e.g., of other people. Periods are clipped to the range of the
+    /// free/busy time and merged with the present ones.
+    pub fn add_busy(&mut self, periods: impl IntoIterator<Item = Period>) {
+        let (start, end) = (self.start, self.end);
+        let mut periods = periods
+            .into_iter()
+            .map(|period| Period {
+                start: period.start.max(start),
+                end: period.end.min(end),
+            })
+            // Periods without duration do not block any time
+            .filter(|period| period.start < period.end)
+            .chain(self.busy.drain(..))
+            .collect::<Vec<_>>();
+        periods.sort_by_key(|period| period.start);
+
+        for period in periods {
+            // Periods are sorted by their start, so only the last one can be extended
+            match self.busy.last_mut() {
+                Some(last) if period.start <= last.end => last.end = last.end.max(period.end),
+                _ => self.busy.push(period),
+            }
+        }
+    }
+
+    /// A calendar object publishing the busy time as VFREEBUSY component (RFC 5545, section
+    /// 3.6.4) on behalf of `organizer`.
+    pub fn to_ical(&self, organizer: Option<&str>) -> IcalCalendar {
+        let property = |name: &str, value: String| Property {
+            name: name.to_owned(),
+            params: None,
+            value: Some(value),
+        };
+
+        let mut free_busy = IcalFreeBusy::new();
+        free_busy.properties.push(property(
+            "UID",
+            uuid::Uuid::new_v4().hyphenated().to_string(),
+        ));
+        free_busy
+            .properties
+            .push(property("DTSTAMP", generate_timestamp()));
+        if let Some(organizer) = organizer {
+            let address = if organizer.contains(':') {
+                organizer.to_owned()
+            } else {
+                format!("mailto:{}", organizer)
+            };
+            free_busy.properties.push(property("ORGANIZER", address));
+        }
+        free_busy.properties.push(property(
+            "DTSTART",
+            self.start.format(ICAL_UTC_FORMAT).to_string(),
+        ));
+        free_busy.properties.push(property(
+            "DTEND",
+            self.end.format(ICAL_UTC_FORMAT).to_string(),
+        ));
+        if !self.busy.is_empty() {
+            let periods = self
+                .busy
+                .iter()
+                .map(|period| {
+                    format!(
+                        "{}/{}",
+                        period.start.format(ICAL_UTC_FORMAT),
+                        period.end.format(ICAL_UTC_FORMAT)
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            free_busy.properties.push(Property {
+                name: "FREEBUSY".to_owned(),
+                params: Some(vec![("FBTYPE".to_owned(), vec!["BUSY".to_owned()])]),
+                value: Some(periods.join(",")),
+            });
+        }
+
+        let mut calendar = IcalCalendar::new();
+        calendar.properties = calendar_properties();
+        calendar
+            .properties
+            .push(property("METHOD", Method::Publish.to_string()));
+        calendar.free_busys.push(free_busy);
+        calendar
+    }
+
+    /// Write the busy time in `format`. `organizer` is the address the iCalendar format
+    /// publishes the busy time for.
+    pub fn render(&self, format: FreeBusyFormat, organizer: Option<&str>) -> Result<String> {
+        match format {
+            FreeBusyFormat::Text => {
+                let mut text = format!(
+                    "Busy between {} and {}:\n",
+                    self.start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    self.end.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                );
+                if self.busy.is_empty() {
+                    text += "  never\n";
+                }
+                for period in self.busy.iter() {
+                    text += &format!("  {}\n", period);
+                }
+                Ok(text)
+            }
+            FreeBusyFormat::Ical => ser::to_string(&self.to_ical(organizer)),
+            FreeBusyFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| Error::new(ErrorKind::SerializeError, &e.to_string())),
+        }
+    }
+}
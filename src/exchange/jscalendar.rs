@@ -0,0 +1,984 @@
+//! Conversion between jackal events and JSCalendar (RFC 8984) `Event` objects.
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone};
+use chrono_tz::{OffsetName, Tz};
+use ical::parser::ical::component::{IcalAlarm, IcalCalendar, IcalEvent};
+use ical::property::Property;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::provider::ical::datetime::{generate_timestamp, IcalDateTime, IcalDuration};
+use crate::provider::ical::value::{escape_text, param_value, split_text_list, unescape_text};
+use crate::provider::ical::{self, calendar_properties};
+use crate::provider::{Error, ErrorKind, Eventlike, Result, TimeSpan};
+
+const LOCAL_DATETIME_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S";
+const UTC_DATETIME_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%SZ";
+
+fn event_type() -> String {
+    "Event".to_owned()
+}
+
+fn recurrence_rule_type() -> String {
+    "RecurrenceRule".to_owned()
+}
+
+fn nday_type() -> String {
+    "NDay".to_owned()
+}
+
+fn location_type() -> String {
+    "Location".to_owned()
+}
+
+fn link_type() -> String {
+    "Link".to_owned()
+}
+
+fn participant_type() -> String {
+    "Participant".to_owned()
+}
+
+fn alert_type() -> String {
+    "Alert".to_owned()
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A JSCalendar `Event` object. Only the properties jackal is able to represent are modelled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsEvent {
+    #[serde(rename = "@type", default = "event_type")]
+    pub object_type: String,
+    pub uid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub start: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub show_without_time: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recurrence_rules: Vec<RecurrenceRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_recurrence_rules: Vec<RecurrenceRule>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub recurrence_overrides: BTreeMap<String, Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locations: BTreeMap<String, Location>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<String, Link>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub participants: BTreeMap<String, Participant>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub alerts: BTreeMap<String, Alert>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keywords: BTreeMap<String, bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_busy_status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurrenceRule {
+    #[serde(rename = "@type", default = "recurrence_rule_type")]
+    pub object_type: String,
+    pub frequency: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rscale: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_day_of_week: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_day: Vec<NDay>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_month_day: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_month: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_year_day: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_week_no: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_hour: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_minute: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_second: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_set_position: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NDay {
+    #[serde(rename = "@type", default = "nday_type")]
+    pub object_type: String,
+    pub day: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nth_of_period: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    #[serde(rename = "@type", default = "location_type")]
+    pub object_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    #[serde(rename = "@type", default = "link_type")]
+    pub object_type: String,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Participant {
+    #[serde(rename = "@type", default = "participant_type")]
+    pub object_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub send_to: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roles: BTreeMap<String, bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub participation_status: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub expect_reply: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    #[serde(rename = "@type", default = "alert_type")]
+    pub object_type: String,
+    pub trigger: Trigger,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "@type")]
+pub enum Trigger {
+    #[serde(rename_all = "camelCase")]
+    OffsetTrigger {
+        offset: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        relative_to: Option<String>,
+    },
+    AbsoluteTrigger {
+        when: String,
+    },
+}
+
+/// A JSCalendar `Group` object, used to exchange several events at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Group {
+    #[serde(rename = "@type")]
+    object_type: String,
+    entries: Vec<JsEvent>,
+}
+
+fn properties<'a>(event: &'a IcalEvent, name: &'a str) -> impl Iterator<Item = &'a Property> {
+    event.properties.iter().filter(move |p| p.name == name)
+}
+
+fn property_value<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a str> {
+    properties(event, name)
+        .next()
+        .and_then(|p| p.value.as_deref())
+}
+
+/// Convert all values of a DATE(-TIME) property into local datetimes of the time zone `tz`.
+fn local_datetimes(property: &Property, tz: &Tz) -> Vec<String> {
+    let value = property.value.as_deref().unwrap_or_default();
+
+    value
+        .split(',')
+        .filter_map(|single| {
+            let single = Property {
+                name: property.name.clone(),
+                params: property.params.clone(),
+                value: Some(single.to_owned()),
+            };
+
+            match IcalDateTime::try_from(&single) {
+                Ok(IcalDateTime::Date(date)) => Some(date.and_hms_opt(0, 0, 0).unwrap()),
+                Ok(IcalDateTime::Floating(dt)) => Some(dt),
+                Ok(IcalDateTime::Utc(dt)) => Some(dt.with_timezone(tz).naive_local()),
+                Ok(IcalDateTime::Local(dt)) => Some(dt.with_timezone(tz).naive_local()),
+                Err(e) => {
+                    log::warn!("Ignoring invalid {}: {}", property.name, e);
+                    None
+                }
+            }
+        })
+        .map(|dt| dt.format(LOCAL_DATETIME_FORMAT).to_string())
+        .collect()
+}
+
+impl NDay {
+    /// Parse a single BYDAY value like `-1SU`.
+    fn from_ical(value: &str) -> Result<Self> {
+        let (nth, day) = value.split_at(value.len().saturating_sub(2));
+        let nth_of_period = if nth.is_empty() {
+            None
+        } else {
+            Some(
+                nth.parse()
+                    .map_err(|_| Error::new(ErrorKind::RecurRuleParse, value))?,
+            )
+        };
+
+        Ok(NDay {
+            object_type: nday_type(),
+            day: day.to_ascii_lowercase(),
+            nth_of_period,
+        })
+    }
+}
+
+impl RecurrenceRule {
+    /// Parse the value of an RRULE (or EXRULE) property. UNTIL is converted into a local
+    /// datetime of the time zone `tz`.
+    pub fn from_ical(value: &str, tz: &Tz) -> Result<Self> {
+        let mut rule = RecurrenceRule {
+            object_type: recurrence_rule_type(),
+            ..Default::default()
+        };
+
+        fn numbers<T: std::str::FromStr>(values: &str) -> Result<Vec<T>> {
+            values
+                .split(',')
+                .map(|v| {
+                    v.parse::<T>()
+                        .map_err(|_| Error::new(ErrorKind::RecurRuleParse, v))
+                })
+                .collect()
+        }
+
+        for part in value.trim_start_matches("RRULE:").split(';') {
+            let (key, values) = part
+                .split_once('=')
+                .ok_or_else(|| Error::new(ErrorKind::RecurRuleParse, part))?;
+
+            match key {
+                "FREQ" => rule.frequency = values.to_ascii_lowercase(),
+                "INTERVAL" => rule.interval = numbers(values)?.first().copied(),
+                "COUNT" => rule.count = numbers(values)?.first().copied(),
+                "UNTIL" => {
+                    let until = Property {
+                        name: "UNTIL".to_owned(),
+                        params: None,
+                        value: Some(values.to_owned()),
+                    };
+                    rule.until = local_datetimes(&until, tz).into_iter().next();
+                }
+                "WKST" => rule.first_day_of_week = Some(values.to_ascii_lowercase()),
+                "RSCALE" => rule.rscale = Some(values.to_ascii_lowercase()),
+                "SKIP" => rule.skip = Some(values.to_ascii_lowercase()),
+                "BYDAY" => {
+                    rule.by_day = values
+                        .split(',')
+                        .map(NDay::from_ical)
+                        .collect::<Result<_>>()?
+                }
+                "BYMONTHDAY" => rule.by_month_day = numbers(values)?,
+                "BYMONTH" => rule.by_month = values.split(',').map(str::to_owned).collect(),
+                "BYYEARDAY" => rule.by_year_day = numbers(values)?,
+                "BYWEEKNO" => rule.by_week_no = numbers(values)?,
+                "BYHOUR" => rule.by_hour = numbers(values)?,
+                "BYMINUTE" => rule.by_minute = numbers(values)?,
+                "BYSECOND" => rule.by_second = numbers(values)?,
+                "BYSETPOS" => rule.by_set_position = numbers(values)?,
+                _ => log::warn!("Ignoring unknown recurrence rule part '{}'", part),
+            }
+        }
+
+        Ok(rule)
+    }
+
+    /// Create the value of an RRULE property. UNTIL is given in UTC if the time zone is known.
+    pub fn to_ical(&self, tz: Option<&Tz>) -> Result<String> {
+        fn join<T: ToString>(values: &[T]) -> String {
+            values
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+
+        let mut parts = vec![format!("FREQ={}", self.frequency.to_ascii_uppercase())];
+
+        if let Some(interval) = self.interval {
+            parts.push(format!("INTERVAL={}", interval));
+        }
+        if let Some(count) = self.count {
+            parts.push(format!("COUNT={}", count));
+        }
+        if let Some(until) = &self.until {
+            let until = NaiveDateTime::parse_from_str(until, LOCAL_DATETIME_FORMAT)?;
+            let until = match tz {
+                Some(tz) => tz
+                    .from_local_datetime(&until)
+                    .earliest()
+                    .ok_or_else(|| Error::new(ErrorKind::TimeParse, "Invalid UNTIL"))?
+                    .naive_utc()
+                    .format("%Y%m%dT%H%M%SZ"),
+                None => until.format("%Y%m%dT%H%M%S"),
+            };
+            parts.push(format!("UNTIL={}", until));
+        }
+        if let Some(wkst) = &self.first_day_of_week {
+            parts.push(format!("WKST={}", wkst.to_ascii_uppercase()));
+        }
+        if let Some(rscale) = &self.rscale {
+            parts.push(format!("RSCALE={}", rscale.to_ascii_uppercase()));
+        }
+        if let Some(skip) = &self.skip {
+            parts.push(format!("SKIP={}", skip.to_ascii_uppercase()));
+        }
+        if !self.by_day.is_empty() {
+            let days = self
+                .by_day
+                .iter()
+                .map(|nday| {
+                    format!(
+                        "{}{}",
+                        nday.nth_of_period.map_or(String::new(), |n| n.to_string()),
+                        nday.day.to_ascii_uppercase()
+                    )
+                })
+                .collect::<Vec<_>>();
+            parts.push(format!("BYDAY={}", days.join(",")));
+        }
+
+        for (key, values) in [
+            ("BYMONTHDAY", join(&self.by_month_day)),
+            ("BYMONTH", join(&self.by_month)),
+            ("BYYEARDAY", join(&self.by_year_day)),
+            ("BYWEEKNO", join(&self.by_week_no)),
+            ("BYHOUR", join(&self.by_hour)),
+            ("BYMINUTE", join(&self.by_minute)),
+            ("BYSECOND", join(&self.by_second)),
+            ("BYSETPOS", join(&self.by_set_position)),
+        ] {
+            if !values.is_empty() {
+                parts.push(format!("{}={}", key, values));
+            }
+        }
+
+        Ok(parts.join(";"))
+    }
+}
+
+fn participant_from_ical(property: &Property) -> Participant {
+    let address = property.value.clone().unwrap_or_default();
+    let param = |name| param_value(property, name).map(str::to_owned);
+
+    let mut roles = BTreeMap::new();
+    match param("ROLE").as_deref() {
+        Some("CHAIR") => {
+            roles.insert("chair".to_owned(), true);
+            roles.insert("attendee".to_owned(), true);
+        }
+        Some("OPT-PARTICIPANT") => {
+            roles.insert("optional".to_owned(), true);
+            roles.insert("attendee".to_owned(), true);
+        }
+        Some("NON-PARTICIPANT") => {
+            roles.insert("informational".to_owned(), true);
+        }
+        _ => {
+            roles.insert("attendee".to_owned(), true);
+        }
+    }
+
+    Participant {
+        object_type: participant_type(),
+        name: param("CN"),
+        email: address
+            .strip_prefix("mailto:")
+            .or_else(|| address.strip_prefix("MAILTO:"))
+            .map(str::to_owned),
+        send_to: BTreeMap::from([("imip".to_owned(), address.clone())]),
+        kind: param("CUTYPE").map(|cutype| cutype.to_ascii_lowercase()),
+        roles,
+        participation_status: param("PARTSTAT").map(|partstat| partstat.to_ascii_lowercase()),
+        expect_reply: param("RSVP").map_or(false, |rsvp| rsvp.eq_ignore_ascii_case("TRUE")),
+    }
+}
+
+fn alert_from_ical(alarm: &IcalAlarm) -> Option<Alert> {
+    let trigger_property = alarm.properties.iter().find(|p| p.name == "TRIGGER")?;
+    let trigger_value = trigger_property.value.clone()?;
+
+    let trigger = if param_value(trigger_property, "VALUE") == Some("DATE-TIME") {
+        let when = IcalDateTime::try_from(trigger_property).ok()?;
+        Trigger::AbsoluteTrigger {
+            when: when
+                .as_datetime(&chrono::Utc)
+                .format(UTC_DATETIME_FORMAT)
+                .to_string(),
+        }
+    } else {
+        Trigger::OffsetTrigger {
+            offset: trigger_value.trim_start_matches('+').to_owned(),
+            relative_to: match param_value(trigger_property, "RELATED") {
+                Some("END") => Some("end".to_owned()),
+                _ => None,
+            },
+        }
+    };
+
+    let action = alarm
+        .properties
+        .iter()
+        .find(|p| p.name == "ACTION")
+        .and_then(|p| p.value.as_deref())
+        .map(|action| match action {
+            "EMAIL" => "email".to_owned(),
+            _ => "display".to_owned(),
+        });
+
+    Some(Alert {
+        object_type: alert_type(),
+        trigger,
+        action,
+    })
+}
+
+/// Numbered ids as used for the id maps of JSCalendar objects.
+fn numbered<T>(values: impl IntoIterator<Item = T>) -> BTreeMap<String, T> {
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| ((idx + 1).to_string(), value))
+        .collect()
+}
+
+/// Properties of an event that the overrides of single instances cannot patch.
+const UNPATCHABLE: [&str; 5] = [
+    "@type",
+    "uid",
+    "recurrenceRules",
+    "excludedRecurrenceRules",
+    "recurrenceOverrides",
+];
+
+fn to_object(event: &JsEvent) -> Map<String, Value> {
+    match serde_json::to_value(event) {
+        Ok(Value::Object(object)) => object,
+        _ => Map::new(),
+    }
+}
+
+/// The `PatchObject` turning `base` into `instance`. Only top-level properties are patched.
+fn diff(base: &Map<String, Value>, instance: &Map<String, Value>) -> Map<String, Value> {
+    let patchable = |key: &String| !UNPATCHABLE.contains(&key.as_str());
+
+    let mut patch = instance
+        .iter()
+        .filter(|(key, value)| patchable(key) && base.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<Map<_, _>>();
+
+    for key in base.keys().filter(|key| patchable(key)) {
+        if !instance.contains_key(key) {
+            patch.insert(key.clone(), Value::Null);
+        }
+    }
+
+    patch
+}
+
+/// Apply a `PatchObject`, whose keys are JSON pointers into `object`. Null values remove the
+/// property they point to.
+fn apply_patch(object: &mut Value, patch: &Map<String, Value>) -> Result<()> {
+    for (pointer, value) in patch {
+        let invalid = || {
+            Error::new(
+                ErrorKind::ParseError,
+                &format!("Invalid patch '{}'", pointer),
+            )
+        };
+
+        let mut segments = pointer
+            .strip_prefix('/')
+            .unwrap_or(pointer)
+            .split('/')
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect::<Vec<_>>();
+        let last = segments.pop().ok_or_else(invalid)?;
+
+        let mut target = &mut *object;
+        for segment in segments {
+            target = target
+                .as_object_mut()
+                .ok_or_else(invalid)?
+                .entry(segment)
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+
+        let target = target.as_object_mut().ok_or_else(invalid)?;
+        if value.is_null() {
+            target.remove(&last);
+        } else {
+            target.insert(last, value.clone());
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert an event into a JSCalendar `Event`. Recurrence rules, exceptions and all properties
+/// jackal does not interpret itself are taken from the underlying iCalendar event. Modified
+/// instances become patches in `recurrenceOverrides`.
+pub fn from_event(event: &ical::Event) -> JsEvent {
+    let ical_event = event.ical_event();
+    let tz = *event.tz();
+    let first = event.occurrence_rule().first();
+
+    let (start, time_zone, duration, show_without_time) = match &first {
+        TimeSpan::Allday(begin, end, _) => (
+            begin.and_hms_opt(0, 0, 0).unwrap(),
+            None,
+            end.map_or(Duration::days(1), |end| end - *begin),
+            true,
+        ),
+        ts => (
+            ts.begin().naive_local(),
+            Some(match tz {
+                Tz::UTC => "Etc/UTC".to_owned(),
+                _ => ts.begin().offset().tz_id().to_owned(),
+            }),
+            ts.duration(),
+            false,
+        ),
+    };
+
+    let rules = |name| {
+        properties(ical_event, name)
+            .filter_map(|p| p.value.as_deref())
+            .filter_map(|value| match RecurrenceRule::from_ical(value, &tz) {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    log::warn!("Ignoring invalid recurrence rule: {}", e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut recurrence_overrides = BTreeMap::new();
+    for rdate in properties(ical_event, "RDATE") {
+        for dt in local_datetimes(rdate, &tz) {
+            recurrence_overrides.insert(dt, Map::new());
+        }
+    }
+    for exdate in properties(ical_event, "EXDATE") {
+        for dt in local_datetimes(exdate, &tz) {
+            recurrence_overrides.insert(
+                dt,
+                [("excluded".to_owned(), Value::Bool(true))]
+                    .into_iter()
+                    .collect(),
+            );
+        }
+    }
+
+    let mut participants = properties(ical_event, "ATTENDEE")
+        .map(participant_from_ical)
+        .collect::<Vec<_>>();
+
+    if let Some(organizer) = properties(ical_event, "ORGANIZER").next() {
+        let address = organizer.value.as_deref().unwrap_or_default();
+        if let Some(attendee) = participants
+            .iter_mut()
+            .find(|p| p.send_to.get("imip").map(String::as_str) == Some(address))
+        {
+            attendee.roles.insert("owner".to_owned(), true);
+        } else {
+            let mut owner = participant_from_ical(organizer);
+            owner.roles = BTreeMap::from([("owner".to_owned(), true)]);
+            participants.push(owner);
+        }
+    }
+
+    let mut js_event = JsEvent {
+        object_type: event_type(),
+        uid: event.uid().to_owned(),
+        title: property_value(ical_event, "SUMMARY").map(str::to_owned),
+        description: property_value(ical_event, "DESCRIPTION").map(str::to_owned),
+        start: start.format(LOCAL_DATETIME_FORMAT).to_string(),
+        time_zone,
+        duration: if duration.is_zero() {
+            None
+        } else {
+            Some(IcalDuration::from(duration).to_string())
+        },
+        show_without_time,
+        recurrence_rules: rules("RRULE"),
+        excluded_recurrence_rules: rules("EXRULE"),
+        recurrence_overrides,
+        locations: numbered(
+            property_value(ical_event, "LOCATION").map(|location| Location {
+                object_type: location_type(),
+                name: Some(location.to_owned()),
+            }),
+        ),
+        links: numbered(property_value(ical_event, "URL").map(|url| Link {
+            object_type: link_type(),
+            href: url.to_owned(),
+        })),
+        participants: numbered(participants),
+        alerts: numbered(ical_event.alarms.iter().filter_map(alert_from_ical)),
+        keywords: properties(ical_event, "CATEGORIES")
+            .filter_map(|p| p.value.as_deref())
+            .flat_map(split_text_list)
+            .map(|category| (unescape_text(category), true))
+            .collect(),
+        status: property_value(ical_event, "STATUS").map(str::to_ascii_lowercase),
+        free_busy_status: property_value(ical_event, "TRANSP").map(|transp| {
+            if transp == "TRANSPARENT" {
+                "free".to_owned()
+            } else {
+                "busy".to_owned()
+            }
+        }),
+        privacy: property_value(ical_event, "CLASS").map(|class| match class {
+            "PRIVATE" => "private".to_owned(),
+            "CONFIDENTIAL" => "secret".to_owned(),
+            _ => "public".to_owned(),
+        }),
+        priority: property_value(ical_event, "PRIORITY").and_then(|p| p.parse().ok()),
+        sequence: property_value(ical_event, "SEQUENCE").and_then(|s| s.parse().ok()),
+    };
+
+    let base = to_object(&js_event);
+    for instance in event.overrides() {
+        let Some(recurrence_id) = properties(instance.ical_event(), "RECURRENCE-ID")
+            .next()
+            .and_then(|p| local_datetimes(p, &tz).into_iter().next())
+        else {
+            continue;
+        };
+
+        // Instances start at their recurrence id unless they are moved
+        let mut base = base.clone();
+        base.insert("start".to_owned(), Value::String(recurrence_id.clone()));
+
+        let patch = diff(&base, &to_object(&from_event(instance)));
+        if !patch.is_empty() {
+            js_event.recurrence_overrides.insert(recurrence_id, patch);
+        }
+    }
+
+    js_event
+}
+
+fn text_property(name: &str, value: &str) -> Property {
+    Property {
+        name: name.to_owned(),
+        params: None,
+        value: Some(value.to_owned()),
+    }
+}
+
+/// Convert a JSCalendar `Event` into a calendar object holding a single iCalendar event and an
+/// event with RECURRENCE-ID per patched instance. This is the inverse of [`from_event`]; the
+/// result can be read by [`ical::Event::from_ical`].
+pub fn to_ical(event: &JsEvent) -> Result<IcalCalendar> {
+    let start = NaiveDateTime::parse_from_str(&event.start, LOCAL_DATETIME_FORMAT)?;
+    let tz = match event.time_zone.as_deref() {
+        Some(name) => Some(
+            name.parse::<Tz>()
+                .map_err(|err: String| Error::new(ErrorKind::DateParse, err.as_str()))?,
+        ),
+        None => None,
+    };
+
+    // Renders local datetimes of the event either in UTC, with a TZID or floating
+    let datetime_property = |name: &str, dt: NaiveDateTime| -> Result<Property> {
+        let value = match tz {
+            Some(Tz::UTC) | Some(Tz::Etc__UTC) => {
+                IcalDateTime::Utc(chrono::Utc.from_utc_datetime(&dt))
+            }
+            Some(tz) => IcalDateTime::Local(
+                tz.from_local_datetime(&dt)
+                    .earliest()
+                    .ok_or_else(|| Error::new(ErrorKind::TimeParse, "Invalid local time"))?,
+            ),
+            None if event.show_without_time => IcalDateTime::Date(dt.date()),
+            None => IcalDateTime::Floating(dt),
+        };
+        Ok(value.to_property(name.to_owned()))
+    };
+
+    let mut ical_event = IcalEvent::new();
+    let properties = &mut ical_event.properties;
+
+    properties.push(text_property("UID", &event.uid));
+    properties.push(text_property("DTSTAMP", &generate_timestamp()));
+    properties.push(datetime_property("DTSTART", start)?);
+
+    if let Some(duration) = &event.duration {
+        if event.show_without_time {
+            let days: Duration = duration.parse::<IcalDuration>()?.into();
+            let end = start.date() + Duration::days(days.num_days().max(1));
+            properties.push(IcalDateTime::from(end).to_property("DTEND".to_owned()));
+        } else {
+            properties.push(text_property("DURATION", duration));
+        }
+    }
+
+    if let Some(title) = &event.title {
+        properties.push(text_property("SUMMARY", title));
+    }
+    if let Some(description) = &event.description {
+        properties.push(text_property("DESCRIPTION", description));
+    }
+
+    for rule in event.recurrence_rules.iter() {
+        properties.push(text_property("RRULE", &rule.to_ical(tz.as_ref())?));
+    }
+    for rule in event.excluded_recurrence_rules.iter() {
+        properties.push(text_property("EXRULE", &rule.to_ical(tz.as_ref())?));
+    }
+
+    let mut instances = Vec::new();
+    for (recurrence_id, patch) in event.recurrence_overrides.iter() {
+        let dt = NaiveDateTime::parse_from_str(recurrence_id, LOCAL_DATETIME_FORMAT)?;
+        if patch.get("excluded") == Some(&Value::Bool(true)) {
+            properties.push(datetime_property("EXDATE", dt)?);
+        } else if patch.is_empty() {
+            properties.push(datetime_property("RDATE", dt)?);
+        } else {
+            let mut instance = serde_json::to_value(JsEvent {
+                start: recurrence_id.clone(),
+                recurrence_rules: Vec::new(),
+                excluded_recurrence_rules: Vec::new(),
+                recurrence_overrides: BTreeMap::new(),
+                ..event.clone()
+            })
+            .map_err(|e| Error::new(ErrorKind::SerializeError, &e.to_string()))?;
+            apply_patch(&mut instance, patch)?;
+            let instance: JsEvent = serde_json::from_value(instance)
+                .map_err(|e| Error::new(ErrorKind::ParseError, &e.to_string()))?;
+
+            let mut instance = to_ical(&instance)?.events.remove(0);
+            // Right after UID and DTSTAMP
+            instance
+                .properties
+                .insert(2, datetime_property("RECURRENCE-ID", dt)?);
+            instances.push(instance);
+        }
+    }
+
+    if let Some(location) = event.locations.values().find_map(|l| l.name.as_ref()) {
+        properties.push(text_property("LOCATION", location));
+    }
+    if let Some(link) = event.links.values().next() {
+        properties.push(text_property("URL", &link.href));
+    }
+
+    for participant in event.participants.values() {
+        let address = match (participant.send_to.get("imip"), &participant.email) {
+            (Some(address), _) => address.clone(),
+            (None, Some(email)) => format!("mailto:{}", email),
+            (None, None) => continue,
+        };
+
+        let mut params = Vec::new();
+        if let Some(name) = &participant.name {
+            params.push(("CN".to_owned(), vec![name.clone()]));
+        }
+
+        let has_role = |role: &str| participant.roles.get(role) == Some(&true);
+
+        if has_role("owner") {
+            properties.push(Property {
+                name: "ORGANIZER".to_owned(),
+                params: Some(params.clone()),
+                value: Some(address.clone()),
+            });
+        }
+
+        if !(has_role("attendee")
+            || has_role("chair")
+            || has_role("optional")
+            || has_role("informational"))
+        {
+            continue;
+        }
+
+        let role = if has_role("chair") {
+            "CHAIR"
+        } else if has_role("optional") {
+            "OPT-PARTICIPANT"
+        } else if has_role("informational") {
+            "NON-PARTICIPANT"
+        } else {
+            "REQ-PARTICIPANT"
+        };
+        params.push(("ROLE".to_owned(), vec![role.to_owned()]));
+
+        if let Some(status) = &participant.participation_status {
+            params.push(("PARTSTAT".to_owned(), vec![status.to_ascii_uppercase()]));
+        }
+        if let Some(kind) = &participant.kind {
+            params.push(("CUTYPE".to_owned(), vec![kind.to_ascii_uppercase()]));
+        }
+        if participant.expect_reply {
+            params.push(("RSVP".to_owned(), vec!["TRUE".to_owned()]));
+        }
+
+        properties.push(Property {
+            name: "ATTENDEE".to_owned(),
+            params: Some(params),
+            value: Some(address),
+        });
+    }
+
+    if !event.keywords.is_empty() {
+        let categories = event
+            .keywords
+            .iter()
+            .filter(|(_, &set)| set)
+            .map(|(keyword, _)| escape_text(keyword))
+            .collect::<Vec<_>>();
+        properties.push(text_property("CATEGORIES", &categories.join(",")));
+    }
+    if let Some(status) = &event.status {
+        properties.push(text_property("STATUS", &status.to_ascii_uppercase()));
+    }
+    if let Some(free_busy) = &event.free_busy_status {
+        let transp = if free_busy == "free" {
+            "TRANSPARENT"
+        } else {
+            "OPAQUE"
+        };
+        properties.push(text_property("TRANSP", transp));
+    }
+    if let Some(privacy) = &event.privacy {
+        let class = match privacy.as_str() {
+            "private" => "PRIVATE",
+            "secret" => "CONFIDENTIAL",
+            _ => "PUBLIC",
+        };
+        properties.push(text_property("CLASS", class));
+    }
+    if let Some(priority) = event.priority {
+        properties.push(text_property("PRIORITY", &priority.to_string()));
+    }
+    if let Some(sequence) = event.sequence {
+        properties.push(text_property("SEQUENCE", &sequence.to_string()));
+    }
+
+    for alert in event.alerts.values() {
+        let mut alarm = IcalAlarm::new();
+
+        alarm.properties.push(match &alert.trigger {
+            Trigger::OffsetTrigger {
+                offset,
+                relative_to,
+            } => Property {
+                name: "TRIGGER".to_owned(),
+                params: match relative_to.as_deref() {
+                    Some("end") => Some(vec![("RELATED".to_owned(), vec!["END".to_owned()])]),
+                    _ => None,
+                },
+                value: Some(offset.clone()),
+            },
+            Trigger::AbsoluteTrigger { when } => {
+                let when = DateTime::parse_from_rfc3339(when)
+                    .map_err(Error::from)?
+                    .with_timezone(&chrono::Utc);
+                let mut trigger = IcalDateTime::Utc(when).to_property("TRIGGER".to_owned());
+                trigger.params = Some(vec![("VALUE".to_owned(), vec!["DATE-TIME".to_owned()])]);
+                trigger
+            }
+        });
+
+        let action = if alert.action.as_deref() == Some("email") {
+            "EMAIL"
+        } else {
+            "DISPLAY"
+        };
+        alarm.properties.push(text_property("ACTION", action));
+        alarm.properties.push(text_property(
+            "DESCRIPTION",
+            event.title.as_deref().unwrap_or("Reminder"),
+        ));
+
+        ical_event.alarms.push(alarm);
+    }
+
+    let mut calendar = IcalCalendar::new();
+    calendar.properties = calendar_properties();
+    calendar.events.push(ical_event);
+    calendar.events.extend(instances);
+
+    Ok(calendar)
+}
+
+/// Serialize events as a JSCalendar `Group`.
+pub fn to_string(events: &[JsEvent]) -> Result<String> {
+    let group = Group {
+        object_type: "Group".to_owned(),
+        entries: events.to_vec(),
+    };
+
+    serde_json::to_string_pretty(&group)
+        .map_err(|e| Error::new(ErrorKind::SerializeError, &e.to_string()))
+}
+
+/// Parse a JSCalendar `Group`, a single `Event` or a list of events.
+pub fn from_str(input: &str) -> Result<Vec<JsEvent>> {
+    let value: Value = serde_json::from_str(input)
+        .map_err(|e| Error::new(ErrorKind::ParseError, &e.to_string()))?;
+
+    let events = match value {
+        Value::Array(_) => serde_json::from_value(value),
+        Value::Object(ref object) if object.get("@type") == Some(&"Group".into()) => {
+            serde_json::from_value::<Group>(value).map(|group| group.entries)
+        }
+        _ => serde_json::from_value(value).map(|event| vec![event]),
+    };
+
+    events.map_err(|e| Error::new(ErrorKind::ParseError, &e.to_string()))
+}
@@ -0,0 +1,191 @@
+pub mod csv;
+pub mod freebusy;
+pub mod jscalendar;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::agenda::Agenda;
+use crate::provider::ical::itip::Outcome;
+use crate::provider::ical::{self, ser, xcal};
+use crate::provider::{Error, ErrorKind, ParticipationStatus, ProviderCalendar, Result};
+
+/// Formats events can be exported to and imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ical,
+    XCal,
+    JsCalendar,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "ics" | "ical" | "icalendar" => Ok(Format::Ical),
+            "xcal" | "xml" => Ok(Format::XCal),
+            "jscalendar" | "jscal" | "json" => Ok(Format::JsCalendar),
+            _ => Err(Error::new(
+                ErrorKind::ParseError,
+                &format!("Unknown format '{}'", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Format::Ical => "ics",
+            Format::XCal => "xcal",
+            Format::JsCalendar => "jscalendar",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+pub(crate) fn selected_calendars<'a>(
+    agenda: &'a Agenda,
+    names: &[String],
+) -> Result<Vec<&'a ProviderCalendar>> {
+    if names.is_empty() {
+        return Ok(agenda.calendars().collect());
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            agenda.calendar_by_name(name).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::CalendarParse,
+                    &format!("Calendar '{}' not found", name),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Export all events of the calendars named `calendars` into a single document. If no
+/// calendar is named, all calendars are exported.
+pub fn export(agenda: &Agenda, calendars: &[String], format: Format) -> Result<String> {
+    let events = selected_calendars(agenda, calendars)?
+        .into_iter()
+        .flat_map(|calendar| match calendar {
+            ProviderCalendar::Ical(c) => c.events(),
+        })
+        .collect::<Vec<_>>();
+
+    if format == Format::JsCalendar {
+        let events = events
+            .into_iter()
+            .map(jscalendar::from_event)
+            .collect::<Vec<_>>();
+        return jscalendar::to_string(&events);
+    }
+
+    let merged = ical::merge_calendars(events.iter().map(|event| event.as_ical()));
+
+    match format {
+        Format::Ical => ser::to_string(&merged),
+        Format::XCal => xcal::to_string(&merged),
+        Format::JsCalendar => unreachable!(),
+    }
+}
+
+/// Import all events of the document `input` into the calendar named `calendar`. Returns the
+/// number of imported events.
+pub fn import(agenda: &mut Agenda, calendar: &str, format: Format, input: &str) -> Result<usize> {
+    let documents = match format {
+        Format::Ical => ical::parse_calendars(input)?,
+        Format::XCal => xcal::from_str(input)?,
+        Format::JsCalendar => jscalendar::from_str(input)?
+            .iter()
+            .map(jscalendar::to_ical)
+            .collect::<Result<_>>()?,
+    };
+
+    let target = agenda
+        .provider_calendar_by_name_mut(calendar)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::CalendarParse,
+                &format!("Calendar '{}' not found", calendar),
+            )
+        })?;
+
+    let mut imported = 0;
+    for document in documents {
+        imported += match &mut *target {
+            ProviderCalendar::Ical(c) => c.import(document)?,
+        };
+    }
+
+    Ok(imported)
+}
+
+/// Process the scheduling messages (invitations, updates, cancellations and replies) of the
+/// iCalendar document `input`. Messages are applied to the calendar already containing their
+/// event, new invitations are added to the calendar named `calendar`.
+pub fn process_invitations(
+    agenda: &mut Agenda,
+    calendar: &str,
+    input: &str,
+) -> Result<Vec<Outcome>> {
+    if agenda.calendar_by_name(calendar).is_none() {
+        return Err(Error::new(
+            ErrorKind::CalendarParse,
+            &format!("Calendar '{}' not found", calendar),
+        ));
+    }
+
+    let mut outcomes = Vec::new();
+    for message in ical::parse_calendars(input)?
+        .into_iter()
+        .flat_map(ical::split_calendar)
+    {
+        let uid = message.events[0]
+            .properties
+            .iter()
+            .find(|p| p.name == "UID")
+            .and_then(|p| p.value.clone())
+            .unwrap_or_default();
+
+        let name = agenda
+            .calendar_of_event(&uid)
+            .map_or(calendar, ProviderCalendar::name)
+            .to_owned();
+
+        outcomes.extend(match agenda.provider_calendar_by_name_mut(&name).unwrap() {
+            ProviderCalendar::Ical(c) => c.process_itip(message)?,
+        });
+    }
+
+    Ok(outcomes)
+}
+
+/// Respond to the invitation to the event with the specified uid as the attendee owning one of
+/// `addresses`. Returns the iCalendar reply to send to the organizer.
+pub fn reply(
+    agenda: &mut Agenda,
+    uid: &str,
+    addresses: &[String],
+    status: &ParticipationStatus,
+) -> Result<String> {
+    let name = agenda
+        .calendar_of_event(uid)
+        .map(|calendar| calendar.name().to_owned())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::EventMissingKey,
+                &format!("Event '{}' not found", uid),
+            )
+        })?;
+
+    let message = match agenda.provider_calendar_by_name_mut(&name).unwrap() {
+        ProviderCalendar::Ical(c) => c.respond(uid, addresses, status)?,
+    };
+
+    ser::to_string(&message)
+}
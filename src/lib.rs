@@ -1,5 +1,11 @@
 pub mod agenda;
 pub mod config;
+pub mod conflicts;
 pub mod events;
+pub mod exchange;
+pub mod notification;
 pub mod provider;
+pub mod report;
+pub mod search;
+pub mod slots;
 pub mod ui;
@@ -0,0 +1,421 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use super::{Digest, OccurrenceKey, SnoozeOption};
+use crate::config::BackendConfig;
+use crate::provider::{AlarmAction, Occurrence};
+
+fn serialize_display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Everything a backend needs to know to remind of an occurrence.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reminder {
+    #[serde(flatten)]
+    pub key: OccurrenceKey,
+    pub title: String,
+    pub calendar: String,
+    pub end: DateTime<Utc>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    #[serde(serialize_with = "serialize_display")]
+    pub action: AlarmAction,
+    /// The reminder is delivered late, e.g., because the system was suspended
+    pub missed: bool,
+}
+
+impl Reminder {
+    pub fn new(occurrence: &Occurrence, action: AlarmAction) -> Self {
+        use linkify::{LinkFinder, LinkKind};
+
+        let event = occurrence.event;
+
+        // Prefer the explicit URL of the event, otherwise look for links in its location and
+        // description
+        let find_link = |text: &str| {
+            let mut finder = LinkFinder::new();
+            let mut links = finder.kinds(&[LinkKind::Url]).links(text);
+            links.next().map(|l| l.as_str().to_owned())
+        };
+        let url = event
+            .url()
+            .map(str::to_owned)
+            .or_else(|| event.location().and_then(find_link))
+            .or_else(|| event.description().and_then(find_link))
+            .or_else(|| event.description_uri().map(str::to_owned));
+
+        Reminder {
+            key: OccurrenceKey::from(occurrence),
+            title: event.title().to_owned(),
+            calendar: occurrence.calendar.to_owned(),
+            end: occurrence.end(),
+            location: event.location().map(str::to_owned),
+            description: event.description().map(str::to_owned),
+            url,
+            action,
+            missed: false,
+        }
+    }
+
+    pub fn begin(&self) -> DateTime<Utc> {
+        self.key.begin
+    }
+
+    /// Whether the occurrence is over at `now`.
+    pub fn is_over(&self, now: DateTime<Utc>) -> bool {
+        now >= self.end
+    }
+
+    /// A one-line summary like "Upcoming event 'Standup'".
+    pub fn summary(&self, now: DateTime<Utc>) -> String {
+        let summary = if now < self.begin() {
+            format!("Upcoming event '{}'", self.title)
+        } else if !self.is_over(now) {
+            format!("Current event '{}'", self.title)
+        } else {
+            return format!("Missed event '{}'", self.title);
+        };
+
+        if self.missed {
+            format!("Missed reminder: {}", summary)
+        } else {
+            summary
+        }
+    }
+
+    /// The time of the occurrence followed by its location and description.
+    pub fn body(&self) -> String {
+        let begin = self.begin().with_timezone(&Local);
+        let end = self.end.with_timezone(&Local);
+
+        let mut body = if begin.date_naive() != end.date_naive() {
+            format!("{}-{}", begin, end)
+        } else {
+            format!("{}-{}", begin.time(), end.time())
+        };
+        for line in self.location.iter().chain(self.description.iter()) {
+            body += "\n";
+            body += line;
+        }
+
+        body
+    }
+}
+
+/// How a reminder was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    /// The reminder was dismissed by the user, which is remembered, and is not shown again
+    Done,
+    /// The reminder was handed to a backend that does not report back, e.g., a hook. It is not
+    /// shown again, but neither remembered as dismissed.
+    Delivered,
+    /// The reminder is to be shown again at the specified time
+    Snoozed(DateTime<Utc>),
+    /// The reminder is to be shown again right away
+    Again,
+    /// The notification was closed without an answer, e.g., by its timeout. The reminder is
+    /// not shown again, but neither remembered as dismissed.
+    Closed,
+}
+
+/// Delivers reminders to the user.
+pub trait Backend: Send + Sync {
+    /// Deliver the reminder. Interactive backends block until the user reacted to it and may
+    /// offer the `snoozes`.
+    fn notify(&self, reminder: &Reminder, snoozes: &[SnoozeOption]) -> io::Result<Response>;
+
+    /// Deliver the daily digest.
+    fn digest(&self, digest: &Digest) -> io::Result<()>;
+}
+
+pub fn from_config(config: &BackendConfig) -> Box<dyn Backend> {
+    match config {
+        BackendConfig::Desktop { open_command } => Box::new(Desktop {
+            open_command: open_command
+                .clone()
+                .or_else(|| std::env::var_os("TERMINAL").map(|_| TERMINAL_OPEN_COMMAND.to_owned())),
+        }),
+        BackendConfig::Command { command } => Box::new(ShellHook {
+            command: command.clone(),
+        }),
+        BackendConfig::Json { path } => Box::new(JsonLines { path: path.clone() }),
+        BackendConfig::Bell => Box::new(Bell),
+        BackendConfig::Tmux => Box::new(Tmux),
+    }
+}
+
+/// Launches jk in the user's terminal, focused on the event. Used if `$TERMINAL` is set and no
+/// other command is configured.
+const TERMINAL_OPEN_COMMAND: &str = "\"$TERMINAL\" -e jk --date \"$JK_DATE\" --event \"$JK_UID\"";
+
+/// Desktop notifications via D-Bus.
+pub struct Desktop {
+    /// Without a command, the "Open in jk" action is not offered
+    open_command: Option<String>,
+}
+
+fn open_url(url: &str) -> io::Result<()> {
+    Command::new("xdg-open").arg(url).spawn().map(|_| ())
+}
+
+impl Desktop {
+    fn open_in_jk(&self, open_command: &str, reminder: &Reminder) -> io::Result<()> {
+        let date = reminder.begin().with_timezone(&Local).date_naive();
+
+        Command::new("sh")
+            .arg("-c")
+            .arg(open_command)
+            .env("JK_DATE", date.to_string())
+            .env("JK_UID", &reminder.key.uid)
+            .spawn()
+            .map(|_| ())
+    }
+}
+
+impl Backend for Desktop {
+    fn notify(&self, reminder: &Reminder, snoozes: &[SnoozeOption]) -> io::Result<Response> {
+        let now = Utc::now();
+        let mut n = notify_rust::Notification::new();
+
+        n.action("dismiss", "Dismiss");
+        if reminder.action == AlarmAction::Audio {
+            n.sound_name("alarm-clock-elapsed");
+        }
+        if reminder.url.is_some() {
+            n.action("open_url", "Open URL");
+        }
+        if self.open_command.is_some() {
+            n.action("open_jk", "Open in jk");
+        }
+
+        // Only offer snoozes that let the notification reappear before the event is over
+        for snooze in snoozes.iter() {
+            let until = snooze.until(now, reminder.begin());
+            if now < until && until < reminder.end {
+                n.action(&snooze.action_id(), &format!("Snooze: {}", snooze));
+            }
+        }
+
+        let timeout = if now < reminder.begin() {
+            notify_rust::Timeout::Milliseconds((reminder.begin() - now).num_milliseconds() as u32)
+        } else if !reminder.is_over(now) {
+            notify_rust::Timeout::Milliseconds((reminder.end - now).num_milliseconds() as u32)
+        } else {
+            notify_rust::Timeout::Default
+        };
+
+        n.summary(&reminder.summary(now))
+            .body(&reminder.body())
+            .timeout(timeout)
+            .hint(notify_rust::Hint::Resident(true));
+
+        let handle = n
+            .show()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let mut response = Response::Again;
+        handle.wait_for_action(|action| {
+            response = match action {
+                "dismiss" => Response::Done,
+                "__closed" => Response::Closed,
+                "open_url" => {
+                    if let Err(e) = open_url(reminder.url.as_ref().unwrap()) {
+                        log::error!("Could not open URL: {}", e);
+                    }
+                    Response::Again
+                }
+                "open_jk" => {
+                    let open_command = self.open_command.as_deref().unwrap();
+                    if let Err(e) = self.open_in_jk(open_command, reminder) {
+                        log::error!("Could not open jk: {}", e);
+                    }
+                    Response::Again
+                }
+                action => snoozes
+                    .iter()
+                    .find(|snooze| snooze.action_id() == action)
+                    .map_or(Response::Again, |snooze| {
+                        Response::Snoozed(snooze.until(Utc::now(), reminder.begin()))
+                    }),
+            }
+        });
+
+        Ok(response)
+    }
+
+    fn digest(&self, digest: &Digest) -> io::Result<()> {
+        notify_rust::Notification::new()
+            .summary(&digest.summary())
+            .body(&digest.body())
+            .timeout(notify_rust::Timeout::Never)
+            .show()
+            .map(|_| ())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+/// Runs a shell command with the fields of the reminder in environment variables.
+pub struct ShellHook {
+    command: String,
+}
+
+impl ShellHook {
+    fn run(&self, env: &[(&str, String)]) -> io::Result<()> {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .envs(env.iter().cloned())
+            .status()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Notification command failed with {}", status),
+            ))
+        }
+    }
+}
+
+impl Backend for ShellHook {
+    fn notify(&self, reminder: &Reminder, _snoozes: &[SnoozeOption]) -> io::Result<Response> {
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+
+        self.run(&[
+            ("JK_KIND", "reminder".to_owned()),
+            ("JK_UID", reminder.key.uid.clone()),
+            ("JK_TITLE", reminder.title.clone()),
+            ("JK_CALENDAR", reminder.calendar.clone()),
+            ("JK_BEGIN", reminder.begin().to_rfc3339()),
+            ("JK_END", reminder.end.to_rfc3339()),
+            ("JK_LOCATION", optional(&reminder.location)),
+            ("JK_DESCRIPTION", optional(&reminder.description)),
+            ("JK_URL", optional(&reminder.url)),
+            ("JK_ACTION", reminder.action.to_string()),
+            ("JK_SUMMARY", reminder.summary(Utc::now())),
+            ("JK_BODY", reminder.body()),
+        ])?;
+
+        Ok(Response::Delivered)
+    }
+
+    fn digest(&self, digest: &Digest) -> io::Result<()> {
+        self.run(&[
+            ("JK_KIND", "digest".to_owned()),
+            ("JK_DATE", digest.date.to_string()),
+            ("JK_SUMMARY", digest.summary()),
+            ("JK_BODY", digest.body()),
+        ])
+    }
+}
+
+/// Writes reminders as JSON lines to stdout or a file, which may be a FIFO.
+pub struct JsonLines {
+    path: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum JsonLine<'a> {
+    Reminder(&'a Reminder),
+    Digest(&'a Digest),
+}
+
+impl JsonLines {
+    fn write(&self, value: &JsonLine) -> io::Result<()> {
+        let mut line = serde_json::to_string(value)?;
+        line.push('\n');
+
+        match &self.path {
+            // Opening a FIFO blocks until there is a reader
+            Some(path) => OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)?
+                .write_all(line.as_bytes()),
+            None => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+                stdout.write_all(line.as_bytes())?;
+                stdout.flush()
+            }
+        }
+    }
+}
+
+impl Backend for JsonLines {
+    fn notify(&self, reminder: &Reminder, _snoozes: &[SnoozeOption]) -> io::Result<Response> {
+        self.write(&JsonLine::Reminder(reminder))?;
+        Ok(Response::Delivered)
+    }
+
+    fn digest(&self, digest: &Digest) -> io::Result<()> {
+        self.write(&JsonLine::Digest(digest))
+    }
+}
+
+/// Rings the terminal bell and prints the reminder.
+pub struct Bell;
+
+fn ring(summary: &str, body: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    writeln!(stdout, "\x07{}:\n{}", summary, body)?;
+    stdout.flush()
+}
+
+impl Backend for Bell {
+    fn notify(&self, reminder: &Reminder, _snoozes: &[SnoozeOption]) -> io::Result<Response> {
+        ring(&reminder.summary(Utc::now()), &reminder.body())?;
+        Ok(Response::Delivered)
+    }
+
+    fn digest(&self, digest: &Digest) -> io::Result<()> {
+        ring(&digest.summary(), &digest.body())
+    }
+}
+
+/// Shows the reminder in the status line of all tmux clients.
+pub struct Tmux;
+
+fn display_message(summary: &str, body: &str) -> io::Result<()> {
+    let message = format!("{}: {}", summary, body.replace('\n', ", "));
+
+    // tmux interprets '#' as the start of a format
+    let status = Command::new("tmux")
+        .arg("display-message")
+        .arg(message.replace('#', "##"))
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("tmux display-message failed with {}", status),
+        ))
+    }
+}
+
+impl Backend for Tmux {
+    fn notify(&self, reminder: &Reminder, _snoozes: &[SnoozeOption]) -> io::Result<Response> {
+        display_message(&reminder.summary(Utc::now()), &reminder.body())?;
+        Ok(Response::Delivered)
+    }
+
+    fn digest(&self, digest: &Digest) -> io::Result<()> {
+        display_message(&digest.summary(), &digest.body())
+    }
+}
@@ -0,0 +1,141 @@
+//! Control socket of jk-notify.
+//!
+//! Clients connect to a Unix domain socket, send a single [`Request`] as one line of JSON and
+//! receive a single [`Reply`] as one line of JSON before the connection is closed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// A command sent to jk-notify.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// List everything scheduled in the current window
+    ListScheduled,
+    /// Snooze the reminders of an occurrence. Without `begin`, the occurrence of the event
+    /// closest to now is snoozed.
+    Snooze {
+        uid: String,
+        begin: Option<DateTime<Utc>>,
+        /// A snooze option like "10m", "5m before" or "start"
+        snooze: String,
+    },
+    /// Dismiss the reminders of an occurrence, chosen like for `Snooze`
+    Dismiss {
+        uid: String,
+        begin: Option<DateTime<Utc>>,
+    },
+    /// Reload all calendars from disk
+    Reload,
+}
+
+/// Something scheduled by jk-notify.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ScheduledEntry {
+    Reminder {
+        time: DateTime<Utc>,
+        uid: String,
+        begin: DateTime<Utc>,
+        title: String,
+        calendar: String,
+    },
+    Digest {
+        time: DateTime<Utc>,
+    },
+}
+
+impl ScheduledEntry {
+    pub fn time(&self) -> DateTime<Utc> {
+        match self {
+            ScheduledEntry::Reminder { time, .. } | ScheduledEntry::Digest { time } => *time,
+        }
+    }
+}
+
+/// The answer of jk-notify to a [`Request`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Reply {
+    Ok { message: String },
+    Scheduled { entries: Vec<ScheduledEntry> },
+    Error { message: String },
+}
+
+/// `$XDG_RUNTIME_DIR/jackal/notify.sock`, falling back to the state directory on platforms
+/// without a runtime directory.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::state_dir)
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("jackal").join("notify.sock"))
+}
+
+/// Send `request` to the jk-notify listening at `path` and wait for its reply.
+pub fn send(path: &Path, request: &Request) -> io::Result<Reply> {
+    let mut stream = UnixStream::connect(path)?;
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+fn handle_connection(stream: UnixStream, handler: &impl Fn(Request) -> Reply) -> io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let reply = match serde_json::from_str(&line) {
+        Ok(request) => handler(request),
+        Err(e) => Reply::Error {
+            message: format!("Invalid request: {}", e),
+        },
+    };
+
+    let mut line = serde_json::to_string(&reply)?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes())
+}
+
+/// Listen at `path` and answer requests with `handler` in a background thread.
+///
+/// Fails if another process is listening at `path` already. A stale socket left behind by a
+/// crashed process is replaced.
+pub fn serve(
+    path: &Path,
+    handler: impl Fn(Request) -> Reply + Send + 'static,
+) -> io::Result<thread::JoinHandle<()>> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("'{}' is in use by another process", path.display()),
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    thread::Builder::new()
+        .name("jackal-notify-control".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| handle_connection(stream, &handler));
+                if let Err(e) = result {
+                    log::error!("Could not handle control connection: {}", e);
+                }
+            }
+        })
+}
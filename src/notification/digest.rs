@@ -0,0 +1,139 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+use std::fmt;
+
+use crate::agenda::Agenda;
+use crate::provider::{Occurrence, Transparency};
+
+/// An event of the digest.
+#[derive(Debug, Clone, Serialize)]
+pub struct DigestEntry {
+    pub uid: String,
+    pub title: String,
+    pub calendar: String,
+    pub begin: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub location: Option<String>,
+}
+
+impl From<&Occurrence<'_>> for DigestEntry {
+    fn from(occurrence: &Occurrence<'_>) -> Self {
+        DigestEntry {
+            uid: occurrence.event.uid().to_owned(),
+            title: occurrence.event.title().to_owned(),
+            calendar: occurrence.calendar.to_owned(),
+            begin: occurrence.begin(),
+            end: occurrence.end(),
+            location: occurrence.event.location().map(str::to_owned),
+        }
+    }
+}
+
+/// Summary of the events of a day.
+#[derive(Debug, Clone, Serialize)]
+pub struct Digest {
+    pub date: NaiveDate,
+    pub allday: Vec<DigestEntry>,
+    pub events: Vec<DigestEntry>,
+    /// Pairs of overlapping events, given by their titles
+    pub conflicts: Vec<(String, String)>,
+}
+
+impl Digest {
+    /// The digest of all events of `date` that are not cancelled.
+    pub fn for_day(agenda: &Agenda, date: &NaiveDate) -> Self {
+        let occurrences = agenda
+            .events_of_day(date)
+            .filter(|occurrence| !occurrence.event.is_cancelled())
+            .collect::<Vec<_>>();
+
+        let (allday, timed): (Vec<_>, Vec<_>) = occurrences
+            .iter()
+            .partition(|occurrence| occurrence.span.is_allday());
+
+        // Events marked as free time do not conflict with anything
+        let busy = timed
+            .iter()
+            .filter(|occurrence| occurrence.event.transparency() == Transparency::Opaque)
+            .collect::<Vec<_>>();
+        let mut conflicts = Vec::new();
+        for (idx, first) in busy.iter().enumerate() {
+            for second in busy[idx + 1..].iter() {
+                // Sorted by begin, so no later event can overlap with `first` either
+                if second.begin() >= first.end() {
+                    break;
+                }
+                conflicts.push((
+                    first.event.title().to_owned(),
+                    second.event.title().to_owned(),
+                ));
+            }
+        }
+
+        Digest {
+            date: *date,
+            allday: allday.into_iter().map(DigestEntry::from).collect(),
+            events: timed.into_iter().map(DigestEntry::from).collect(),
+            conflicts,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allday.is_empty() && self.events.is_empty()
+    }
+
+    /// A one-line summary like "Agenda for Mon, 2024-01-08".
+    pub fn summary(&self) -> String {
+        format!("Agenda for {}", self.date.format("%a, %Y-%m-%d"))
+    }
+
+    /// The entries of the digest, one per line.
+    pub fn body(&self) -> String {
+        let mut lines = Vec::new();
+
+        if self.is_empty() {
+            lines.push("No events".to_owned());
+        }
+        for entry in self.allday.iter() {
+            lines.push(format!("All day  {}", entry.title));
+        }
+        for entry in self.events.iter() {
+            let begin = entry.begin.with_timezone(&Local);
+            let end = entry.end.with_timezone(&Local);
+            let mut line = format!(
+                "{}-{}  {}",
+                begin.format("%H:%M"),
+                end.format("%H:%M"),
+                entry.title
+            );
+            if let Some(location) = &entry.location {
+                line += &format!(" @ {}", location);
+            }
+            lines.push(line);
+        }
+        for (first, second) in self.conflicts.iter() {
+            lines.push(format!("Conflict: '{}' overlaps '{}'", first, second));
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        writeln!(f, "{}", self.body())
+    }
+}
+
+/// The next time after `now` the digest is due, given the local time of day it is sent at.
+pub fn next_digest_time(now: DateTime<Utc>, time: NaiveTime) -> DateTime<Utc> {
+    let today = now.with_timezone(&Local).date_naive();
+
+    [today, today.succ_opt().unwrap()]
+        .iter()
+        .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+        .map(|local| local.with_timezone(&Utc))
+        .find(|due| *due > now)
+        .unwrap_or_else(|| now + chrono::Duration::days(1))
+}
@@ -0,0 +1,149 @@
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::agenda::Agenda;
+use crate::config::Config;
+use crate::provider::{Error, ErrorKind, Occurrence, Result, Transparency};
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// A daily time window without notifications, e.g., "22:00-07:00".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // The window spans midnight
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s.split_once('-').ok_or_else(|| {
+            Error::new(
+                ErrorKind::TimeParse,
+                &format!("Invalid quiet hours '{}', expected e.g. '22:00-07:00'", s),
+            )
+        })?;
+
+        Ok(QuietHours {
+            start: NaiveTime::parse_from_str(start.trim(), TIME_FORMAT)?,
+            end: NaiveTime::parse_from_str(end.trim(), TIME_FORMAT)?,
+        })
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT)
+        )
+    }
+}
+
+fn parse_all(windows: &[String]) -> Result<Vec<QuietHours>> {
+    windows.iter().map(|window| window.parse()).collect()
+}
+
+/// Why a notification is suppressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Suppression {
+    QuietHours(QuietHours),
+    /// A busy event of the focus calendar takes place
+    Focus(String),
+}
+
+impl fmt::Display for Suppression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Suppression::QuietHours(window) => write!(f, "quiet hours {}", window),
+            Suppression::Focus(title) => write!(f, "focus block '{}'", title),
+        }
+    }
+}
+
+/// Do-not-disturb rules deciding which reminders are delivered.
+#[derive(Debug, Clone, Default)]
+pub struct DndRules {
+    quiet_hours: Vec<QuietHours>,
+    /// Quiet hours of calendars overriding the global ones
+    calendar_quiet_hours: HashMap<String, Vec<QuietHours>>,
+    focus_calendar: Option<String>,
+    urgent_priority: u8,
+}
+
+impl DndRules {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let calendar_quiet_hours = config
+            .collections
+            .iter()
+            .flat_map(|collection| collection.calendars.iter())
+            .filter_map(|calendar| {
+                let windows = calendar.quiet_hours.as_ref()?;
+                Some(parse_all(windows).map(|windows| (calendar.name.clone(), windows)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DndRules {
+            quiet_hours: parse_all(&config.notify.quiet_hours)?,
+            calendar_quiet_hours,
+            focus_calendar: config.notify.focus_calendar.clone(),
+            urgent_priority: config.notify.urgent_priority,
+        })
+    }
+
+    /// Whether the event has a priority high enough to break through quiet hours.
+    fn is_urgent(&self, occurrence: &Occurrence) -> bool {
+        occurrence
+            .event
+            .priority()
+            .map_or(false, |priority| priority <= self.urgent_priority)
+    }
+
+    /// The reason a reminder of `occurrence` at `at` is not to be delivered, if any.
+    pub fn suppression(
+        &self,
+        agenda: &Agenda,
+        occurrence: &Occurrence,
+        at: DateTime<Utc>,
+    ) -> Option<Suppression> {
+        if let Some(focus_calendar) = &self.focus_calendar {
+            let focus = agenda
+                .events_in(at.naive_utc()..=at.naive_utc())
+                .filter(|focus| focus.calendar == focus_calendar)
+                .filter(|focus| !focus.event.is_cancelled())
+                .find(|focus| focus.event.transparency() == Transparency::Opaque);
+
+            if let Some(focus) = focus {
+                return Some(Suppression::Focus(focus.event.title().to_owned()));
+            }
+        }
+
+        if self.is_urgent(occurrence) {
+            return None;
+        }
+
+        let time = at.with_timezone(&Local).time();
+        self.calendar_quiet_hours
+            .get(occurrence.calendar)
+            .unwrap_or(&self.quiet_hours)
+            .iter()
+            .find(|window| window.contains(time))
+            .map(|window| Suppression::QuietHours(*window))
+    }
+}
@@ -0,0 +1,14 @@
+pub mod backend;
+pub mod control;
+pub mod digest;
+pub mod dnd;
+pub mod schedule;
+pub mod snooze;
+pub mod state;
+
+pub use backend::{Backend, Reminder, Response};
+pub use digest::Digest;
+pub use dnd::DndRules;
+pub use schedule::DueReminder;
+pub use snooze::SnoozeOption;
+pub use state::{NotificationState, OccurrenceKey};
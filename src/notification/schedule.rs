@@ -0,0 +1,127 @@
+use chrono::{DateTime, Duration, Utc};
+use std::ops::Range;
+
+use crate::agenda::Agenda;
+use crate::provider::{Alarm, AlarmAction, AlarmTrigger, Occurrence};
+
+use super::{NotificationState, OccurrenceKey};
+
+/// A reminder of an occurrence that is due at `time`.
+#[derive(Clone)]
+pub struct DueReminder<'a> {
+    /// When the alarm goes off or, if the reminder was snoozed, when the snooze ends
+    pub time: DateTime<Utc>,
+    pub action: AlarmAction,
+    pub occurrence: Occurrence<'a>,
+}
+
+impl DueReminder<'_> {
+    /// Identifies the reminder, as an occurrence may have several alarms.
+    pub fn key(&self) -> (OccurrenceKey, DateTime<Utc>) {
+        (OccurrenceKey::from(&self.occurrence), self.time)
+    }
+}
+
+/// The times `alarms` go off for `occurrence`, with their action.
+pub fn trigger_times(
+    alarms: impl IntoIterator<Item = Alarm>,
+    occurrence: &Occurrence,
+) -> Vec<(DateTime<Utc>, AlarmAction)> {
+    alarms
+        .into_iter()
+        .flat_map(|alarm| {
+            alarm
+                .trigger_times(occurrence.begin(), occurrence.end())
+                .into_iter()
+                .map(move |time| (time, alarm.action.clone()))
+        })
+        .collect()
+}
+
+fn is_absolute(alarm: &Alarm) -> bool {
+    matches!(alarm.trigger, AlarmTrigger::Absolute(_))
+}
+
+/// The longest time between an occurrence and one of its alarms relative to it.
+pub fn max_alarm_offset(agenda: &Agenda) -> Duration {
+    agenda
+        .first_occurrences()
+        .flat_map(|occurrence| occurrence.event().alarms())
+        .filter_map(|alarm| match alarm.trigger {
+            AlarmTrigger::Relative { offset, .. } => Some(
+                offset.abs() + alarm.interval.unwrap_or_else(Duration::zero) * alarm.repeat as i32,
+            ),
+            AlarmTrigger::Absolute(_) => None,
+        })
+        .max()
+        .unwrap_or_else(Duration::zero)
+}
+
+/// All reminders due in `window`, sorted by their time. Events without alarms are reminded of
+/// `headsup` before they begin. Relative alarms are looked for in occurrences up to
+/// `lookaround` around the window, dismissed occurrences are skipped and snoozed ones are
+/// reminded of once the snooze ends.
+pub fn due_reminders<'a>(
+    agenda: &'a Agenda,
+    state: &NotificationState,
+    headsup: Duration,
+    lookaround: Duration,
+    window: Range<DateTime<Utc>>,
+) -> Vec<DueReminder<'a>> {
+    // Alarms relative to their event go off for each occurrence
+    let relative = agenda
+        .events_in((window.start - lookaround).naive_utc()..(window.end + lookaround).naive_utc())
+        .filter(|occurrence| !occurrence.event().is_cancelled())
+        .map(|occurrence| {
+            let alarms = occurrence.event().alarms();
+            let triggers = if alarms.is_empty() {
+                // Fall back to the global headsup time
+                vec![(occurrence.begin() - headsup, AlarmAction::Display)]
+            } else {
+                let relative = alarms.into_iter().filter(|alarm| !is_absolute(alarm));
+                trigger_times(relative, &occurrence)
+            };
+            (occurrence, triggers)
+        });
+
+    // Absolute ones only once for the whole event, which is due to its first occurrence
+    let absolute = agenda
+        .first_occurrences()
+        .filter(|occurrence| !occurrence.event().is_cancelled())
+        .map(|occurrence| {
+            let absolute = occurrence.event().alarms().into_iter().filter(is_absolute);
+            let triggers = trigger_times(absolute, &occurrence);
+            (occurrence, triggers)
+        });
+
+    let mut reminders = relative
+        .chain(absolute)
+        .flat_map(|(occurrence, triggers)| {
+            let key = OccurrenceKey::from(&occurrence);
+            if state.is_dismissed(&key) {
+                return Vec::new();
+            }
+            let snoozed_until = state.snoozed_until(&key);
+
+            triggers
+                .into_iter()
+                .map(|(time, action)| match snoozed_until {
+                    Some(until) if until > time => (until, action),
+                    _ => (time, action),
+                })
+                .filter(|(time, _)| window.contains(time))
+                .map(|(time, action)| DueReminder {
+                    time,
+                    action,
+                    occurrence: occurrence.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Alarms that went off before the end of a snooze are reminded of only once
+    reminders.sort_by_key(|reminder| reminder.key());
+    reminders.dedup_by_key(|reminder| reminder.key());
+    reminders.sort_by_key(|reminder| reminder.time);
+    reminders
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::provider::{parse_duration, Error, ErrorKind, Result};
+
+/// How long a notification is snoozed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnoozeOption {
+    /// For a fixed time
+    For(Duration),
+    /// Until the specified time before the start of the event
+    BeforeStart(Duration),
+    UntilStart,
+}
+
+impl SnoozeOption {
+    /// The time a notification snoozed at `now` for an event beginning at `begin` reappears.
+    pub fn until(&self, now: DateTime<Utc>, begin: DateTime<Utc>) -> DateTime<Utc> {
+        match *self {
+            SnoozeOption::For(duration) => now + duration,
+            SnoozeOption::BeforeStart(duration) => begin - duration,
+            SnoozeOption::UntilStart => begin,
+        }
+    }
+
+    /// Identifier of the notification action.
+    pub fn action_id(&self) -> String {
+        match self {
+            SnoozeOption::For(duration) => format!("snooze-for-{}", duration.num_seconds()),
+            SnoozeOption::BeforeStart(duration) => {
+                format!("snooze-before-{}", duration.num_seconds())
+            }
+            SnoozeOption::UntilStart => "snooze-start".to_owned(),
+        }
+    }
+}
+
+fn format_duration(duration: &Duration) -> String {
+    if duration.num_minutes() >= 60 && duration.num_minutes() % 60 == 0 {
+        format!("{} h", duration.num_hours())
+    } else if duration.num_seconds() >= 60 {
+        format!("{} min", duration.num_minutes())
+    } else {
+        format!("{} s", duration.num_seconds())
+    }
+}
+
+impl FromStr for SnoozeOption {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+
+        if s == "start" || s == "until start" {
+            return Ok(SnoozeOption::UntilStart);
+        }
+
+        let option = match s
+            .strip_suffix("before start")
+            .or_else(|| s.strip_suffix("before"))
+        {
+            Some(before) => SnoozeOption::BeforeStart(parse_duration(before)?),
+            None => SnoozeOption::For(parse_duration(&s)?),
+        };
+
+        match option {
+            SnoozeOption::For(duration) | SnoozeOption::BeforeStart(duration)
+                if duration < Duration::zero() =>
+            {
+                Err(Error::new(
+                    ErrorKind::DurationParse,
+                    &format!("Snooze '{}' must not be negative", s),
+                ))
+            }
+            option => Ok(option),
+        }
+    }
+}
+
+impl fmt::Display for SnoozeOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnoozeOption::For(duration) => write!(f, "{}", format_duration(duration)),
+            SnoozeOption::BeforeStart(duration) => {
+                write!(f, "{} before", format_duration(duration))
+            }
+            SnoozeOption::UntilStart => write!(f, "Until start"),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::provider::Occurrence;
+
+/// Identifies a single occurrence of a (possibly recurring) event.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OccurrenceKey {
+    pub uid: String,
+    pub begin: DateTime<Utc>,
+}
+
+impl From<&Occurrence<'_>> for OccurrenceKey {
+    fn from(occurrence: &Occurrence<'_>) -> Self {
+        OccurrenceKey {
+            uid: occurrence.event.uid().to_owned(),
+            begin: occurrence.begin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snooze {
+    occurrence: OccurrenceKey,
+    until: DateTime<Utc>,
+}
+
+/// Dismissed and snoozed notifications, persisted so that restarts of jk-notify do not show
+/// them again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NotificationState {
+    #[serde(default)]
+    dismissed: BTreeSet<OccurrenceKey>,
+    #[serde(default)]
+    snoozed: Vec<Snooze>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl NotificationState {
+    /// `$XDG_STATE_HOME/jackal/notify-state.json`, falling back to the local data directory on
+    /// platforms without a state directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("jackal").join("notify-state.json"))
+    }
+
+    /// Load the state from `path`. A missing or unreadable state file results in an empty
+    /// state that is written to `path` on the next change.
+    pub fn load(path: &Path) -> Self {
+        let mut state = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid state file '{}': {}", path.display(), e);
+                NotificationState::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => NotificationState::default(),
+            Err(e) => {
+                log::warn!("Could not read state file '{}': {}", path.display(), e);
+                NotificationState::default()
+            }
+        };
+
+        state.path = Some(path.to_owned());
+        state
+    }
+
+    /// Write the state back to the file it was loaded from, if any.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write to a temporary file first so that the state is never truncated
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp_path, path)
+    }
+
+    pub fn is_dismissed(&self, occurrence: &OccurrenceKey) -> bool {
+        self.dismissed.contains(occurrence)
+    }
+
+    pub fn dismiss(&mut self, occurrence: OccurrenceKey) {
+        self.snoozed
+            .retain(|snooze| snooze.occurrence != occurrence);
+        self.dismissed.insert(occurrence);
+    }
+
+    /// The time the notification of the occurrence was snoozed until.
+    pub fn snoozed_until(&self, occurrence: &OccurrenceKey) -> Option<DateTime<Utc>> {
+        self.snoozed
+            .iter()
+            .find(|snooze| &snooze.occurrence == occurrence)
+            .map(|snooze| snooze.until)
+    }
+
+    pub fn snooze(&mut self, occurrence: OccurrenceKey, until: DateTime<Utc>) {
+        self.snoozed
+            .retain(|snooze| snooze.occurrence != occurrence);
+        self.snoozed.push(Snooze { occurrence, until });
+    }
+
+    /// Forget about all occurrences beginning before `before`. Returns whether anything was
+    /// removed.
+    pub fn prune(&mut self, before: DateTime<Utc>) -> bool {
+        let count = self.dismissed.len() + self.snoozed.len();
+
+        self.dismissed
+            .retain(|occurrence| occurrence.begin >= before);
+        self.snoozed
+            .retain(|snooze| snooze.occurrence.begin >= before || snooze.until >= before);
+
+        count != self.dismissed.len() + self.snoozed.len()
+    }
+}
@@ -1,11 +1,12 @@
-use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use chrono_tz::Tz;
 use std::collections::BTreeMap;
 use std::ops::{Bound, Deref};
 use std::path::{Path, PathBuf};
 use store_interval_tree::{Interval, IntervalTree};
 
-use super::{Calendarlike, EventFilter, Eventlike, Occurrence};
+use super::merge::BoxedOccurrences;
+use super::{Calendarlike, EventFilter, Eventlike, MergedOccurrences, Occurrence};
 
 type Uid = String;
 
@@ -60,7 +61,13 @@ impl<Event: Eventlike> CalendarCore<Event> {
             return Err(event);
         }
 
-        let (first, last) = event.occurrence_rule().clone().with_tz(&Utc {}).as_range();
+        // Modified instances may be moved outside the range of the recurrence rule
+        let (first, last) = event.overrides().iter().map(range_of).fold(
+            range_of(&event),
+            |(first, last), (other_first, other_last)| {
+                (lower_begin(first, other_first), upper_end(last, other_last))
+            },
+        );
         let interval = Interval::new(first, last);
 
         // check if interval is already in tree
@@ -83,6 +90,30 @@ impl<Event: Eventlike> CalendarCore<Event> {
         Ok(())
     }
 
+    /// Whether an event with the specified uid is present in the calendar.
+    pub fn contains(&self, uid: &str) -> bool {
+        self.uid_to_interval.contains_key(uid)
+    }
+
+    /// The event with the specified uid.
+    pub fn get(&self, uid: &str) -> Option<&Event> {
+        let interval = self.uid_to_interval.get(uid)?;
+
+        self.events
+            .query(interval)
+            .filter(|entry| entry.interval() == interval)
+            .flat_map(|entry| entry.value().iter())
+            .find(|event| event.uid() == uid)
+    }
+
+    /// All events of the calendar.
+    pub fn events(&self) -> Vec<&Event> {
+        self.events
+            .query(&Interval::new(Bound::Unbounded, Bound::Unbounded))
+            .flat_map(|entry| entry.value().iter())
+            .collect()
+    }
+
     /// Try to remove an event with the specified id. Returns whether or not such an event was
     /// present before and thus successfully removed.
     pub fn remove_via_uid(&mut self, uid: &str) -> bool {
@@ -106,6 +137,42 @@ impl<Event: Eventlike> CalendarCore<Event> {
     }
 }
 
+fn range_of(event: &impl Eventlike) -> (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>) {
+    event.occurrence_rule().clone().with_tz(&Utc {}).as_range()
+}
+
+/// The lower of two lower bounds.
+fn lower_begin(a: Bound<DateTime<Utc>>, b: Bound<DateTime<Utc>>) -> Bound<DateTime<Utc>> {
+    match (a, b) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => Bound::Unbounded,
+        (Bound::Included(a), Bound::Included(b)) => Bound::Included(a.min(b)),
+        (Bound::Excluded(a), Bound::Excluded(b)) => Bound::Excluded(a.min(b)),
+        (Bound::Included(a), Bound::Excluded(b)) | (Bound::Excluded(b), Bound::Included(a)) => {
+            if a <= b {
+                Bound::Included(a)
+            } else {
+                Bound::Excluded(b)
+            }
+        }
+    }
+}
+
+/// The higher of two upper bounds.
+fn upper_end(a: Bound<DateTime<Utc>>, b: Bound<DateTime<Utc>>) -> Bound<DateTime<Utc>> {
+    match (a, b) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => Bound::Unbounded,
+        (Bound::Included(a), Bound::Included(b)) => Bound::Included(a.max(b)),
+        (Bound::Excluded(a), Bound::Excluded(b)) => Bound::Excluded(a.max(b)),
+        (Bound::Included(a), Bound::Excluded(b)) | (Bound::Excluded(b), Bound::Included(a)) => {
+            if a >= b {
+                Bound::Included(a)
+            } else {
+                Bound::Excluded(b)
+            }
+        }
+    }
+}
+
 impl<Event: Eventlike + 'static, T: Deref<Target = CalendarCore<Event>>> Calendarlike for T {
     fn name(&self) -> &str {
         &self.friendly_name
@@ -124,50 +191,46 @@ impl<Event: Eventlike + 'static, T: Deref<Target = CalendarCore<Event>>> Calenda
         begin: Bound<DateTime<Utc>>,
         end: Bound<DateTime<Utc>>,
     ) -> Vec<Occurrence<'a>> {
-        let begin_dt = match &begin {
-            Bound::Unbounded => DateTime::<Utc>::MIN_UTC,
-            Bound::Included(dt) => dt.clone(),
-            Bound::Excluded(dt) => dt.clone() + Duration::seconds(1),
-        };
+        self.occurrences_in(begin, end).collect()
+    }
 
-        let end_dt = match &end {
-            Bound::Unbounded => DateTime::<Utc>::MAX_UTC,
-            Bound::Included(dt) => dt.clone(),
-            Bound::Excluded(dt) => dt.clone() - Duration::seconds(1),
-        };
-        self.events
-            .query(&Interval::new(begin, end))
-            .flat_map(|entry| entry.value().iter())
-            .flat_map(|event| {
-                event
-                    .occurrence_rule()
-                    .iter()
-                    .skip_while(|ts| ts.begin().with_timezone(&Utc) <= begin_dt)
-                    .take_while(|ts| ts.begin().with_timezone(&Utc) <= end_dt)
-                    .map(move |ts| Occurrence {
-                        span: ts.with_tz(&Utc),
-                        event: event as &'a dyn Eventlike,
-                    })
-            })
-            .collect()
+    fn occurrences_where<'a>(
+        &'a self,
+        begin: Bound<DateTime<Utc>>,
+        end: Bound<DateTime<Utc>>,
+        predicate: &dyn Fn(&dyn Eventlike) -> bool,
+    ) -> MergedOccurrences<'a> {
+        let calendar: &'a str = &self.friendly_name;
+
+        MergedOccurrences::new(
+            self.events
+                .query(&Interval::new(begin, end))
+                .flat_map(|entry| entry.value().iter())
+                .flat_map(|event| std::iter::once(event).chain(event.overrides()))
+                .filter(|event| predicate(*event))
+                .map(move |event| -> BoxedOccurrences<'a> {
+                    Box::new(
+                        event
+                            .occurrence_rule()
+                            .iter_overlapping(begin, end)
+                            .map(move |ts| Occurrence {
+                                span: ts.with_tz(&Utc),
+                                event: event as &'a dyn Eventlike,
+                                calendar,
+                            }),
+                    )
+                }),
+        )
     }
 
     fn filter_events<'a>(&'a self, filter: EventFilter) -> Vec<Occurrence<'a>> {
-        match filter {
-            EventFilter::InRange(begin, end) => {
-                let begin_dt = match begin {
-                    Bound::Unbounded => Bound::Unbounded,
-                    Bound::Included(dt) => Bound::Included(Utc.from_utc_datetime(&dt)),
-                    Bound::Excluded(dt) => Bound::Excluded(Utc.from_utc_datetime(&dt)),
-                };
-
-                let end_dt = match end {
-                    Bound::Unbounded => Bound::Unbounded,
-                    Bound::Included(dt) => Bound::Included(Utc.from_utc_datetime(&dt)),
-                    Bound::Excluded(dt) => Bound::Excluded(Utc.from_utc_datetime(&dt)),
-                };
-                self.events_in(begin_dt, end_dt)
-            }
-        }
+        let (begin, end) = filter.query_bounds();
+        let name = self.name();
+
+        self.occurrences_where(begin, end, &|event: &dyn Eventlike| {
+            filter.matches_event(event, name) != Some(false)
+        })
+        .filter(|occurrence| filter.matches(occurrence))
+        .collect()
     }
 }
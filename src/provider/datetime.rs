@@ -1,6 +1,10 @@
-use chrono::{DateTime, Duration, Month, NaiveDate, TimeZone};
-use rrule::{RRuleSet, RRuleSetIter};
+use chrono::{DateTime, Datelike, Duration, Local, Month, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use num_traits::FromPrimitive;
+use rrule::{Frequency, RRuleSet};
 use std::ops::Bound;
+use std::sync::Mutex;
+
+use super::{Error, ErrorKind, Result};
 
 pub fn days_of_month(month: &Month, year: i32) -> u32 {
     if month.number_from_month() == 12 {
@@ -14,6 +18,15 @@ pub fn days_of_month(month: &Month, year: i32) -> u32 {
     .num_days() as u32
 }
 
+/// The first and the last day of the month of `date`.
+pub fn month_of(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first = date.with_day(1).unwrap();
+    let month = Month::from_u32(date.month()).unwrap();
+    let last = first + Duration::days(days_of_month(&month, date.year()) as i64 - 1);
+
+    (first, last)
+}
+
 pub fn _days_of_year(year: i32) -> u32 {
     NaiveDate::from_ymd_opt(year, 1, 1)
         .unwrap()
@@ -21,6 +34,121 @@ pub fn _days_of_year(year: i32) -> u32 {
         .num_days() as u32
 }
 
+/// The UTC time at which the local day `date` begins.
+pub fn local_midnight(date: NaiveDate) -> NaiveDateTime {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        // Midnight may be skipped by a DST change, the day then starts an hour later
+        .or_else(|| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(1, 0, 0).unwrap())
+                .earliest()
+        })
+        .unwrap()
+        .naive_utc()
+}
+
+/// Parse an ISO 8601 duration like `PT1H30M`, `P2D` or `-P1W`, as used by iCalendar and
+/// JSCalendar.
+pub fn parse_iso8601_duration(input: &str) -> Result<Duration> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::DurationParse,
+            &format!("Invalid ISO 8601 duration '{}'", input),
+        )
+    };
+
+    let (sign, rest) = match input.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, input.strip_prefix('+').unwrap_or(input)),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) if !time.is_empty() => (date, time),
+        Some(_) => return Err(invalid()),
+        None if !rest.is_empty() => (rest, ""),
+        None => return Err(invalid()),
+    };
+
+    let mut duration = Duration::zero();
+    for (part, units) in [(date, "WD"), (time, "HMS")] {
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+
+            let value = number.parse::<i64>().map_err(|_| invalid())?;
+            number.clear();
+
+            if !units.contains(c) {
+                return Err(invalid());
+            }
+            duration = duration
+                + match c {
+                    'W' => Duration::weeks(value),
+                    'D' => Duration::days(value),
+                    'H' => Duration::hours(value),
+                    'M' => Duration::minutes(value),
+                    _ => Duration::seconds(value),
+                };
+        }
+
+        if !number.is_empty() {
+            return Err(invalid());
+        }
+    }
+
+    Ok(duration * sign)
+}
+
+/// Parse a human readable duration like `1h30m`, `2d`, `10 minutes` or `45` (minutes). Units
+/// are given by their initial or as words (`w`, `week(s)`, `d`, `day(s)`, `h`, `hour(s)`, `m`,
+/// `min`, `minute(s)`, `s`, `sec`, `second(s)`). ISO 8601 durations as used by iCalendar
+/// (`PT1H30M`) are accepted as well.
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    let invalid = || {
+        Error::new(
+            ErrorKind::DurationParse,
+            &format!(
+                "Invalid duration '{}', expected e.g. '1h30m', '10 minutes' or 'PT15M'",
+                input
+            ),
+        )
+    };
+
+    if input.trim_start_matches(&['+', '-'][..]).starts_with('P') {
+        return parse_iso8601_duration(input);
+    }
+
+    if let Ok(minutes) = input.parse::<i64>() {
+        return Ok(Duration::minutes(minutes));
+    }
+
+    let mut duration = Duration::zero();
+    let mut chars = input.chars().filter(|c| !c.is_whitespace()).peekable();
+    while chars.peek().is_some() {
+        let number = std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect::<String>();
+        let unit = std::iter::from_fn(|| chars.next_if(|c| c.is_alphabetic())).collect::<String>();
+        let value = number.parse::<i64>().map_err(|_| invalid())?;
+
+        duration = duration
+            + match unit.to_lowercase().as_str() {
+                "w" | "week" | "weeks" => Duration::weeks(value),
+                "d" | "day" | "days" => Duration::days(value),
+                "h" | "hour" | "hours" => Duration::hours(value),
+                "m" | "min" | "minute" | "minutes" => Duration::minutes(value),
+                "s" | "sec" | "second" | "seconds" => Duration::seconds(value),
+                _ => return Err(invalid()),
+            };
+    }
+
+    Ok(duration)
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub enum TimeSpan<Tz: TimeZone> {
     Allday(NaiveDate, Option<NaiveDate>, Tz),
@@ -58,35 +186,56 @@ impl<Tz: TimeZone> TimeSpan<Tz> {
         matches!(self, TimeSpan::Instant(_))
     }
 
+    /// The begin of the span. The dates of all-day events are floating, i.e., they begin at
+    /// local midnight regardless of the time zone of the span.
     pub fn begin(&self) -> DateTime<Tz> {
         match &self {
-            TimeSpan::Allday(begin, _, tz) => tz
-                .from_local_datetime(&begin.and_hms_opt(0, 0, 0).unwrap())
-                .earliest()
-                .unwrap(),
+            TimeSpan::Allday(begin, _, tz) => tz.from_utc_datetime(&local_midnight(*begin)),
             TimeSpan::TimePoints(begin, _) => begin.clone(),
             TimeSpan::Duration(begin, _) => begin.clone(),
             TimeSpan::Instant(begin) => begin.clone(),
         }
     }
 
+    /// The end of the span, which is not part of it. All-day events end at the beginning of
+    /// the day after their last day, in the local time zone as for their begin.
     pub fn end(&self) -> DateTime<Tz> {
         match &self {
-            TimeSpan::Allday(begin, end, tz) => tz
-                .from_local_datetime(
-                    &end.as_ref()
-                        .unwrap_or(&begin)
-                        .and_hms_opt(23, 59, 59)
-                        .unwrap(),
-                )
-                .earliest()
-                .unwrap(),
+            TimeSpan::Allday(begin, end, tz) => {
+                tz.from_utc_datetime(&local_midnight(end.unwrap_or(*begin + Duration::days(1))))
+            }
             TimeSpan::TimePoints(_, end) => end.clone(),
             TimeSpan::Duration(begin, dur) => begin.clone() + dur.clone(),
             TimeSpan::Instant(end) => end.clone(),
         }
     }
 
+    /// Whether the span ends after `begin`. Spans without duration end after `begin` if they
+    /// take place at or after it.
+    fn ends_after(&self, begin: &Bound<DateTime<Utc>>) -> bool {
+        let span_end = self.end().with_timezone(&Utc);
+        match begin {
+            Bound::Unbounded => true,
+            Bound::Included(begin) if self.begin() == self.end() => span_end >= *begin,
+            Bound::Included(begin) | Bound::Excluded(begin) => span_end > *begin,
+        }
+    }
+
+    /// Whether the span begins before `end`.
+    fn begins_before(&self, end: &Bound<DateTime<Utc>>) -> bool {
+        let span_begin = self.begin().with_timezone(&Utc);
+        match end {
+            Bound::Unbounded => true,
+            Bound::Included(end) => span_begin <= *end,
+            Bound::Excluded(end) => span_begin < *end,
+        }
+    }
+
+    /// Whether the span shares any point in time with the range between `begin` and `end`.
+    pub fn overlaps(&self, begin: &Bound<DateTime<Utc>>, end: &Bound<DateTime<Utc>>) -> bool {
+        self.ends_after(begin) && self.begins_before(end)
+    }
+
     pub fn duration(&self) -> Duration {
         match &self {
             TimeSpan::Allday(begin, end, _) => end
@@ -98,6 +247,7 @@ impl<Tz: TimeZone> TimeSpan<Tz> {
         }
     }
 
+    /// The span in the time zone `tz`. All-day spans keep their dates, as these are floating.
     pub fn with_tz<Tz2: TimeZone>(self, tz: &Tz2) -> TimeSpan<Tz2> {
         match self {
             TimeSpan::Allday(begin, end, _) => TimeSpan::<Tz2>::Allday(begin, end, tz.clone()),
@@ -112,16 +262,193 @@ impl<Tz: TimeZone> TimeSpan<Tz> {
     }
 }
 
+/// The instance of a recurring event beginning at `begin`, which lasts as long as the `first`
+/// one. Recurrence rules of all-day events expand to midnights in the time zone of the event,
+/// whose dates are those of the instances.
+fn instance_at<Tz: TimeZone>(first: &TimeSpan<Tz>, begin: DateTime<Tz>) -> TimeSpan<Tz> {
+    match first {
+        TimeSpan::Allday(first_date, end, tz) => {
+            let date = begin.date_naive();
+            TimeSpan::Allday(date, end.map(|end| date + (end - *first_date)), tz.clone())
+        }
+        _ => TimeSpan::from_start_and_duration(begin, first.duration()),
+    }
+}
+
 impl<Tz: TimeZone> From<TimeSpan<Tz>> for Duration {
     fn from(timespan: TimeSpan<Tz>) -> Self {
         timespan.duration()
     }
 }
 
+/// Begins of the instances of a recurrence rule in the ranges queried last. Each window only
+/// holds a bounded number of instances from a queried time on, so queries far into a
+/// long-running series neither store nor yield the whole series, while views switching between
+/// a few ranges, e.g., several month panes, find their instances in one of the windows.
+#[derive(Default)]
+pub struct InstanceCache {
+    /// Most recently used first
+    windows: Mutex<Vec<CachedInstances>>,
+}
+
+#[derive(Clone, Default)]
+struct CachedInstances {
+    /// All instances beginning at or after this time are cached, up to the last one
+    from: Option<DateTime<Utc>>,
+    begins: Vec<DateTime<Utc>>,
+    /// No instances follow the last cached one
+    exhausted: bool,
+}
+
+/// Number of instances expanded at once at most.
+const CACHED_INSTANCES: u16 = 256;
+
+/// Number of windows kept per rule.
+const CACHED_WINDOWS: usize = 4;
+
+impl CachedInstances {
+    /// The first instance beginning at or after `start`, `None` if it is not cached.
+    fn first_from(&self, start: DateTime<Utc>) -> Option<Option<DateTime<Utc>>> {
+        if self.from.map_or(true, |from| start < from) {
+            return None;
+        }
+
+        let idx = self.begins.partition_point(|begin| *begin < start);
+        match self.begins.get(idx) {
+            Some(begin) => Some(Some(*begin)),
+            None if self.exhausted => Some(None),
+            None => None,
+        }
+    }
+}
+
+/// A DTSTART whole periods of the only rule of `rrule` after its own one and at least a period
+/// before `start`, from which on the rule yields the same instances around `start`. The
+/// implicit parts of the rule, e.g., the day of month, were taken from the original DTSTART
+/// already. Rules with COUNT or exception rules are not moved, as these count from the
+/// original DTSTART.
+fn advanced_start(rrule: &RRuleSet, start: DateTime<Utc>) -> Option<DateTime<rrule::Tz>> {
+    let rule = match rrule.get_rrule().as_slice() {
+        [rule] if rule.get_count().is_none() && rrule.get_exrule().is_empty() => rule,
+        _ => return None,
+    };
+
+    let dt_start = rrule.get_dt_start();
+    let tz = dt_start.timezone();
+    let first = dt_start.naive_local().date();
+    let target = start.with_timezone(&tz).naive_local().date();
+    let interval = i64::from(rule.get_interval().max(1));
+
+    // Instances of the period of DTSTART that precede it are not yielded, so the new DTSTART
+    // stays a period ahead of `start`
+    let date = match rule.get_freq() {
+        Frequency::Daily | Frequency::Weekly => {
+            let days = match rule.get_freq() {
+                Frequency::Weekly => 7 * interval,
+                _ => interval,
+            };
+            let periods = (target - first).num_days() / days - 1;
+            if periods <= 0 {
+                return None;
+            }
+            first + Duration::days(periods * days)
+        }
+        Frequency::Monthly | Frequency::Yearly => {
+            let months = match rule.get_freq() {
+                Frequency::Yearly => 12 * interval,
+                _ => interval,
+            };
+            let month_index =
+                |date: NaiveDate| i64::from(date.year()) * 12 + i64::from(date.month0());
+            let periods = (month_index(target) - month_index(first)) / months - 1;
+            if periods <= 0 {
+                return None;
+            }
+            // The first of the month exists in every month
+            let month = month_index(first) + periods * months;
+            NaiveDate::from_ymd_opt((month / 12) as i32, (month % 12) as u32 + 1, 1)?
+        }
+        _ => return None,
+    };
+
+    tz.from_local_datetime(&date.and_time(dt_start.naive_local().time()))
+        .earliest()
+}
+
+/// `rrule` limited to the instances at or after `start`. rrule expands all instances from
+/// DTSTART on to find these, so DTSTART is moved close to `start` if possible.
+fn seek(rrule: &RRuleSet, start: DateTime<Utc>) -> RRuleSet {
+    let rrule = match advanced_start(rrule, start) {
+        Some(dt_start) => {
+            let mut moved = RRuleSet::new(dt_start);
+            for rule in rrule.get_rrule() {
+                moved = moved.rrule(rule.clone());
+            }
+            for rdate in rrule.get_rdate() {
+                moved = moved.rdate(rdate.clone());
+            }
+            for exdate in rrule.get_exdate() {
+                moved = moved.exdate(exdate.clone());
+            }
+            moved
+        }
+        None => rrule.clone(),
+    };
+
+    // Whether `after` includes the given time differs between versions of rrule
+    let after = start
+        .checked_sub_signed(Duration::seconds(1))
+        .unwrap_or(start)
+        .with_timezone(&rrule::Tz::Tz(chrono_tz::UTC));
+    rrule.after(after)
+}
+
+impl InstanceCache {
+    /// The begin of the first instance of `rrule` at or after `start`.
+    fn first_from(&self, rrule: &RRuleSet, start: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut windows = self.windows.lock().unwrap();
+
+        let cached = windows
+            .iter()
+            .enumerate()
+            .find_map(|(idx, window)| Some((idx, window.first_from(start)?)));
+        if let Some((idx, first)) = cached {
+            let window = windows.remove(idx);
+            windows.insert(0, window);
+            return first;
+        }
+
+        let result = seek(rrule, start).all(CACHED_INSTANCES);
+        let window = CachedInstances {
+            from: Some(start),
+            begins: result
+                .dates
+                .iter()
+                .map(|dt| dt.with_timezone(&Utc))
+                .filter(|begin| *begin >= start)
+                .collect(),
+            exhausted: !result.limited,
+        };
+        let first = window.first_from(start).flatten();
+
+        windows.insert(0, window);
+        windows.truncate(CACHED_WINDOWS);
+        first
+    }
+}
+
+impl Clone for InstanceCache {
+    fn clone(&self) -> Self {
+        InstanceCache {
+            windows: Mutex::new(self.windows.lock().unwrap().clone()),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum OccurrenceRule<Tz: TimeZone> {
     Onetime(TimeSpan<Tz>),
-    Recurring(TimeSpan<Tz>, RRuleSet),
+    Recurring(TimeSpan<Tz>, RRuleSet, InstanceCache),
 }
 
 impl<Tz: TimeZone> OccurrenceRule<Tz> {
@@ -129,7 +456,7 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => ts.is_allday(),
-            Recurring(ts, _) => ts.is_allday(),
+            Recurring(ts, _, _) => ts.is_allday(),
         }
     }
 
@@ -140,14 +467,14 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
 
     pub fn is_recurring(&self) -> bool {
         use OccurrenceRule::*;
-        matches!(self, Recurring(_, _))
+        matches!(self, Recurring(_, _, _))
     }
 
     pub fn first(&self) -> TimeSpan<Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => ts.clone(),
-            Recurring(ts, _) => ts.clone(),
+            Recurring(ts, _, _) => ts.clone(),
         }
     }
 
@@ -155,13 +482,13 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => Some(ts.clone()),
-            Recurring(ts, rrule) => {
+            Recurring(ts, rrule, _) => {
                 // check if any of the rules is infinite
                 if rrule.get_rrule().iter().all(|r| r.get_count().is_some()) {
                     rrule
                         .into_iter()
                         .last()
-                        .map(|_| TimeSpan::from_start_and_duration(ts.begin(), ts.duration()))
+                        .map(|dt| instance_at(ts, dt.with_timezone(&ts.begin().timezone())))
                 } else {
                     None
                 }
@@ -173,7 +500,7 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => ts.duration(),
-            Recurring(ts, _) => ts.duration(),
+            Recurring(ts, _, _) => ts.duration(),
         }
     }
 
@@ -181,15 +508,17 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => OccurrenceRule::<Tz2>::Onetime(ts.with_tz(tz)),
-            Recurring(ts, rrule) => OccurrenceRule::<Tz2>::Recurring(ts.with_tz(tz), rrule),
+            Recurring(ts, rrule, cache) => {
+                OccurrenceRule::<Tz2>::Recurring(ts.with_tz(tz), rrule, cache)
+            }
         }
     }
 
     pub fn with_recurring(self, rule: RRuleSet) -> Self {
         use OccurrenceRule::*;
         match self {
-            Onetime(ts) => OccurrenceRule::Recurring(ts, rule),
-            Recurring(ts, _) => OccurrenceRule::Recurring(ts, rule),
+            Onetime(ts) => OccurrenceRule::Recurring(ts, rule, InstanceCache::default()),
+            Recurring(ts, _, _) => OccurrenceRule::Recurring(ts, rule, InstanceCache::default()),
         }
     }
 
@@ -197,26 +526,62 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => ts.begin().timezone(),
-            Recurring(ts, _) => ts.begin().timezone(),
+            Recurring(ts, _, _) => ts.begin().timezone(),
         }
     }
 
     pub fn iter<'a>(&'a self) -> OccurrenceIter<'a, Tz> {
+        self.iter_from(DateTime::<Utc>::MIN_UTC)
+    }
+
+    /// Iterate over the occurrences beginning at or after `start`.
+    pub fn iter_from<'a>(&'a self, start: DateTime<Utc>) -> OccurrenceIter<'a, Tz> {
         use OccurrenceRule::*;
         match self {
             Onetime(ts) => OccurrenceIter {
-                start: Some(ts.clone()),
-                rrule_iter: None,
+                start: Some(ts.clone()).filter(|ts| ts.begin().with_timezone(&Utc) >= start),
+                recurring: None,
+                next_begin: start,
+                min_begin: start,
                 tz: self.timezone(),
             },
-            Recurring(ts, rrule) => OccurrenceIter {
+            Recurring(ts, rrule, cache) => OccurrenceIter {
                 start: Some(ts.clone()),
-                rrule_iter: Some(rrule.into_iter()),
+                recurring: Some((rrule, cache)),
+                // Midnights in the time zone of all-day events differ from the local ones, by
+                // which their instances begin, by less than a day
+                next_begin: if ts.is_allday() {
+                    start.checked_sub_signed(Duration::days(1)).unwrap_or(start)
+                } else {
+                    start
+                },
+                min_begin: start,
                 tz: self.timezone(),
             },
         }
     }
 
+    /// Iterate over the occurrences overlapping the range between `begin` and `end`, including
+    /// those that began before `begin` and are still ongoing.
+    pub fn iter_overlapping<'a>(
+        &'a self,
+        begin: Bound<DateTime<Utc>>,
+        end: Bound<DateTime<Utc>>,
+    ) -> impl Iterator<Item = TimeSpan<Tz>> + 'a {
+        // All instances last as long as the first one, so overlapping instances begin at most
+        // that long before `begin`. The extra day covers all-day events across DST changes.
+        let seek = match begin {
+            Bound::Included(dt) | Bound::Excluded(dt) => dt
+                .checked_sub_signed(self.duration() + Duration::days(1))
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+            Bound::Unbounded => DateTime::<Utc>::MIN_UTC,
+        };
+
+        self.iter_from(seek)
+            .take_while(move |ts| ts.begins_before(&end))
+            .filter(move |ts| ts.ends_after(&begin))
+    }
+
     pub fn as_range<'a>(&'a self) -> (Bound<DateTime<Tz>>, Bound<DateTime<Tz>>) {
         (
             Bound::Included(self.first().begin()),
@@ -228,7 +593,11 @@ impl<Tz: TimeZone> OccurrenceRule<Tz> {
 
 pub struct OccurrenceIter<'a, Tz: TimeZone> {
     start: Option<TimeSpan<Tz>>,
-    rrule_iter: Option<RRuleSetIter<'a>>,
+    recurring: Option<(&'a RRuleSet, &'a InstanceCache)>,
+    /// The instances of the recurrence rule beginning before this time were yielded
+    next_begin: DateTime<Utc>,
+    /// Only occurrences beginning at or after this time are yielded
+    min_begin: DateTime<Utc>,
     tz: Tz,
 }
 
@@ -236,13 +605,16 @@ impl<Tz: TimeZone> Iterator for OccurrenceIter<'_, Tz> {
     type Item = TimeSpan<Tz>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(it) = &mut self.rrule_iter {
-            it.next().map(|dt| {
-                TimeSpan::from_start_and_duration(
-                    dt.with_timezone(&self.tz),
-                    self.start.as_ref().unwrap().duration(),
-                )
-            })
+        if let Some((rrule, cache)) = self.recurring {
+            loop {
+                let begin = cache.first_from(rrule, self.next_begin)?;
+                self.next_begin = begin + Duration::nanoseconds(1);
+
+                let span = instance_at(self.start.as_ref().unwrap(), begin.with_timezone(&self.tz));
+                if span.begin().with_timezone(&Utc) >= self.min_begin {
+                    return Some(span);
+                }
+            }
         } else {
             self.start.take()
         }
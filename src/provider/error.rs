@@ -93,6 +93,24 @@ impl<E: std::fmt::Debug> From<nom::Err<E>> for Error {
     }
 }
 
+impl From<quick_xml::Error> for Error {
+    fn from(xml_error: quick_xml::Error) -> Self {
+        Error::new(
+            ErrorKind::ParseError,
+            &format!("Invalid XML: {}", xml_error),
+        )
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(csv_error: csv::Error) -> Self {
+        Error::new(
+            ErrorKind::ParseError,
+            &format!("Invalid CSV: {}", csv_error),
+        )
+    }
+}
+
 impl From<RRuleError> for Error {
     fn from(rrule_error: RRuleError) -> Self {
         Error::new(ErrorKind::RecurRuleParse, &format!("{}", rrule_error))
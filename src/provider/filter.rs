@@ -0,0 +1,544 @@
+//! Filters selecting occurrences of events, and a query language to write them.
+//!
+//! A query is a list of terms which all have to match, e.g.
+//! `calendar:work and category:review and not status:cancelled and duration>1h after:2024-01-01`.
+//! Terms are combined with `and` (which may be omitted), `or` and `not`, and grouped with
+//! parentheses. `@name` refers to a saved filter of the config. The terms are
+//!
+//! - `calendar:NAME`, `category:NAME`: events of the calendar or with the category
+//! - `status:tentative|confirmed|cancelled|none`: events with the status
+//! - `title:TEXT`, `location:TEXT`, `text:TEXT` or just `TEXT`: events containing the text in
+//!   their title, location or any of title, description, location and categories
+//! - `duration<1h`, `priority<=2` (also `<`, `>=`, `>`, `=` and `:`): events with a duration
+//!   or priority comparing to the value
+//! - `after:DATE`, `before:DATE`, `on:DATE`: occurrences overlapping the local time from,
+//!   until or on `DATE`, which is either `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM`
+//!
+//! Values containing spaces or parentheses, and text equal to a keyword, are put in double
+//! quotes, and all text is compared case-insensitively.
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until, take_while1},
+    character::complete::{alpha1, char, multispace0, satisfy},
+    combinator::{all_consuming, cut, map, map_res, not, value, verify},
+    error::{FromExternalError, ParseError},
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
+
+use super::{
+    local_midnight, parse_duration, Error, ErrorKind, EventStatus, Eventlike, Occurrence, Result,
+};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Saved filters may refer to other saved filters, but not deeper than this. This also stops
+/// saved filters referring to themselves.
+const MAX_SAVED_DEPTH: usize = 16;
+
+const KEYWORDS: &[&str] = &["and", "or", "not"];
+
+/// Queries not bounding the end of the occurrences search this many days past their begin, or
+/// past now if that is later. Without an end, a filter which no longer matches an infinitely
+/// recurring event would search its occurrences forever.
+const QUERY_HORIZON_DAYS: i64 = 5 * 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+            Comparison::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EventFilter {
+    /// Occurrences overlapping the range of UTC times
+    InRange(Bound<NaiveDateTime>, Bound<NaiveDateTime>),
+    Calendar(String),
+    /// Events with the lowercase category
+    Category(String),
+    /// Events with the status, `None` matching events without status
+    Status(Option<EventStatus>),
+    /// Events containing the lowercase text in their title, description, location or
+    /// categories
+    Text(String),
+    /// Events containing the lowercase text in their title
+    Title(String),
+    /// Events containing the lowercase text in their location
+    Location(String),
+    Duration(Comparison, Duration),
+    /// Events whose priority number compares to the value. Events without priority never match.
+    Priority(Comparison, u8),
+    Not(Box<EventFilter>),
+    And(Box<EventFilter>, Box<EventFilter>),
+    Or(Box<EventFilter>, Box<EventFilter>),
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        EventFilter::InRange(Bound::Unbounded, Bound::Unbounded)
+    }
+}
+
+impl EventFilter {
+    /// Parse a filter query, resolving references `@name` to the queries of `saved`.
+    pub fn parse(input: &str, saved: &HashMap<String, String>) -> Result<Self> {
+        FilterParser { saved, depth: 0 }.parse(input)
+    }
+
+    pub fn datetime_range<R: RangeBounds<NaiveDateTime>>(self, range: R) -> Self {
+        self.and(EventFilter::InRange(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        ))
+    }
+
+    pub fn and(self, other: EventFilter) -> Self {
+        EventFilter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: EventFilter) -> Self {
+        EventFilter::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn not(self) -> Self {
+        EventFilter::Not(Box::new(self))
+    }
+
+    /// Whether the occurrence matches the filter.
+    pub fn matches(&self, occurrence: &Occurrence) -> bool {
+        match self {
+            EventFilter::InRange(begin, end) => occurrence
+                .span
+                .overlaps(&utc_bound(*begin), &utc_bound(*end)),
+            EventFilter::Not(filter) => !filter.matches(occurrence),
+            EventFilter::And(a, b) => a.matches(occurrence) && b.matches(occurrence),
+            EventFilter::Or(a, b) => a.matches(occurrence) || b.matches(occurrence),
+            _ => self.matches_event(occurrence.event, occurrence.calendar) == Some(true),
+        }
+    }
+
+    /// Whether all occurrences of `event` in `calendar` match the filter (`Some(true)`), none of
+    /// them does (`Some(false)`), or it depends on the occurrence (`None`).
+    pub fn matches_event(&self, event: &dyn Eventlike, calendar: &str) -> Option<bool> {
+        let contains = |text: Option<&str>, needle: &str| {
+            text.map_or(false, |text| text.to_lowercase().contains(needle))
+        };
+
+        match self {
+            EventFilter::InRange(..) => None,
+            EventFilter::Calendar(name) => Some(calendar == name),
+            EventFilter::Category(category) => Some(
+                event
+                    .categories()
+                    .iter()
+                    .any(|c| c.to_lowercase() == *category),
+            ),
+            EventFilter::Status(status) => Some(event.status() == *status),
+            EventFilter::Text(text) => Some(
+                contains(Some(event.title()), text)
+                    || contains(event.description(), text)
+                    || contains(event.location(), text)
+                    || event
+                        .categories()
+                        .iter()
+                        .any(|c| contains(Some(c.as_str()), text)),
+            ),
+            EventFilter::Title(text) => Some(contains(Some(event.title()), text)),
+            EventFilter::Location(text) => Some(contains(event.location(), text)),
+            EventFilter::Duration(comparison, duration) => {
+                Some(comparison.holds(event.duration().cmp(duration)))
+            }
+            EventFilter::Priority(comparison, priority) => Some(
+                event
+                    .priority()
+                    .map_or(false, |own| comparison.holds(own.cmp(priority))),
+            ),
+            EventFilter::Not(filter) => filter.matches_event(event, calendar).map(|m| !m),
+            EventFilter::And(a, b) => {
+                match (
+                    a.matches_event(event, calendar),
+                    b.matches_event(event, calendar),
+                ) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            EventFilter::Or(a, b) => {
+                match (
+                    a.matches_event(event, calendar),
+                    b.matches_event(event, calendar),
+                ) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// A range containing all matching occurrences, which may contain others as well.
+    pub fn bounds(&self) -> (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>) {
+        match self {
+            EventFilter::InRange(begin, end) => (utc_bound(*begin), utc_bound(*end)),
+            EventFilter::And(a, b) => {
+                let (a, b) = (a.bounds(), b.bounds());
+                (later_begin(a.0, b.0), earlier_end(a.1, b.1))
+            }
+            EventFilter::Or(a, b) => {
+                let (a, b) = (a.bounds(), b.bounds());
+                (earlier_begin(a.0, b.0), later_end(a.1, b.1))
+            }
+            _ => (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+
+    /// The bounds of the occurrences to search for matches, which are `bounds` with an
+    /// unbounded end capped at a horizon of a few years.
+    pub fn query_bounds(&self) -> (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>) {
+        let (begin, end) = self.bounds();
+        let end = match end {
+            Bound::Unbounded => {
+                let from = match begin {
+                    Bound::Included(begin) | Bound::Excluded(begin) => begin.max(Utc::now()),
+                    Bound::Unbounded => Utc::now(),
+                };
+                Bound::Excluded(from + Duration::days(QUERY_HORIZON_DAYS))
+            }
+            end => end,
+        };
+
+        (begin, end)
+    }
+}
+
+fn utc_bound(bound: Bound<NaiveDateTime>) -> Bound<DateTime<Utc>> {
+    match bound {
+        Bound::Included(dt) => Bound::Included(Utc.from_utc_datetime(&dt)),
+        Bound::Excluded(dt) => Bound::Excluded(Utc.from_utc_datetime(&dt)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+type UtcBound = Bound<DateTime<Utc>>;
+
+fn later_begin(a: UtcBound, b: UtcBound) -> UtcBound {
+    match (a, b) {
+        (Bound::Unbounded, other) | (other, Bound::Unbounded) => other,
+        (Bound::Included(x), Bound::Included(y)) => Bound::Included(x.max(y)),
+        (Bound::Excluded(x), Bound::Excluded(y)) => Bound::Excluded(x.max(y)),
+        (Bound::Included(x), Bound::Excluded(y)) | (Bound::Excluded(y), Bound::Included(x)) => {
+            if x > y {
+                Bound::Included(x)
+            } else {
+                Bound::Excluded(y)
+            }
+        }
+    }
+}
+
+fn earlier_begin(a: UtcBound, b: UtcBound) -> UtcBound {
+    match (a, b) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => Bound::Unbounded,
+        (Bound::Included(x), Bound::Included(y)) => Bound::Included(x.min(y)),
+        (Bound::Excluded(x), Bound::Excluded(y)) => Bound::Excluded(x.min(y)),
+        (Bound::Included(x), Bound::Excluded(y)) | (Bound::Excluded(y), Bound::Included(x)) => {
+            if x <= y {
+                Bound::Included(x)
+            } else {
+                Bound::Excluded(y)
+            }
+        }
+    }
+}
+
+fn earlier_end(a: UtcBound, b: UtcBound) -> UtcBound {
+    match (a, b) {
+        (Bound::Unbounded, other) | (other, Bound::Unbounded) => other,
+        (Bound::Included(x), Bound::Included(y)) => Bound::Included(x.min(y)),
+        (Bound::Excluded(x), Bound::Excluded(y)) => Bound::Excluded(x.min(y)),
+        (Bound::Included(x), Bound::Excluded(y)) | (Bound::Excluded(y), Bound::Included(x)) => {
+            if x < y {
+                Bound::Included(x)
+            } else {
+                Bound::Excluded(y)
+            }
+        }
+    }
+}
+
+fn later_end(a: UtcBound, b: UtcBound) -> UtcBound {
+    match (a, b) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => Bound::Unbounded,
+        (Bound::Included(x), Bound::Included(y)) => Bound::Included(x.max(y)),
+        (Bound::Excluded(x), Bound::Excluded(y)) => Bound::Excluded(x.max(y)),
+        (Bound::Included(x), Bound::Excluded(y)) | (Bound::Excluded(y), Bound::Included(x)) => {
+            if x >= y {
+                Bound::Included(x)
+            } else {
+                Bound::Excluded(y)
+            }
+        }
+    }
+}
+
+/// Parser error, carrying the reason if it is more than a syntax error.
+struct FilterError<'i> {
+    input: &'i str,
+    cause: Option<Error>,
+}
+
+impl<'i> ParseError<&'i str> for FilterError<'i> {
+    fn from_error_kind(input: &'i str, _kind: nom::error::ErrorKind) -> Self {
+        FilterError { input, cause: None }
+    }
+
+    fn append(_input: &'i str, _kind: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'i> FromExternalError<&'i str, Error> for FilterError<'i> {
+    fn from_external_error(input: &'i str, _kind: nom::error::ErrorKind, cause: Error) -> Self {
+        FilterError {
+            input,
+            cause: Some(cause),
+        }
+    }
+}
+
+type FilterResult<'i, T> = IResult<&'i str, T, FilterError<'i>>;
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::ParseError, &message)
+}
+
+/// A local date or date with time as UTC.
+fn parse_local_datetime(value: &str) -> Result<NaiveDateTime> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, DATE_FORMAT) {
+        return Ok(local_midnight(date));
+    }
+
+    let datetime = NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)?;
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|datetime| datetime.naive_utc())
+        .ok_or_else(|| invalid(format!("'{}' does not exist in the local time zone", value)))
+}
+
+/// The filter of a term `key:value`, or `key<value` etc. if `comparison` is given.
+fn term_filter(key: &str, comparison: Option<Comparison>, value: &str) -> Result<EventFilter> {
+    let filter = match key {
+        "duration" => {
+            return Ok(EventFilter::Duration(
+                comparison.unwrap_or(Comparison::Equal),
+                parse_duration(value)?,
+            ))
+        }
+        "priority" => {
+            let priority = value
+                .parse()
+                .map_err(|_| invalid(format!("Invalid priority '{}'", value)))?;
+            return Ok(EventFilter::Priority(
+                comparison.unwrap_or(Comparison::Equal),
+                priority,
+            ));
+        }
+        "calendar" => EventFilter::Calendar(value.to_owned()),
+        "category" => EventFilter::Category(value.to_lowercase()),
+        "status" if value.eq_ignore_ascii_case("none") => EventFilter::Status(None),
+        "status" => EventFilter::Status(Some(value.parse()?)),
+        "text" => EventFilter::Text(value.to_lowercase()),
+        "title" => EventFilter::Title(value.to_lowercase()),
+        "location" => EventFilter::Location(value.to_lowercase()),
+        "after" => EventFilter::InRange(
+            Bound::Included(parse_local_datetime(value)?),
+            Bound::Unbounded,
+        ),
+        "before" => EventFilter::InRange(
+            Bound::Unbounded,
+            Bound::Excluded(parse_local_datetime(value)?),
+        ),
+        "on" => {
+            let date = NaiveDate::parse_from_str(value, DATE_FORMAT)?;
+            EventFilter::InRange(
+                Bound::Included(local_midnight(date)),
+                Bound::Excluded(local_midnight(date.succ_opt().unwrap())),
+            )
+        }
+        _ => return Err(invalid(format!("Unknown filter '{}'", key))),
+    };
+
+    if comparison.is_some() {
+        Err(invalid(format!("'{}' can only be compared with ':'", key)))
+    } else {
+        Ok(filter)
+    }
+}
+
+fn keyword<'i>(word: &'static str) -> impl FnMut(&'i str) -> FilterResult<'i, &'i str> {
+    preceded(
+        multispace0,
+        terminated(tag(word), not(satisfy(|c: char| c.is_alphanumeric()))),
+    )
+}
+
+fn quoted(input: &str) -> FilterResult<'_, &str> {
+    delimited(char('"'), take_until("\""), char('"'))(input)
+}
+
+/// A word, which ends at whitespace and parentheses.
+fn word(input: &str) -> FilterResult<'_, &str> {
+    take_while1(|c: char| !c.is_whitespace() && c != '(' && c != ')' && c != '"')(input)
+}
+
+/// A quoted string or a word.
+fn text_value(input: &str) -> FilterResult<'_, &str> {
+    alt((quoted, word))(input)
+}
+
+fn comparison_operator(input: &str) -> FilterResult<'_, Option<Comparison>> {
+    alt((
+        value(None, char(':')),
+        value(Some(Comparison::LessOrEqual), tag("<=")),
+        value(Some(Comparison::GreaterOrEqual), tag(">=")),
+        value(Some(Comparison::Less), char('<')),
+        value(Some(Comparison::Greater), char('>')),
+        value(Some(Comparison::Equal), char('=')),
+    ))(input)
+}
+
+struct FilterParser<'s> {
+    saved: &'s HashMap<String, String>,
+    /// Number of saved filters this one is nested in
+    depth: usize,
+}
+
+impl FilterParser<'_> {
+    fn parse<'i>(&self, input: &'i str) -> Result<EventFilter> {
+        let result =
+            all_consuming(terminated(|i: &'i str| self.or_expression(i), multispace0))(input);
+
+        match result {
+            Ok((_, filter)) => Ok(filter),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                Err(e.cause.unwrap_or_else(|| {
+                    invalid(format!(
+                        "Invalid filter '{}' at '{}'",
+                        input,
+                        e.input.trim()
+                    ))
+                }))
+            }
+            Err(nom::Err::Incomplete(_)) => Err(invalid(format!("Incomplete filter '{}'", input))),
+        }
+    }
+
+    fn saved_filter(&self, name: &str) -> Result<EventFilter> {
+        let query = self
+            .saved
+            .get(name)
+            .ok_or_else(|| invalid(format!("Unknown saved filter '{}'", name)))?;
+
+        if self.depth >= MAX_SAVED_DEPTH {
+            return Err(invalid(format!(
+                "Saved filter '{}' is nested too deeply or refers to itself",
+                name
+            )));
+        }
+
+        FilterParser {
+            saved: self.saved,
+            depth: self.depth + 1,
+        }
+        .parse(query)
+    }
+
+    fn or_expression<'i>(&self, input: &'i str) -> FilterResult<'i, EventFilter> {
+        let (input, first) = self.and_expression(input)?;
+        let (input, others) =
+            many0(preceded(keyword("or"), |i: &'i str| self.and_expression(i)))(input)?;
+
+        Ok((input, others.into_iter().fold(first, EventFilter::or)))
+    }
+
+    fn and_expression<'i>(&self, input: &'i str) -> FilterResult<'i, EventFilter> {
+        let (input, first) = self.unary(input)?;
+        let (input, others) = many0(preceded(
+            alt((keyword("and"), multispace0)),
+            |i: &'i str| self.unary(i),
+        ))(input)?;
+
+        Ok((input, others.into_iter().fold(first, EventFilter::and)))
+    }
+
+    fn unary<'i>(&self, input: &'i str) -> FilterResult<'i, EventFilter> {
+        alt((
+            map(
+                preceded(keyword("not"), cut(|i: &'i str| self.unary(i))),
+                EventFilter::not,
+            ),
+            |i: &'i str| self.atom(i),
+        ))(input)
+    }
+
+    fn atom<'i>(&self, input: &'i str) -> FilterResult<'i, EventFilter> {
+        let (input, _) = multispace0::<_, FilterError>(input)?;
+
+        alt((
+            delimited(
+                char('('),
+                cut(|i: &'i str| self.or_expression(i)),
+                cut(preceded(multispace0, char(')'))),
+            ),
+            preceded(
+                char('@'),
+                cut(map_res(
+                    take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-'),
+                    |name: &str| self.saved_filter(name),
+                )),
+            ),
+            |i: &'i str| self.term(i),
+            map(
+                alt((quoted, verify(word, |text: &str| !KEYWORDS.contains(&text)))),
+                |text: &str| EventFilter::Text(text.to_lowercase()),
+            ),
+        ))(input)
+    }
+
+    fn term<'i>(&self, input: &'i str) -> FilterResult<'i, EventFilter> {
+        let (rest, (key, (comparison, value))) =
+            pair(alpha1, tuple((comparison_operator, cut(text_value))))(input)?;
+
+        match term_filter(key, comparison, value) {
+            Ok(filter) => Ok((rest, filter)),
+            Err(cause) => Err(nom::Err::Failure(FilterError {
+                input,
+                cause: Some(cause),
+            })),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::{Duration, Utc};
+use ical::parser::ical::component::IcalAlarm;
+use ical::property::Property;
+use std::convert::TryFrom;
+
+use super::datetime::{IcalDateTime, IcalDuration};
+use super::value::param_value;
+use crate::provider::{Alarm, AlarmAction, AlarmRelation, AlarmTrigger, Error, ErrorKind, Result};
+
+fn property<'a>(alarm: &'a IcalAlarm, name: &str) -> Option<&'a Property> {
+    alarm.properties.iter().find(|p| p.name == name)
+}
+
+fn text_property(name: &str, value: &str) -> Property {
+    Property {
+        name: name.to_owned(),
+        params: None,
+        value: Some(value.to_owned()),
+    }
+}
+
+fn parse_trigger(trigger: &Property) -> Result<AlarmTrigger> {
+    if param_value(trigger, "VALUE").map_or(false, |v| v.eq_ignore_ascii_case("DATE-TIME")) {
+        return Ok(AlarmTrigger::Absolute(
+            IcalDateTime::try_from(trigger)?.as_datetime(&Utc),
+        ));
+    }
+
+    let offset = Duration::from(IcalDuration::try_from(trigger)?);
+    let related = match param_value(trigger, "RELATED") {
+        Some(related) if related.eq_ignore_ascii_case("END") => AlarmRelation::End,
+        _ => AlarmRelation::Start,
+    };
+
+    Ok(AlarmTrigger::Relative { offset, related })
+}
+
+impl TryFrom<&IcalAlarm> for Alarm {
+    type Error = Error;
+
+    fn try_from(alarm: &IcalAlarm) -> Result<Self> {
+        let trigger = property(alarm, "TRIGGER")
+            .ok_or_else(|| Error::new(ErrorKind::EventMissingKey, "Alarm without TRIGGER"))?;
+
+        let repeat = property(alarm, "REPEAT")
+            .and_then(|p| p.value.as_deref())
+            .and_then(|repeat| repeat.trim().parse().ok())
+            .unwrap_or(0);
+        let interval = property(alarm, "DURATION")
+            .map(IcalDuration::try_from)
+            .transpose()?
+            .map(Duration::from);
+
+        Ok(Alarm {
+            action: property(alarm, "ACTION")
+                .and_then(|p| p.value.as_deref())
+                .map(AlarmAction::from)
+                .unwrap_or_default(),
+            trigger: parse_trigger(trigger)?,
+            repeat,
+            interval,
+            description: property(alarm, "DESCRIPTION").and_then(|p| p.value.clone()),
+        })
+    }
+}
+
+impl From<&Alarm> for IcalAlarm {
+    fn from(alarm: &Alarm) -> Self {
+        let mut ical = IcalAlarm::new();
+
+        ical.properties
+            .push(text_property("ACTION", &alarm.action.to_string()));
+
+        ical.properties.push(match &alarm.trigger {
+            AlarmTrigger::Relative { offset, related } => Property {
+                name: "TRIGGER".to_owned(),
+                params: match related {
+                    AlarmRelation::End => {
+                        Some(vec![("RELATED".to_owned(), vec!["END".to_owned()])])
+                    }
+                    AlarmRelation::Start => None,
+                },
+                value: Some(IcalDuration::from(*offset).to_string()),
+            },
+            AlarmTrigger::Absolute(at) => {
+                let mut trigger = IcalDateTime::Utc(*at).to_property("TRIGGER".to_owned());
+                trigger.params = Some(vec![("VALUE".to_owned(), vec!["DATE-TIME".to_owned()])]);
+                trigger
+            }
+        });
+
+        if let (true, Some(interval)) = (alarm.repeat > 0, alarm.interval) {
+            ical.properties
+                .push(text_property("REPEAT", &alarm.repeat.to_string()));
+            ical.properties.push(text_property(
+                "DURATION",
+                &IcalDuration::from(interval).to_string(),
+            ));
+        }
+
+        // DISPLAY and EMAIL alarms require a description
+        if alarm.description.is_some() || alarm.action != AlarmAction::Audio {
+            ical.properties.push(text_property(
+                "DESCRIPTION",
+                alarm.description.as_deref().unwrap_or("Reminder"),
+            ));
+        }
+
+        ical
+    }
+}
@@ -1,4 +1,5 @@
 use chrono_tz::Tz;
+use ical::parser::ical::component::IcalCalendar;
 use std::collections::HashSet;
 use std::fs;
 use std::io::Write;
@@ -9,9 +10,10 @@ use crate::config::CalendarConfig;
 use crate::provider::ical::event::uid_from_path;
 use crate::provider::ical::ICAL_FILE_EXT;
 use crate::provider::{self, CalendarCore, Eventlike};
-use crate::provider::{MutCalendarlike, NewEvent, OccurrenceRule, TimeSpan};
+use crate::provider::{MutCalendarlike, NewEvent, OccurrenceRule};
 
 use super::ser::to_string;
+use super::split_calendar;
 use super::{Error, ErrorKind, Event, Result};
 
 pub struct Calendar {
@@ -82,48 +84,67 @@ pub fn from_dir(
     })
 }
 
-impl MutCalendarlike for Calendar {
-    fn add_event(&mut self, new_event: NewEvent<Tz>) -> Result<()> {
-        let mut occurrence = if let Some(end) = new_event.end {
-            OccurrenceRule::Onetime(TimeSpan::from_start_and_end(new_event.begin, end))
-        } else if let Some(duration) = new_event.duration {
-            OccurrenceRule::Onetime(TimeSpan::from_start_and_duration(new_event.begin, duration))
-        } else {
-            OccurrenceRule::Onetime(TimeSpan::from_start(new_event.begin))
-        };
+impl Calendar {
+    /// Import all events of a calendar object. Every event is stored in its own file named
+    /// after its UID. Events that cannot be imported are skipped. Returns the number of imported
+    /// events.
+    pub fn import(&mut self, ical: IcalCalendar) -> Result<usize> {
+        let mut imported = 0;
 
-        if let Some(rrule) = new_event.rrule {
-            occurrence = occurrence.with_recurring(
-                rrule.build(
-                    new_event
-                        .begin
-                        .with_timezone(&rrule::Tz::Tz(new_event.begin.timezone())),
-                )?,
-            );
+        for calendar in split_calendar(ical) {
+            match self.import_event(calendar) {
+                Ok(()) => imported += 1,
+                Err(e) => log::warn!("Skipping event: {}", e),
+            }
         }
 
-        let event_uid = uuid::Uuid::new_v4();
-        let target_path =
-            self.path
-                .join(&format!("{}.{}", event_uid.as_hyphenated(), ICAL_FILE_EXT));
-        let source_path =
-            std::env::temp_dir().join(&format!("{}.{}", event_uid.as_hyphenated(), ICAL_FILE_EXT));
-
-        self.current_modifications
-            .insert(CalendarModification::Create(target_path.clone()));
-
-        let mut file = fs::File::create(&source_path)?;
+        Ok(imported)
+    }
 
-        let mut event = Event::new(&source_path, occurrence)?;
+    pub(super) fn import_event(&mut self, ical: IcalCalendar) -> Result<()> {
+        let uid = ical.events[0]
+            .properties
+            .iter()
+            .find(|p| p.name == "UID")
+            .and_then(|p| p.value.clone())
+            .ok_or_else(|| Error::new(ErrorKind::EventMissingKey, "No UID found"))?;
 
-        if let Some(title) = new_event.title {
-            event.set_title(title.as_ref());
+        if uid.is_empty() || uid.contains(std::path::is_separator) {
+            return Err(Error::new(
+                ErrorKind::EventParse,
+                &format!("UID '{}' cannot be used as file name", uid),
+            ));
         }
 
-        if let Some(description) = new_event.description {
-            event.set_summary(description.as_ref());
+        if self.inner.contains(&uid) {
+            return Err(Error::new(
+                ErrorKind::CalendarParse,
+                &format!("Duplicate event uid '{}'", uid),
+            ));
         }
 
+        let path = self.path.join(&format!("{}.{}", uid, ICAL_FILE_EXT));
+        let event = Event::from_ical(&path, ical)?;
+
+        self.write_event(event)
+    }
+
+    /// Write the event into the calendar directory and add it to the calendar. An event with
+    /// the same uid is replaced.
+    pub(super) fn write_event(&mut self, event: Event) -> Result<()> {
+        let file_name = event.path().file_name().unwrap().to_owned();
+        let target_path = self.path.join(&file_name);
+        let source_path = std::env::temp_dir().join(&file_name);
+
+        let modification = if target_path.exists() {
+            CalendarModification::Modify(target_path.clone())
+        } else {
+            CalendarModification::Create(target_path.clone())
+        };
+        self.current_modifications.insert(modification.clone());
+
+        let mut file = fs::File::create(&source_path)?;
+
         // TODO: serde
         let s = to_string(&event.as_ical())?;
         log::info!("{}", s);
@@ -133,6 +154,7 @@ impl MutCalendarlike for Calendar {
         fs::copy(&source_path, &target_path)?;
         fs::remove_file(source_path)?;
 
+        self.inner.remove_via_uid(event.uid());
         self.inner
             .insert(event.move_to_dir(&target_path.parent().unwrap()))
             .map_err(|e| {
@@ -142,15 +164,60 @@ impl MutCalendarlike for Calendar {
                 )
             })?;
 
-        self.current_modifications
-            .remove(&CalendarModification::Create(target_path.clone()));
+        self.current_modifications.remove(&modification);
 
         Ok(())
     }
+}
+
+impl MutCalendarlike for Calendar {
+    fn add_event(&mut self, new_event: NewEvent<Tz>) -> Result<()> {
+        let mut occurrence = OccurrenceRule::Onetime(new_event.span());
+
+        if let Some(rrule) = new_event.rrule {
+            occurrence = occurrence.with_recurring(
+                rrule.build(
+                    new_event
+                        .begin
+                        .with_timezone(&rrule::Tz::Tz(new_event.begin.timezone())),
+                )?,
+            );
+        }
+
+        let event_uid = uuid::Uuid::new_v4();
+        let source_path =
+            std::env::temp_dir().join(&format!("{}.{}", event_uid.as_hyphenated(), ICAL_FILE_EXT));
+
+        // The event has to be backed by a file from the start
+        fs::File::create(&source_path)?;
+
+        let mut event = Event::new(&source_path, occurrence)?;
+
+        if let Some(title) = new_event.title {
+            event.set_title(title.as_ref());
+        }
+
+        if let Some(description) = new_event.description {
+            event.set_description(description.as_ref());
+        }
+
+        if let Some(location) = new_event.location {
+            event.set_location(location.as_ref());
+        }
+
+        for alarm in new_event.alarms.iter() {
+            event.add_alarm(alarm);
+        }
+
+        self.write_event(event)
+    }
     fn process_external_modifications(&mut self) {
         fn remove_for_path(calendar: &mut CalendarCore<Event>, path: &Path) {
             let Some(uid) = uid_from_path(path) else {
-                log::warn!("Unable to obtain uid from file removal event path '{}'", path.to_string_lossy());
+                log::warn!(
+                    "Unable to obtain uid from file removal event path '{}'",
+                    path.to_string_lossy()
+                );
                 return;
             };
             if !calendar.remove_via_uid(&uid) {
@@ -190,7 +257,7 @@ impl MutCalendarlike for Calendar {
     }
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 enum CalendarModification {
     Create(PathBuf),
     Remove(PathBuf),
@@ -132,6 +132,7 @@ impl IcalDuration {
         chrono::Duration::seconds(
             self.sign as i64
                 * ((self.weeks * 7 * 24 * 60 * 60)
+                    + (self.days * 24 * 60 * 60)
                     + (self.hours * 60 * 60)
                     + (self.minutes * 60)
                     + (self.seconds)),
@@ -143,14 +144,12 @@ impl FromStr for IcalDuration {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let (rest, sign) = Self::parse_sign(s)
-            .or_else(|err| {
-                return Err(Self::Err::new(
-                    ErrorKind::DurationParse,
-                    &format!("{}", err),
-                ));
-            })
-            .unwrap();
+        let (rest, sign) = Self::parse_sign(s).or_else(|err| {
+            return Err(Self::Err::new(
+                ErrorKind::DurationParse,
+                &format!("{}", err),
+            ));
+        })?;
 
         let (_, mut duration) = (all_consuming(preceded(
             char('P'),
@@ -161,8 +160,7 @@ impl FromStr for IcalDuration {
                 ErrorKind::DurationParse,
                 &format!("{}", err),
             ));
-        })
-        .unwrap();
+        })?;
 
         duration.sign = if let Some(sign) = sign {
             if sign == '-' {
@@ -181,15 +179,26 @@ impl FromStr for IcalDuration {
 impl Display for IcalDuration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut output = String::with_capacity(10);
-        output += if self.sign >= 0 { "+" } else { "-" };
+        // The sign is optional for positive durations, and not allowed by JSCalendar
+        if self.sign < 0 {
+            output += "-";
+        }
         output += "P";
         if self.weeks != 0 {
             output += &format!("{}W", self.weeks.abs());
         } else {
-            output += "T";
             if self.days != 0 {
                 output += &format!("{}D", self.days.abs())
             }
+            // Whole days have no time part, "P1DT" is invalid
+            let has_time = self.hours != 0 || self.minutes != 0 || self.seconds != 0;
+            if has_time || self.days == 0 {
+                output += "T";
+            }
+            if !has_time && self.days == 0 {
+                // A zero duration, e.g., an alarm at the start of an event
+                output += "0S";
+            }
             if self.hours != 0 {
                 output += &format!("{}H", self.hours.abs())
             }
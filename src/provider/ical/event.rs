@@ -1,4 +1,4 @@
-use chrono::{Datelike, Duration, Month, NaiveDate, Weekday};
+use chrono::{DateTime, Datelike, Duration, Month, NaiveDate, TimeZone, Utc, Weekday};
 use chrono_tz::{OffsetName, Tz};
 use num_traits::FromPrimitive;
 use rrule::RRule;
@@ -10,17 +10,21 @@ use tz;
 use tz::timezone::*;
 
 use ical::parser::ical::component::{
-    IcalCalendar, IcalEvent, IcalTimeZone, IcalTimeZoneTransition, Transition as IcalTransition,
+    IcalAlarm, IcalCalendar, IcalEvent, IcalTimeZone, IcalTimeZoneTransition,
+    Transition as IcalTransition,
 };
 use ical::parser::ical::IcalParser;
 use ical::parser::Component;
 use ical::property::Property;
 
 use super::datetime::*;
+use super::value::{param_value, split_text_list, unescape_text};
 use super::{PropertyList, ISO8601_2004_LOCAL_FORMAT};
 
 use crate::provider::{
-    days_of_month, Error, ErrorKind, Eventlike, OccurrenceRule, Result, TimeSpan,
+    days_of_month, Alarm, Attendee, CalAddress, CalendarUserType, Classification, Error, ErrorKind,
+    EventStatus, Eventlike, OccurrenceRule, ParticipationStatus, Result, Role, TimeSpan,
+    Transparency,
 };
 
 #[derive(Clone)]
@@ -29,6 +33,28 @@ pub struct Event {
     occurrence: OccurrenceRule<Tz>,
     ical: IcalCalendar,
     tz: Tz,
+    /// Instances of the recurring event modified by a RECURRENCE-ID, which are stored in the
+    /// same calendar object.
+    overrides: Vec<Event>,
+}
+
+/// Primary language subtag of the user's locale, e.g., `de` for `de_DE.UTF-8`.
+fn preferred_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .and_then(|locale| {
+            locale
+                .split(|c| c == '_' || c == '.' || c == '-')
+                .next()
+                .map(str::to_owned)
+        })
+        .filter(|language| language != "C" && language != "POSIX")
+}
+
+fn find_property<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a Property> {
+    event.properties.iter().find(|p| p.name == name)
 }
 
 pub fn uid_from_path(path: &Path) -> Option<String> {
@@ -48,18 +74,7 @@ impl Event {
         })?;
 
         let mut ical_calendar = IcalCalendar::new();
-        ical_calendar.properties = vec![
-            Property {
-                name: "PRODID".to_owned(),
-                params: None,
-                value: Some(super::JACKAL_PRODID.to_owned()),
-            },
-            Property {
-                name: "VERSION".to_owned(),
-                params: None,
-                value: Some(super::JACKAL_CALENDAR_VERSION.to_owned()),
-            },
-        ];
+        ical_calendar.properties = super::calendar_properties();
 
         if let Tz::UTC = occurrence.timezone() {
             ()
@@ -219,7 +234,7 @@ impl Event {
                     .properties
                     .append(&mut IcalTimeSpan(ts.clone()).into());
             }
-            OccurrenceRule::Recurring(ts, rrule) => {
+            OccurrenceRule::Recurring(ts, rrule, _) => {
                 ical_event
                     .properties
                     .append(&mut IcalTimeSpan(ts.clone()).into());
@@ -244,6 +259,7 @@ impl Event {
             occurrence,
             ical: ical_calendar,
             tz,
+            overrides: Vec::new(),
         })
     }
 
@@ -276,7 +292,7 @@ impl Event {
 
         let mut reader = IcalParser::new(buf);
 
-        let ical: IcalCalendar = match reader.next() {
+        let mut ical: IcalCalendar = match reader.next() {
             Some(cal) => match cal {
                 Ok(c) => c,
                 Err(e) => {
@@ -298,21 +314,49 @@ impl Event {
             }
         };
 
+        super::value::decode_calendar(&mut ical);
+
         Self::from_ical(path, ical)
     }
 
-    pub fn from_ical(path: &Path, ical: IcalCalendar) -> Result<Self> {
-        if ical.events.len() > 1 {
+    pub fn from_ical(path: &Path, mut ical: IcalCalendar) -> Result<Self> {
+        if ical.events.is_empty() {
+            return Err(Error::from(ErrorKind::CalendarParse)
+                .with_msg(&format!("Calendar '{}' has no event entry", path.display())));
+        }
+
+        // All instances of a recurring event share one calendar object (RFC 5545, section
+        // 3.8.4.4). The master entry, i.e., the one without RECURRENCE-ID, comes first.
+        if let Some(master) = ical
+            .events
+            .iter()
+            .position(|event| find_property(event, "RECURRENCE-ID").is_none())
+        {
+            let master = ical.events.remove(master);
+            ical.events.insert(0, master);
+        }
+
+        let uid = find_property(&ical.events[0], "UID").and_then(|p| p.value.as_deref());
+        if ical.events.iter().skip(1).any(|event| {
+            find_property(event, "RECURRENCE-ID").is_none()
+                || find_property(event, "UID").and_then(|p| p.value.as_deref()) != uid
+        }) {
             return Err(Error::from(ErrorKind::CalendarParse).with_msg(&format!(
                 "Calendar '{}' has more than one event entry",
                 path.display()
             )));
         }
 
-        if ical.events.is_empty() {
-            return Err(Error::from(ErrorKind::CalendarParse)
-                .with_msg(&format!("Calendar '{}' has no event entry", path.display())));
-        }
+        let overrides = ical.events[1..]
+            .iter()
+            .map(|event| {
+                let mut single = IcalCalendar::new();
+                single.properties = ical.properties.clone();
+                single.timezones = ical.timezones.clone();
+                single.events.push(event.clone());
+                Self::from_ical(path, single)
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         let event = ical.events.first().unwrap();
 
@@ -329,7 +373,8 @@ impl Event {
         // Required (if METHOD not set)
         let dtstart_spec = IcalDateTime::try_from(dtstart)?;
 
-        // Set TZ id based on start spec
+        // Set TZ id based on start spec. Dates are floating and begin at local midnight
+        // regardless of it.
         let tz = if let IcalDateTime::Local(dt) = dtstart_spec {
             dt.timezone()
         } else {
@@ -382,21 +427,66 @@ impl Event {
                 .unwrap()
                 .parse::<RRule<rrule::Unvalidated>>()
             {
-                let start = occurrence.first().begin();
                 let tz = occurrence.timezone();
-                occurrence = occurrence
-                    .with_recurring(ruleset.build(start.with_timezone(&rrule::Tz::Tz(tz)))?);
+                // Dates recur as midnights in the time zone of the event, see `TimeSpan::begin`
+                // for when they begin locally
+                let start = match occurrence.first() {
+                    TimeSpan::Allday(date, _, _) => tz
+                        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                        .earliest()
+                        .ok_or_else(|| Error::new(ErrorKind::DateParse, "Invalid local time"))?,
+                    span => span.begin(),
+                };
+                let mut ruleset = ruleset.build(start.with_timezone(&rrule::Tz::Tz(tz)))?;
+
+                // Dates refer to the instance beginning on that day
+                let instance = |property: &Property| -> Result<DateTime<Tz>> {
+                    match IcalDateTime::try_from(property)? {
+                        IcalDateTime::Date(date) => tz
+                            .from_local_datetime(&date.and_time(start.time()))
+                            .earliest()
+                            .ok_or_else(|| Error::new(ErrorKind::DateParse, "Invalid local time")),
+                        dt => Ok(dt.as_datetime(&tz)),
+                    }
+                };
+
+                // Excluded instances and modified ones, which replace those of the rule
+                let excluded = event
+                    .properties
+                    .iter()
+                    .filter(|p| p.name == "EXDATE")
+                    .flat_map(|exdate| {
+                        let value = exdate.value.as_deref().unwrap_or_default();
+                        value.split(',').map(move |single| Property {
+                            name: exdate.name.clone(),
+                            params: exdate.params.clone(),
+                            value: Some(single.to_owned()),
+                        })
+                    })
+                    .chain(
+                        ical.events
+                            .iter()
+                            .skip(1)
+                            .filter_map(|event| find_property(event, "RECURRENCE-ID"))
+                            .cloned(),
+                    );
+                for property in excluded {
+                    ruleset =
+                        ruleset.exdate(instance(&property)?.with_timezone(&rrule::Tz::Tz(tz)));
+                }
+
+                occurrence = occurrence.with_recurring(ruleset);
             }
         }
 
         // TODO: VTIMEZONE
-        // TODO: Check for exdate
 
         Ok(Event {
             path: path.into(),
             occurrence,
             ical,
             tz,
+            overrides,
         })
     }
 
@@ -412,11 +502,67 @@ impl Event {
         }
     }
 
-    fn get_property_mut(&mut self, name: &str) -> Option<&mut Property> {
-        self.ical.events[0]
+    /// Index of the property `name` in the user's preferred language, or of the first one if
+    /// there is none in that language.
+    fn text_property_index(&self, name: &str) -> Option<usize> {
+        let mut candidates = self.ical.events[0]
             .properties
-            .iter_mut()
-            .find(|prop| prop.name == name)
+            .iter()
+            .enumerate()
+            .filter(|(_, prop)| prop.name == name)
+            .peekable();
+        let (first, _) = candidates.next()?;
+
+        if candidates.peek().is_none() {
+            return Some(first);
+        }
+
+        let language = match preferred_language() {
+            Some(language) => language,
+            None => return Some(first),
+        };
+
+        candidates
+            .find(|(_, prop)| {
+                param_value(prop, "LANGUAGE").map_or(false, |tag| {
+                    tag.split('-')
+                        .next()
+                        .map_or(false, |primary| primary.eq_ignore_ascii_case(&language))
+                })
+            })
+            .map_or(Some(first), |(idx, _)| Some(idx))
+    }
+
+    fn get_text_property(&self, name: &str) -> Option<&Property> {
+        let idx = self.text_property_index(name)?;
+        Some(&self.ical.events[0].properties[idx])
+    }
+
+    fn get_datetime_property(&self, name: &str) -> Option<DateTime<Utc>> {
+        let prop = self.ical.events[0]
+            .properties
+            .iter()
+            .find(|prop| prop.name == name)?;
+
+        match IcalDateTime::try_from(prop) {
+            Ok(dt) => Some(dt.as_datetime(&Utc)),
+            Err(e) => {
+                log::warn!("Invalid {} in event {}: {}", name, self.uid(), e);
+                None
+            }
+        }
+    }
+
+    /// The language tag of the event's title, if given.
+    pub fn language(&self) -> Option<&str> {
+        self.get_text_property("SUMMARY")
+            .and_then(|prop| param_value(prop, "LANGUAGE"))
+    }
+
+    /// The property `name` the getters read, so that setters change what is shown.
+    fn get_text_property_mut(&mut self, name: &str) -> Option<&mut Property> {
+        let idx = self.text_property_index(name)?;
+        Some(&mut self.ical.events[0].properties[idx])
     }
 
     pub fn set_summary(&mut self, summary: &str) {
@@ -424,7 +570,7 @@ impl Event {
     }
 
     pub fn set_title(&mut self, title: &str) {
-        if let Some(property) = self.get_property_mut("SUMMARY") {
+        if let Some(property) = self.get_text_property_mut("SUMMARY") {
             property.value = Some(title.to_owned());
         } else {
             self.ical.events[0].add_property(Property {
@@ -436,7 +582,7 @@ impl Event {
     }
 
     pub fn set_description(&mut self, desc: &str) {
-        if let Some(property) = self.get_property_mut("DESCRIPTION") {
+        if let Some(property) = self.get_text_property_mut("DESCRIPTION") {
             property.value = Some(desc.to_owned());
         } else {
             self.ical.events[0].add_property(Property {
@@ -447,6 +593,22 @@ impl Event {
         }
     }
 
+    pub fn add_alarm(&mut self, alarm: &Alarm) {
+        self.ical.events[0].alarms.push(IcalAlarm::from(alarm));
+    }
+
+    pub fn set_location(&mut self, location: &str) {
+        if let Some(property) = self.get_text_property_mut("LOCATION") {
+            property.value = Some(location.to_owned());
+        } else {
+            self.ical.events[0].add_property(Property {
+                name: "LOCATION".to_owned(),
+                params: None,
+                value: Some(location.to_owned()),
+            });
+        }
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
@@ -462,6 +624,11 @@ impl Event {
     pub(super) fn move_to_dir(mut self, dir: &Path) -> Self {
         assert!(dir.is_dir(), "Provided path must point to a directory");
         self.path = dir.join(self.path.file_name().unwrap());
+        self.overrides = self
+            .overrides
+            .into_iter()
+            .map(|event| event.move_to_dir(dir))
+            .collect();
         self
     }
 
@@ -475,7 +642,9 @@ impl Event {
 
 impl Eventlike for Event {
     fn title(&self) -> &str {
-        self.get_property_value("SUMMARY").unwrap()
+        self.get_text_property("SUMMARY")
+            .and_then(|prop| prop.value.as_deref())
+            .unwrap_or_default()
     }
 
     fn uid(&self) -> &str {
@@ -487,7 +656,118 @@ impl Eventlike for Event {
     }
 
     fn description(&self) -> Option<&str> {
-        self.get_property_value("DESCRIPTION")
+        self.get_text_property("DESCRIPTION")
+            .and_then(|prop| prop.value.as_deref())
+    }
+
+    fn description_uri(&self) -> Option<&str> {
+        self.get_text_property("DESCRIPTION")
+            .and_then(|prop| param_value(prop, "ALTREP"))
+            .map(|uri| uri.trim_start_matches('<').trim_end_matches('>'))
+    }
+
+    fn location(&self) -> Option<&str> {
+        self.get_text_property("LOCATION")
+            .and_then(|prop| prop.value.as_deref())
+    }
+
+    fn url(&self) -> Option<&str> {
+        self.get_property_value("URL")
+    }
+
+    fn categories(&self) -> Vec<String> {
+        self.ical.events[0]
+            .properties
+            .iter()
+            .filter(|prop| prop.name == "CATEGORIES")
+            .filter_map(|prop| prop.value.as_deref())
+            .flat_map(split_text_list)
+            .map(unescape_text)
+            .filter(|category| !category.is_empty())
+            .collect()
+    }
+
+    fn status(&self) -> Option<EventStatus> {
+        self.get_property_value("STATUS")
+            .and_then(|status| status.parse().ok())
+    }
+
+    fn transparency(&self) -> Transparency {
+        self.get_property_value("TRANSP")
+            .and_then(|transp| transp.parse().ok())
+            .unwrap_or_default()
+    }
+
+    fn class(&self) -> Classification {
+        self.get_property_value("CLASS")
+            .map(Classification::from)
+            .unwrap_or_default()
+    }
+
+    fn priority(&self) -> Option<u8> {
+        self.get_property_value("PRIORITY")
+            .and_then(|priority| priority.trim().parse::<u8>().ok())
+            .filter(|priority| (1..=9).contains(priority))
+    }
+
+    fn organizer(&self) -> Option<CalAddress> {
+        let prop = self.ical.events[0]
+            .properties
+            .iter()
+            .find(|prop| prop.name == "ORGANIZER")?;
+
+        Some(CalAddress {
+            address: prop.value.clone()?,
+            name: param_value(prop, "CN").map(str::to_owned),
+        })
+    }
+
+    fn attendees(&self) -> Vec<Attendee> {
+        self.ical.events[0]
+            .properties
+            .iter()
+            .filter(|prop| prop.name == "ATTENDEE")
+            .filter_map(|prop| {
+                let mut attendee = Attendee::new(CalAddress {
+                    address: prop.value.clone()?,
+                    name: param_value(prop, "CN").map(str::to_owned),
+                });
+
+                if let Some(role) = param_value(prop, "ROLE") {
+                    attendee.role = Role::from(role);
+                }
+                if let Some(status) = param_value(prop, "PARTSTAT") {
+                    attendee.status = ParticipationStatus::from(status);
+                }
+                if let Some(cutype) = param_value(prop, "CUTYPE") {
+                    attendee.cutype = CalendarUserType::from(cutype);
+                }
+                attendee.rsvp = param_value(prop, "RSVP")
+                    .map_or(false, |rsvp| rsvp.eq_ignore_ascii_case("TRUE"));
+
+                Some(attendee)
+            })
+            .collect()
+    }
+
+    fn created(&self) -> Option<DateTime<Utc>> {
+        self.get_datetime_property("CREATED")
+    }
+
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.get_datetime_property("LAST-MODIFIED")
+    }
+
+    fn alarms(&self) -> Vec<Alarm> {
+        self.ical.events[0]
+            .alarms
+            .iter()
+            .filter_map(|alarm| {
+                Alarm::try_from(alarm)
+                    .map_err(|e| log::warn!("Ignoring alarm of '{}': {}", self.title(), e))
+                    .ok()
+            })
+            .collect()
     }
 
     fn occurrence_rule(&self) -> &OccurrenceRule<Tz> {
@@ -501,6 +781,10 @@ impl Eventlike for Event {
     fn duration(&self) -> Duration {
         self.occurrence.duration().into()
     }
+
+    fn overrides(&self) -> &[Self] {
+        &self.overrides
+    }
 }
 
 impl From<Event> for IcalEvent {
@@ -0,0 +1,435 @@
+//! Processing of scheduling messages as defined by iTIP (RFC 5546).
+
+use chrono::{DateTime, Utc};
+use ical::parser::ical::component::{IcalCalendar, IcalEvent};
+use ical::property::Property;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::datetime::{generate_timestamp, IcalDateTime};
+use super::value::{param_value, remove_param, set_param};
+use super::{calendar_properties, split_calendar, timezone_id, Calendar, Event};
+use crate::provider::{CalAddress, Error, ErrorKind, Eventlike, ParticipationStatus, Result};
+
+/// The METHOD of a scheduling message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    Publish,
+    Request,
+    Reply,
+    Cancel,
+    Other(String),
+}
+
+impl From<&str> for Method {
+    fn from(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "PUBLISH" => Method::Publish,
+            "REQUEST" => Method::Request,
+            "REPLY" => Method::Reply,
+            "CANCEL" => Method::Cancel,
+            other => Method::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Method::Publish => "PUBLISH",
+            Method::Request => "REQUEST",
+            Method::Reply => "REPLY",
+            Method::Cancel => "CANCEL",
+            Method::Other(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// The method of a calendar object, if it is a scheduling message.
+pub fn method(ical: &IcalCalendar) -> Option<Method> {
+    ical.properties
+        .iter()
+        .find(|p| p.name == "METHOD")
+        .and_then(|p| p.value.as_deref())
+        .map(Method::from)
+}
+
+/// Answer to an invitation.
+pub fn parse_response(s: &str) -> Result<ParticipationStatus> {
+    match s.to_ascii_lowercase().as_str() {
+        "accept" | "accepted" | "yes" => Ok(ParticipationStatus::Accepted),
+        "decline" | "declined" | "no" => Ok(ParticipationStatus::Declined),
+        "tentative" | "maybe" => Ok(ParticipationStatus::Tentative),
+        _ => Err(Error::new(
+            ErrorKind::ParseError,
+            &format!(
+                "Invalid response '{}', expected accept, decline or tentative",
+                s
+            ),
+        )),
+    }
+}
+
+/// What processing a scheduling message did to an event, identified by its uid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Added(String),
+    Updated(String),
+    Cancelled(String),
+    /// The message is older than the stored event
+    Outdated(String),
+    /// The message refers to an event that is not in the calendar
+    Unknown(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Added(uid) => write!(f, "added {}", uid),
+            Outcome::Updated(uid) => write!(f, "updated {}", uid),
+            Outcome::Cancelled(uid) => write!(f, "cancelled {}", uid),
+            Outcome::Outdated(uid) => write!(f, "ignored outdated message for {}", uid),
+            Outcome::Unknown(uid) => write!(f, "ignored message for unknown event {}", uid),
+        }
+    }
+}
+
+fn property<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a Property> {
+    event.properties.iter().find(|p| p.name == name)
+}
+
+fn property_mut<'a>(event: &'a mut IcalEvent, name: &str) -> Option<&'a mut Property> {
+    event.properties.iter_mut().find(|p| p.name == name)
+}
+
+fn set_property(event: &mut IcalEvent, name: &str, value: &str) {
+    match property_mut(event, name) {
+        Some(property) => property.value = Some(value.to_owned()),
+        None => event.properties.push(Property {
+            name: name.to_owned(),
+            params: None,
+            value: Some(value.to_owned()),
+        }),
+    }
+}
+
+fn uid(event: &IcalEvent) -> Result<String> {
+    property(event, "UID")
+        .and_then(|p| p.value.clone())
+        .ok_or_else(|| Error::new(ErrorKind::EventMissingKey, "No UID found"))
+}
+
+fn sequence(event: &IcalEvent) -> u32 {
+    property(event, "SEQUENCE")
+        .and_then(|p| p.value.as_deref())
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// The instance of a recurring event the entry refers to, `None` for the whole event.
+fn recurrence_id(event: &IcalEvent) -> Option<DateTime<Utc>> {
+    property(event, "RECURRENCE-ID")
+        .and_then(|p| IcalDateTime::try_from(p).ok())
+        .map(|dt| dt.as_datetime(&Utc))
+}
+
+fn dtstamp(value: &str) -> Option<DateTime<Utc>> {
+    value
+        .parse::<IcalDateTime>()
+        .ok()
+        .map(|dt| dt.as_datetime(&Utc))
+}
+
+/// Whether the entry of a message is older than the `stored` one: it has a lower SEQUENCE or,
+/// for the same SEQUENCE, an earlier DTSTAMP (RFC 5546, section 2.1.5).
+fn is_outdated(stored: &IcalEvent, message: &IcalEvent) -> bool {
+    let stamp = |event: &IcalEvent| {
+        property(event, "DTSTAMP")
+            .and_then(|p| p.value.as_deref())
+            .and_then(dtstamp)
+    };
+
+    match sequence(stored).cmp(&sequence(message)) {
+        Ordering::Greater => true,
+        Ordering::Equal => stamp(stored) > stamp(message),
+        Ordering::Less => false,
+    }
+}
+
+/// Parameter of an ATTENDEE keeping the DTSTAMP of the attendee's latest reply, as replies
+/// older than that are to be ignored (RFC 5546, section 2.1.5).
+const REPLY_DTSTAMP_PARAM: &str = "X-JACKAL-DTSTAMP";
+
+fn attendee_matches(property: &Property, address: &CalAddress) -> bool {
+    property.name == "ATTENDEE"
+        && property
+            .value
+            .as_deref()
+            .map_or(false, |value| address.matches(value))
+}
+
+impl Calendar {
+    /// Process a scheduling message: REQUESTs add or update events or single occurrences,
+    /// CANCELs cancel events or single occurrences and REPLYs update the participation status
+    /// of attendees. Messages older than the stored event, as determined by SEQUENCE and for
+    /// requests also by DTSTAMP, are ignored.
+    pub fn process_itip(&mut self, message: IcalCalendar) -> Result<Vec<Outcome>> {
+        let method = method(&message).unwrap_or(Method::Publish);
+
+        let mut outcomes = Vec::new();
+        for mut single in split_calendar(message) {
+            // Stored events are no scheduling messages
+            single.properties.retain(|p| p.name != "METHOD");
+
+            let outcome = match &method {
+                Method::Publish | Method::Request => self.apply_request(single)?,
+                Method::Cancel => self.apply_cancel(single)?,
+                Method::Reply => self.apply_reply(single)?,
+                Method::Other(other) => {
+                    return Err(Error::new(
+                        ErrorKind::CalendarParse,
+                        &format!("Unsupported scheduling method '{}'", other),
+                    ))
+                }
+            };
+            outcomes.push(outcome);
+        }
+
+        Ok(outcomes)
+    }
+
+    fn apply_request(&mut self, message: IcalCalendar) -> Result<Outcome> {
+        let uid = uid(&message.events[0])?;
+
+        let stored = match self.get(&uid) {
+            None => {
+                self.import_event(message)?;
+                return Ok(Outcome::Added(uid));
+            }
+            Some(stored) => stored,
+        };
+        let path = stored.path().to_owned();
+
+        // A request for the whole event replaces it, including its modified instances
+        if let Some(master) = message.events.iter().find(|e| recurrence_id(e).is_none()) {
+            if is_outdated(stored.ical_event(), master) {
+                return Ok(Outcome::Outdated(uid));
+            }
+
+            self.write_event(Event::from_ical(&path, message)?)?;
+            return Ok(Outcome::Updated(uid));
+        }
+
+        // Otherwise single instances are added or replaced (RFC 5546, section 3.2.2.5)
+        let mut ical = stored.as_ical().clone();
+        let mut updated = false;
+        for instance in message.events {
+            let id = recurrence_id(&instance);
+            match ical.events.iter().position(|e| recurrence_id(e) == id) {
+                Some(idx) if is_outdated(&ical.events[idx], &instance) => {}
+                Some(idx) => {
+                    ical.events[idx] = instance;
+                    updated = true;
+                }
+                None => {
+                    ical.events.push(instance);
+                    updated = true;
+                }
+            }
+        }
+
+        if !updated {
+            return Ok(Outcome::Outdated(uid));
+        }
+
+        for timezone in message.timezones {
+            if !ical
+                .timezones
+                .iter()
+                .any(|tz| timezone_id(tz) == timezone_id(&timezone))
+            {
+                ical.timezones.push(timezone);
+            }
+        }
+
+        self.write_event(Event::from_ical(&path, ical)?)?;
+        Ok(Outcome::Updated(uid))
+    }
+
+    fn apply_cancel(&mut self, message: IcalCalendar) -> Result<Outcome> {
+        let uid = uid(&message.events[0])?;
+
+        let stored = match self.get(&uid) {
+            None => return Ok(Outcome::Unknown(uid)),
+            Some(stored) => stored,
+        };
+
+        let path = stored.path().to_owned();
+        let mut ical = stored.as_ical().clone();
+
+        let mut cancelled = false;
+        for event in message.events.iter() {
+            let sequence = self::sequence(event);
+            if self::sequence(&ical.events[0]) > sequence {
+                continue;
+            }
+
+            if let Some(instance) = property(event, "RECURRENCE-ID") {
+                // Only a single occurrence is cancelled, which may have been modified before
+                let id = recurrence_id(event);
+                let instances = ical.events.split_off(1);
+                ical.events
+                    .extend(instances.into_iter().filter(|e| recurrence_id(e) != id));
+
+                let mut exdate = instance.clone();
+                exdate.name = "EXDATE".to_owned();
+                remove_param(&mut exdate, "RANGE");
+                ical.events[0].properties.push(exdate);
+            } else {
+                set_property(&mut ical.events[0], "STATUS", "CANCELLED");
+            }
+            set_property(&mut ical.events[0], "SEQUENCE", &sequence.to_string());
+            cancelled = true;
+        }
+
+        if !cancelled {
+            return Ok(Outcome::Outdated(uid));
+        }
+
+        self.write_event(Event::from_ical(&path, ical)?)?;
+        Ok(Outcome::Cancelled(uid))
+    }
+
+    fn apply_reply(&mut self, message: IcalCalendar) -> Result<Outcome> {
+        let event = &message.events[0];
+        let uid = uid(event)?;
+
+        let stored = match self.get(&uid) {
+            Some(stored) => stored,
+            None => return Ok(Outcome::Unknown(uid)),
+        };
+
+        let path = stored.path().to_owned();
+        let mut ical = stored.as_ical().clone();
+
+        // Replies for a single instance update it if it was modified, the event otherwise
+        let id = recurrence_id(event);
+        let idx = ical
+            .events
+            .iter()
+            .position(|e| id.is_some() && recurrence_id(e) == id)
+            .unwrap_or(0);
+        let stored_event = &mut ical.events[idx];
+
+        // Replies to an earlier revision of the event are outdated
+        if sequence(stored_event) > sequence(event) {
+            return Ok(Outcome::Outdated(uid));
+        }
+
+        let reply_dtstamp = property(event, "DTSTAMP").and_then(|p| p.value.clone());
+        let mut outdated = false;
+        for reply in event.properties.iter().filter(|p| p.name == "ATTENDEE") {
+            let address = CalAddress::new(reply.value.as_deref().unwrap_or_default());
+            let status = param_value(reply, "PARTSTAT").unwrap_or("NEEDS-ACTION");
+
+            let attendee = match stored_event
+                .properties
+                .iter_mut()
+                .find(|p| attendee_matches(p, &address))
+            {
+                Some(attendee) => attendee,
+                None => {
+                    log::warn!("Reply from {} who is not invited to {}", address, uid);
+                    continue;
+                }
+            };
+
+            // Only the latest reply of an attendee counts
+            let last = param_value(attendee, REPLY_DTSTAMP_PARAM).and_then(dtstamp);
+            if last.is_some() && last > reply_dtstamp.as_deref().and_then(dtstamp) {
+                log::info!("Ignoring outdated reply from {} to {}", address, uid);
+                outdated = true;
+                continue;
+            }
+
+            set_param(attendee, "PARTSTAT", status);
+            remove_param(attendee, "RSVP");
+            if let Some(reply_dtstamp) = reply_dtstamp.as_deref() {
+                set_param(attendee, REPLY_DTSTAMP_PARAM, reply_dtstamp);
+            }
+        }
+
+        if outdated {
+            return Ok(Outcome::Outdated(uid));
+        }
+
+        self.write_event(Event::from_ical(&path, ical)?)?;
+        Ok(Outcome::Updated(uid))
+    }
+
+    /// Respond to the invitation with the uid as the attendee owning one of `addresses`. The
+    /// participation status of the stored event is updated and a REPLY message for the
+    /// organizer is returned.
+    pub fn respond(
+        &mut self,
+        uid: &str,
+        addresses: &[String],
+        status: &ParticipationStatus,
+    ) -> Result<IcalCalendar> {
+        let stored = self
+            .get(uid)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::EventMissingKey,
+                    &format!("Event '{}' not found", uid),
+                )
+            })?
+            .clone();
+
+        let own = stored.attendee_for(addresses).ok_or_else(|| {
+            Error::new(
+                ErrorKind::EventMissingKey,
+                &format!("None of your addresses is invited to '{}'", stored.title()),
+            )
+        })?;
+
+        let mut ical = stored.as_ical().clone();
+        let stored_event = &mut ical.events[0];
+
+        let attendee = stored_event
+            .properties
+            .iter_mut()
+            .find(|p| attendee_matches(p, &own.address))
+            .unwrap();
+        set_param(attendee, "PARTSTAT", &status.to_string());
+        remove_param(attendee, "RSVP");
+        let attendee = attendee.clone();
+
+        // The reply only carries what the organizer needs to identify the event
+        let mut reply = IcalEvent::new();
+        for name in &["UID", "SEQUENCE", "ORGANIZER", "DTSTART", "SUMMARY"] {
+            if let Some(property) = property(stored_event, name) {
+                reply.properties.push(property.clone());
+            }
+        }
+        set_property(&mut reply, "DTSTAMP", &generate_timestamp());
+        reply.properties.push(attendee);
+
+        let mut message = IcalCalendar::new();
+        message.properties = calendar_properties();
+        message.properties.push(Property {
+            name: "METHOD".to_owned(),
+            params: None,
+            value: Some(Method::Reply.to_string()),
+        });
+        message.timezones = ical.timezones.clone();
+        message.events.push(reply);
+
+        self.write_event(Event::from_ical(stored.path(), ical)?)?;
+
+        Ok(message)
+    }
+}
@@ -1,16 +1,23 @@
+pub mod alarm;
 pub mod calendar;
 pub mod datetime;
 pub mod event;
+pub mod itip;
 pub mod ser;
+pub mod value;
+pub mod xcal;
 
 pub use calendar::Calendar;
 pub use event::Event;
 
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
 use super::{Error, ErrorKind, ProviderCalendar, Result};
 use crate::config::CalendarConfig;
 
+use ical::parser::ical::component::{IcalCalendar, IcalEvent, IcalTimeZone};
+use ical::parser::ical::IcalParser;
 use ical::property::Property;
 
 type PropertyList = Vec<Property>;
@@ -50,3 +57,125 @@ pub fn from_dir(
 
     Ok(calendars)
 }
+
+/// Parse all calendar objects contained in an iCalendar document.
+pub fn parse_calendars(input: &str) -> Result<Vec<IcalCalendar>> {
+    IcalParser::new(BufReader::new(input.as_bytes()))
+        .map(|calendar| {
+            let mut calendar = calendar.map_err(|e| {
+                Error::new(
+                    ErrorKind::CalendarParse,
+                    &format!("Invalid calendar: {}", e),
+                )
+            })?;
+            value::decode_calendar(&mut calendar);
+            Ok(calendar)
+        })
+        .collect()
+}
+
+/// The calendar level properties jackal writes into every calendar object it creates.
+pub fn calendar_properties() -> PropertyList {
+    vec![
+        Property {
+            name: "PRODID".to_owned(),
+            params: None,
+            value: Some(JACKAL_PRODID.to_owned()),
+        },
+        Property {
+            name: "VERSION".to_owned(),
+            params: None,
+            value: Some(JACKAL_CALENDAR_VERSION.to_owned()),
+        },
+    ]
+}
+
+fn timezone_id(timezone: &IcalTimeZone) -> Option<&str> {
+    timezone
+        .properties
+        .iter()
+        .find(|p| p.name == "TZID")
+        .and_then(|p| p.value.as_deref())
+}
+
+/// Merge the calendar objects of several events into a single calendar object, e.g., to export
+/// them into one file. Timezone definitions are deduplicated by their TZID.
+pub fn merge_calendars<'a>(calendars: impl IntoIterator<Item = &'a IcalCalendar>) -> IcalCalendar {
+    let mut merged = IcalCalendar::new();
+    merged.properties = calendar_properties();
+
+    for calendar in calendars {
+        for timezone in calendar.timezones.iter() {
+            if !merged
+                .timezones
+                .iter()
+                .any(|tz| timezone_id(tz) == timezone_id(timezone))
+            {
+                merged.timezones.push(timezone.clone());
+            }
+        }
+
+        merged.events.extend(calendar.events.iter().cloned());
+    }
+
+    merged
+}
+
+/// Split a calendar object into one calendar object per event as jackal stores them. Modified
+/// instances of a recurring event share its UID and stay in the same calendar object. Every
+/// calendar object keeps the timezone definitions its events refer to.
+pub fn split_calendar(calendar: IcalCalendar) -> Vec<IcalCalendar> {
+    let IcalCalendar {
+        properties,
+        events,
+        timezones,
+        ..
+    } = calendar;
+
+    let mut grouped: Vec<(Option<String>, Vec<IcalEvent>)> = Vec::new();
+    for event in events {
+        let uid = event
+            .properties
+            .iter()
+            .find(|p| p.name == "UID")
+            .and_then(|p| p.value.clone());
+
+        match grouped
+            .iter_mut()
+            .find(|(other, _)| uid.is_some() && *other == uid)
+        {
+            Some((_, group)) => group.push(event),
+            None => grouped.push((uid, vec![event])),
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(_, events)| {
+            let referenced_tzids = events
+                .iter()
+                .flat_map(|event| event.properties.iter())
+                .filter_map(|p| p.params.as_ref())
+                .flatten()
+                .filter(|(name, _)| name == "TZID")
+                .flat_map(|(_, values)| values.iter())
+                .collect::<Vec<_>>();
+
+            let mut single = IcalCalendar::new();
+            single.properties = properties.clone();
+            single.timezones = timezones
+                .iter()
+                .filter(|tz| {
+                    timezone_id(tz).map_or(false, |id| {
+                        referenced_tzids
+                            .iter()
+                            .any(|referenced| referenced.as_str() == id)
+                    })
+                })
+                .cloned()
+                .collect();
+            single.events = events;
+            single
+        })
+        .collect()
+}
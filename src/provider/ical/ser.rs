@@ -5,18 +5,78 @@ use serde::{ser, Serialize};
 use std::default::Default;
 use std::fmt::Display;
 
+use super::value::{encode_param_value, escape_text, is_single_text};
 use crate::provider::{Error, ErrorKind, Result};
 
+/// Line break of content lines as required by RFC 5545, section 3.1
+const CRLF: &'static str = "\r\n";
+
+/// Maximum length of a content line in octets, excluding the line break
+const MAX_LINE_OCTETS: usize = 75;
+
 pub fn to_string(value: &IcalCalendar) -> Result<String> {
     let mut serial = Serializer::default();
     serial.serialize_calendar(&value)?;
     serial.finish()
 }
 
+/// Encode a parameter value and quote it if it contains characters with a special meaning in
+/// content lines.
+fn quote_param_value(value: &str) -> String {
+    let value = encode_param_value(value);
+
+    if value.contains(&[':', ';', ','][..]) {
+        format!("\"{}\"", value)
+    } else {
+        value
+    }
+}
+
+/// Fold a content line so that no line exceeds 75 octets (RFC 5545, section 3.1). Lines are
+/// only split between characters and never directly after whitespace, which parsers may trim.
+fn fold_line(line: &str) -> String {
+    if line.len() <= MAX_LINE_OCTETS {
+        return line.to_owned();
+    }
+
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut rest = line;
+    // The leading space of continuation lines counts towards their length
+    let mut limit = MAX_LINE_OCTETS;
+
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+
+        let mut candidate = split;
+        while candidate > 0 && rest[..candidate].ends_with(char::is_whitespace) {
+            candidate -= 1;
+            while !rest.is_char_boundary(candidate) {
+                candidate -= 1;
+            }
+        }
+        if candidate > 0 {
+            split = candidate;
+        }
+
+        folded += &rest[..split];
+        folded += CRLF;
+        folded += " ";
+        rest = &rest[split..];
+        limit = MAX_LINE_OCTETS - 1;
+    }
+    folded += rest;
+
+    folded
+}
+
 #[derive(Debug, Default)]
 enum Position {
     Key,
     Parameters,
+    ParameterValue,
     Value,
     #[default]
     EOL,
@@ -29,6 +89,7 @@ enum Section {
     TimezoneTransition(String),
     Alarms,
     Events,
+    FreeBusy,
 }
 
 impl Display for Section {
@@ -37,6 +98,7 @@ impl Display for Section {
             Self::Calendar => "VCALENDAR",
             Self::Alarms => "VALARM",
             Self::Events => "VEVENT",
+            Self::FreeBusy => "VFREEBUSY",
             Self::Timezones => "VTIMEZONE",
             Self::TimezoneTransition(s) => s,
         };
@@ -56,7 +118,7 @@ impl Serializer {
         match self.position {
             Position::EOL => {
                 self.position = Position::Key;
-                self.output += &format!("BEGIN:{}\n", &sec);
+                self.output += &format!("BEGIN:{}{}", &sec, CRLF);
                 self.section.push(sec);
                 Ok(())
             }
@@ -71,7 +133,7 @@ impl Serializer {
         match self.position {
             Position::EOL => {
                 let sec = self.section.pop().unwrap();
-                self.output += &format!("END:{}\n", sec);
+                self.output += &format!("END:{}{}", sec, CRLF);
                 Ok(())
             }
             _ => {
@@ -86,12 +148,16 @@ impl Serializer {
     }
 
     fn serialize_properties(&mut self, value: &Vec<Property>) -> Result<()> {
-        for Property {
-            name,
-            params,
-            value,
-        } in value
-        {
+        for property in value {
+            let Property {
+                name,
+                params,
+                value,
+            } = property;
+
+            // Lines are assembled in place and folded once they are complete
+            let line_start = self.output.len();
+
             self.position = Position::Key;
             name.serialize(&mut *self)?;
 
@@ -105,15 +171,23 @@ impl Serializer {
                     self.position = Position::Parameters;
                     name.serialize(&mut *self)?;
                     self.output += "=";
-                    self.position = Position::Value;
+                    self.position = Position::ParameterValue;
                     values.serialize(&mut *self)?;
                 }
             }
 
             self.output += ":";
             self.position = Position::Value;
-            value.serialize(&mut *self)?;
-            self.output += "\n";
+            match value {
+                Some(value) if is_single_text(property) => {
+                    escape_text(value).serialize(&mut *self)?
+                }
+                value => value.serialize(&mut *self)?,
+            }
+
+            let line = self.output.split_off(line_start);
+            self.output += &fold_line(&line);
+            self.output += CRLF;
             self.position = Position::EOL;
         }
         Ok(())
@@ -139,6 +213,13 @@ impl Serializer {
         Ok(())
     }
 
+    fn serialize_free_busy(&mut self, value: &IcalFreeBusy) -> Result<()> {
+        self.begin_section(Section::FreeBusy)?;
+        self.serialize_properties(&value.properties)?;
+        self.end_section()?;
+        Ok(())
+    }
+
     fn serialize_timezones(&mut self, value: &IcalTimeZone) -> Result<()> {
         self.begin_section(Section::Timezones)?;
         self.serialize_properties(&value.properties)?;
@@ -173,6 +254,10 @@ impl Serializer {
             self.serialize_events(&event)?;
         }
 
+        for free_busy in calendar.free_busys.iter() {
+            self.serialize_free_busy(free_busy)?;
+        }
+
         Ok(())
     }
 
@@ -258,7 +343,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.output += v;
+        match self.position {
+            Position::ParameterValue => self.output += &quote_param_value(v),
+            _ => self.output += v,
+        }
         Ok(())
     }
 
@@ -375,7 +463,7 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
         T: ?Sized + Serialize,
     {
         match &self.position {
-            &Position::Value => {
+            &Position::Value | &Position::ParameterValue => {
                 if !(self.output.ends_with(":") || self.output.ends_with("=")) {
                     self.output += ",";
                 }
@@ -0,0 +1,318 @@
+use ical::parser::ical::component::IcalCalendar;
+use ical::property::Property;
+
+/// Value data types of iCalendar properties as defined in RFC 5545, section 3.3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Binary,
+    Boolean,
+    CalAddress,
+    Date,
+    DateTime,
+    Duration,
+    Float,
+    Integer,
+    Period,
+    Recur,
+    Text,
+    Time,
+    Uri,
+    UtcOffset,
+    Unknown,
+}
+
+impl ValueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::Binary => "BINARY",
+            ValueType::Boolean => "BOOLEAN",
+            ValueType::CalAddress => "CAL-ADDRESS",
+            ValueType::Date => "DATE",
+            ValueType::DateTime => "DATE-TIME",
+            ValueType::Duration => "DURATION",
+            ValueType::Float => "FLOAT",
+            ValueType::Integer => "INTEGER",
+            ValueType::Period => "PERIOD",
+            ValueType::Recur => "RECUR",
+            ValueType::Text => "TEXT",
+            ValueType::Time => "TIME",
+            ValueType::Uri => "URI",
+            ValueType::UtcOffset => "UTC-OFFSET",
+            ValueType::Unknown => "UNKNOWN",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let value_type = match name.to_ascii_uppercase().as_str() {
+            "BINARY" => ValueType::Binary,
+            "BOOLEAN" => ValueType::Boolean,
+            "CAL-ADDRESS" => ValueType::CalAddress,
+            "DATE" => ValueType::Date,
+            "DATE-TIME" => ValueType::DateTime,
+            "DURATION" => ValueType::Duration,
+            "FLOAT" => ValueType::Float,
+            "INTEGER" => ValueType::Integer,
+            "PERIOD" => ValueType::Period,
+            "RECUR" => ValueType::Recur,
+            "TEXT" => ValueType::Text,
+            "TIME" => ValueType::Time,
+            "URI" => ValueType::Uri,
+            "UTC-OFFSET" => ValueType::UtcOffset,
+            "UNKNOWN" => ValueType::Unknown,
+            _ => return None,
+        };
+
+        Some(value_type)
+    }
+
+    /// The type a property has if no VALUE parameter is given.
+    pub fn default_for(property: &str) -> Self {
+        match property {
+            "CALSCALE" | "METHOD" | "PRODID" | "VERSION" | "CATEGORIES" | "CLASS" | "COMMENT"
+            | "DESCRIPTION" | "LOCATION" | "RESOURCES" | "STATUS" | "SUMMARY" | "TRANSP"
+            | "TZID" | "TZNAME" | "CONTACT" | "RELATED-TO" | "UID" | "ACTION" | "NAME"
+            | "COLOR" => ValueType::Text,
+            "PERCENT-COMPLETE" | "PRIORITY" | "REPEAT" | "SEQUENCE" => ValueType::Integer,
+            "COMPLETED" | "DTEND" | "DUE" | "DTSTART" | "RECURRENCE-ID" | "EXDATE" | "RDATE"
+            | "CREATED" | "DTSTAMP" | "LAST-MODIFIED" => ValueType::DateTime,
+            "DURATION" | "TRIGGER" | "REFRESH-INTERVAL" => ValueType::Duration,
+            "FREEBUSY" => ValueType::Period,
+            "TZOFFSETFROM" | "TZOFFSETTO" => ValueType::UtcOffset,
+            "TZURL" | "URL" | "ATTACH" | "IMAGE" | "CONFERENCE" | "SOURCE" => ValueType::Uri,
+            "ATTENDEE" | "ORGANIZER" => ValueType::CalAddress,
+            "RRULE" | "EXRULE" => ValueType::Recur,
+            // GEO and REQUEST-STATUS are structured and handled separately by their users
+            "GEO" => ValueType::Float,
+            _ => ValueType::Unknown,
+        }
+    }
+
+    /// The type of the value of `property`, honoring an explicit VALUE parameter.
+    pub fn of(property: &Property) -> Self {
+        param_value(property, "VALUE")
+            .and_then(ValueType::from_name)
+            .unwrap_or_else(|| ValueType::default_for(&property.name))
+    }
+}
+
+/// Whether the value of the property is a comma separated list of values.
+pub fn is_multi_valued(property: &str) -> bool {
+    matches!(
+        property,
+        "CATEGORIES" | "RESOURCES" | "EXDATE" | "RDATE" | "FREEBUSY"
+    )
+}
+
+/// Whether the property holds a single TEXT value. These values are kept unescaped in memory
+/// and only escaped when serialized, whereas lists of TEXT values keep their escaped form.
+pub fn is_single_text(property: &Property) -> bool {
+    ValueType::of(property) == ValueType::Text && !is_multi_valued(&property.name)
+}
+
+fn decode_properties(properties: &mut [Property]) {
+    for property in properties.iter_mut() {
+        if let Some(params) = property.params.as_mut() {
+            for (_, values) in params.iter_mut() {
+                for value in values.iter_mut() {
+                    *value = decode_param_value(value);
+                }
+            }
+        }
+
+        // Encoded values are no content lines and hence not escaped
+        if !decode_base64(property) && is_single_text(property) {
+            if let Some(value) = property.value.as_mut() {
+                *value = unescape_text(value);
+            }
+        }
+    }
+}
+
+/// Decode BASE64 encoded values of properties that are not BINARY. Binary values, e.g., of
+/// attachments, remain encoded. Returns whether the value was decoded.
+fn decode_base64(property: &mut Property) -> bool {
+    let is_base64 = param_value(property, "ENCODING")
+        .map_or(false, |encoding| encoding.eq_ignore_ascii_case("BASE64"));
+
+    if !is_base64 || ValueType::of(property) == ValueType::Binary {
+        return false;
+    }
+
+    let decoded = property
+        .value
+        .as_deref()
+        .and_then(|value| base64::decode(value.trim()).ok())
+        .and_then(|bytes| String::from_utf8(bytes).ok());
+
+    match decoded {
+        Some(decoded) => {
+            property.value = Some(decoded);
+            if let Some(params) = property.params.as_mut() {
+                params.retain(|(name, _)| !name.eq_ignore_ascii_case("ENCODING"));
+            }
+            true
+        }
+        None => {
+            log::warn!("Could not decode BASE64 value of {}", property.name);
+            false
+        }
+    }
+}
+
+/// Bring a freshly parsed calendar into the in-memory representation, i.e., decode parameter
+/// values and BASE64 encoded values and unescape all single TEXT values.
+pub fn decode_calendar(calendar: &mut IcalCalendar) {
+    decode_properties(&mut calendar.properties);
+
+    for timezone in calendar.timezones.iter_mut() {
+        decode_properties(&mut timezone.properties);
+        for transition in timezone.transitions.iter_mut() {
+            decode_properties(&mut transition.properties);
+        }
+    }
+
+    for event in calendar.events.iter_mut() {
+        decode_properties(&mut event.properties);
+        for alarm in event.alarms.iter_mut() {
+            decode_properties(&mut alarm.properties);
+        }
+    }
+}
+
+/// Get the first value of a parameter of the property.
+pub fn param_value<'a>(property: &'a Property, name: &str) -> Option<&'a str> {
+    property
+        .params
+        .as_ref()?
+        .iter()
+        .find(|(param, _)| param.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.first())
+        .map(String::as_str)
+}
+
+/// Set the parameter of the property to a single value, replacing previous values.
+pub fn set_param(property: &mut Property, name: &str, value: &str) {
+    let params = property.params.get_or_insert_with(Vec::new);
+    match params
+        .iter_mut()
+        .find(|(param, _)| param.eq_ignore_ascii_case(name))
+    {
+        Some((_, values)) => *values = vec![value.to_owned()],
+        None => params.push((name.to_owned(), vec![value.to_owned()])),
+    }
+}
+
+/// Remove the parameter from the property.
+pub fn remove_param(property: &mut Property, name: &str) {
+    if let Some(params) = property.params.as_mut() {
+        params.retain(|(param, _)| !param.eq_ignore_ascii_case(name));
+        if params.is_empty() {
+            property.params = None;
+        }
+    }
+}
+
+/// Decode the circumflex escapes of a parameter value (RFC 6868).
+pub fn decode_param_value(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('^', Some('n')) => decoded.push('\n'),
+            ('^', Some('^')) => decoded.push('^'),
+            ('^', Some('\'')) => decoded.push('"'),
+            _ => {
+                decoded.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+
+    decoded
+}
+
+/// Reverse of [`decode_param_value`].
+pub fn encode_param_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => encoded += "^^",
+            '"' => encoded += "^'",
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => encoded += "^n",
+            c => encoded.push(c),
+        }
+    }
+
+    encoded
+}
+
+/// Escape a TEXT value for its use in a content line (RFC 5545, section 3.3.11).
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped += "\\\\",
+            ';' => escaped += "\\;",
+            ',' => escaped += "\\,",
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => escaped += "\\n",
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Reverse of [`escape_text`]. Unknown escape sequences are kept as they are.
+pub fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(c @ ('\\' | ';' | ',' | ':' | '"')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Split a (still escaped) list of TEXT values on all unescaped commas.
+pub fn split_text_list(text: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (idx, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                values.push(&text[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(&text[start..]);
+
+    values
+}
@@ -0,0 +1,643 @@
+use ical::parser::ical::component::*;
+use ical::property::Property;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event as XmlEvent};
+use quick_xml::{Reader, Writer};
+
+use super::value::{escape_text, is_multi_valued, split_text_list, unescape_text, ValueType};
+use crate::provider::{Error, ErrorKind, Result};
+
+const XCAL_NAMESPACE: &'static str = "urn:ietf:params:xml:ns:icalendar-2.0";
+
+/// Serialize the calendar into its xCal (RFC 6321) representation.
+pub fn to_string(value: &IcalCalendar) -> Result<String> {
+    let mut writer = XCalWriter {
+        writer: Writer::new_with_indent(Vec::new(), b' ', 2),
+    };
+
+    writer.write_document(value)?;
+
+    String::from_utf8(writer.writer.into_inner())
+        .map_err(|e| Error::new(ErrorKind::SerializeError, &e.to_string()))
+}
+
+/// Parse all calendars of an xCal document into the same component tree the iCalendar parser
+/// produces.
+pub fn from_str(input: &str) -> Result<Vec<IcalCalendar>> {
+    let root = Node::parse(input)?;
+
+    if root.name != "icalendar" {
+        return Err(Error::new(
+            ErrorKind::CalendarParse,
+            &format!(
+                "Expected xCal root element 'icalendar', got '{}'",
+                root.name
+            ),
+        ));
+    }
+
+    root.children
+        .iter()
+        .filter(|node| node.name == "vcalendar")
+        .map(read_calendar)
+        .collect()
+}
+
+struct XCalWriter {
+    writer: Writer<Vec<u8>>,
+}
+
+impl XCalWriter {
+    fn start(&mut self, name: &str) -> Result<()> {
+        self.writer
+            .write_event(XmlEvent::Start(BytesStart::new(name)))?;
+        Ok(())
+    }
+
+    fn end(&mut self, name: &str) -> Result<()> {
+        self.writer
+            .write_event(XmlEvent::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+
+    fn text_element(&mut self, name: &str, text: &str) -> Result<()> {
+        self.start(name)?;
+        self.writer
+            .write_event(XmlEvent::Text(BytesText::new(text)))?;
+        self.end(name)
+    }
+
+    fn write_document(&mut self, calendar: &IcalCalendar) -> Result<()> {
+        self.writer
+            .write_event(XmlEvent::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+        let mut root = BytesStart::new("icalendar");
+        root.push_attribute(("xmlns", XCAL_NAMESPACE));
+        self.writer.write_event(XmlEvent::Start(root))?;
+
+        self.start("vcalendar")?;
+        self.write_properties(&calendar.properties)?;
+
+        self.start("components")?;
+        for timezone in calendar.timezones.iter() {
+            self.write_timezone(timezone)?;
+        }
+
+        for event in calendar.events.iter() {
+            self.write_event(event)?;
+        }
+        self.end("components")?;
+
+        self.end("vcalendar")?;
+        self.end("icalendar")
+    }
+
+    fn write_timezone(&mut self, timezone: &IcalTimeZone) -> Result<()> {
+        self.start("vtimezone")?;
+        self.write_properties(&timezone.properties)?;
+
+        self.start("components")?;
+        for transition in timezone.transitions.iter() {
+            let name = match transition.transition {
+                Transition::Standard => "standard",
+                Transition::Daylight => "daylight",
+            };
+
+            self.start(name)?;
+            self.write_properties(&transition.properties)?;
+            self.end(name)?;
+        }
+        self.end("components")?;
+
+        self.end("vtimezone")
+    }
+
+    fn write_event(&mut self, event: &IcalEvent) -> Result<()> {
+        self.start("vevent")?;
+        self.write_properties(&event.properties)?;
+
+        if !event.alarms.is_empty() {
+            self.start("components")?;
+            for alarm in event.alarms.iter() {
+                self.start("valarm")?;
+                self.write_properties(&alarm.properties)?;
+                self.end("valarm")?;
+            }
+            self.end("components")?;
+        }
+
+        self.end("vevent")
+    }
+
+    fn write_properties(&mut self, properties: &[Property]) -> Result<()> {
+        self.start("properties")?;
+        for property in properties {
+            self.write_property(property)?;
+        }
+        self.end("properties")
+    }
+
+    fn write_property(&mut self, property: &Property) -> Result<()> {
+        let name = property.name.to_ascii_lowercase();
+        self.start(&name)?;
+
+        // The value type is given by the element name in xCal and hence the VALUE parameter
+        // must not be present.
+        let params = property
+            .params
+            .iter()
+            .flatten()
+            .filter(|(param, _)| !param.eq_ignore_ascii_case("VALUE"))
+            .collect::<Vec<_>>();
+
+        if !params.is_empty() {
+            self.start("parameters")?;
+            for (param, values) in params {
+                let param = param.to_ascii_lowercase();
+                let value_element = param_value_element(&param);
+
+                self.start(&param)?;
+                for value in values {
+                    if value_element == "boolean" {
+                        self.text_element(value_element, &value.to_ascii_lowercase())?;
+                    } else {
+                        self.text_element(value_element, value)?;
+                    }
+                }
+                self.end(&param)?;
+            }
+            self.end("parameters")?;
+        }
+
+        let value = property.value.as_deref().unwrap_or_default();
+        match property.name.as_str() {
+            "GEO" => {
+                let (latitude, longitude) = value.split_once(';').ok_or_else(|| {
+                    Error::new(ErrorKind::SerializeError, "GEO must contain two floats")
+                })?;
+                self.text_element("latitude", latitude)?;
+                self.text_element("longitude", longitude)?;
+            }
+            "REQUEST-STATUS" => {
+                let mut parts = value.splitn(3, ';');
+                self.text_element("code", parts.next().unwrap_or_default())?;
+                self.text_element(
+                    "description",
+                    &unescape_text(parts.next().unwrap_or_default()),
+                )?;
+                if let Some(data) = parts.next() {
+                    self.text_element("data", &unescape_text(data))?;
+                }
+            }
+            name => {
+                let value_type = ValueType::of(property);
+
+                if !is_multi_valued(name) {
+                    self.write_value(value_type, value)?;
+                } else if value_type == ValueType::Text {
+                    for value in split_text_list(value) {
+                        self.write_value(value_type, &unescape_text(value))?;
+                    }
+                } else {
+                    for value in value.split(',') {
+                        self.write_value(value_type, value)?;
+                    }
+                }
+            }
+        }
+
+        self.end(&name)
+    }
+
+    fn write_value(&mut self, value_type: ValueType, value: &str) -> Result<()> {
+        match value_type {
+            ValueType::Text => self.text_element("text", value),
+            ValueType::Date => self.text_element("date", &to_xml_date(value)?),
+            ValueType::DateTime => self.text_element("date-time", &to_xml_date_time(value)?),
+            ValueType::Time => self.text_element("time", &to_xml_time(value)?),
+            ValueType::UtcOffset => self.text_element("utc-offset", &to_xml_utc_offset(value)?),
+            ValueType::Boolean => self.text_element("boolean", &value.to_ascii_lowercase()),
+            ValueType::Unknown => self.text_element("unknown", value),
+            ValueType::Period => {
+                let (start, end) = value.split_once('/').ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::SerializeError,
+                        &format!("Invalid period '{}'", value),
+                    )
+                })?;
+
+                self.start("period")?;
+                self.text_element("start", &to_xml_date_time(start)?)?;
+                if end
+                    .trim_start_matches(|c: char| c == '+' || c == '-')
+                    .starts_with('P')
+                {
+                    self.text_element("duration", end)?;
+                } else {
+                    self.text_element("end", &to_xml_date_time(end)?)?;
+                }
+                self.end("period")
+            }
+            ValueType::Recur => {
+                self.start("recur")?;
+                for part in value.split(';').filter(|p| !p.is_empty()) {
+                    let (key, values) = part.split_once('=').ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::SerializeError,
+                            &format!("Invalid recurrence rule part '{}'", part),
+                        )
+                    })?;
+                    let key = key.to_ascii_lowercase();
+
+                    if key == "until" {
+                        self.text_element(&key, &to_xml_date_or_date_time(values)?)?;
+                    } else {
+                        for value in values.split(',') {
+                            self.text_element(&key, value)?;
+                        }
+                    }
+                }
+                self.end("recur")
+            }
+            other => self.text_element(&other.as_str().to_ascii_lowercase(), value),
+        }
+    }
+}
+
+fn param_value_element(param: &str) -> &'static str {
+    match param {
+        "altrep" | "dir" => "uri",
+        "delegated-from" | "delegated-to" | "member" | "sent-by" => "cal-address",
+        "rsvp" => "boolean",
+        _ => "text",
+    }
+}
+
+fn read_calendar(node: &Node) -> Result<IcalCalendar> {
+    let mut calendar = IcalCalendar::new();
+    calendar.properties = read_properties(node)?;
+
+    for component in node.components() {
+        match component.name.as_str() {
+            "vevent" => calendar.events.push(read_event(component)?),
+            "vtimezone" => calendar.timezones.push(read_timezone(component)?),
+            other => log::warn!("Ignoring unsupported xCal component '{}'", other),
+        }
+    }
+
+    Ok(calendar)
+}
+
+fn read_timezone(node: &Node) -> Result<IcalTimeZone> {
+    let mut timezone = IcalTimeZone::new();
+    timezone.properties = read_properties(node)?;
+
+    for component in node.components() {
+        let transition = match component.name.as_str() {
+            "standard" => Transition::Standard,
+            "daylight" => Transition::Daylight,
+            other => {
+                log::warn!("Ignoring unsupported xCal timezone component '{}'", other);
+                continue;
+            }
+        };
+
+        let mut tr = IcalTimeZoneTransition::new(transition);
+        tr.properties = read_properties(component)?;
+        timezone.transitions.push(tr);
+    }
+
+    Ok(timezone)
+}
+
+fn read_event(node: &Node) -> Result<IcalEvent> {
+    let mut event = IcalEvent::new();
+    event.properties = read_properties(node)?;
+
+    for component in node.components() {
+        if component.name == "valarm" {
+            let mut alarm = IcalAlarm::new();
+            alarm.properties = read_properties(component)?;
+            event.alarms.push(alarm);
+        } else {
+            log::warn!(
+                "Ignoring unsupported xCal event component '{}'",
+                component.name
+            );
+        }
+    }
+
+    Ok(event)
+}
+
+fn read_properties(node: &Node) -> Result<Vec<Property>> {
+    node.child("properties")
+        .map_or(&[][..], |properties| properties.children.as_slice())
+        .iter()
+        .map(read_property)
+        .collect()
+}
+
+fn read_property(node: &Node) -> Result<Property> {
+    let name = node.name.to_ascii_uppercase();
+    let mut params = Vec::new();
+    let mut value_type = None;
+    let mut values = Vec::new();
+
+    for child in node.children.iter() {
+        if child.name == "parameters" {
+            for param in child.children.iter() {
+                let param_values = param
+                    .children
+                    .iter()
+                    .map(|v| {
+                        if v.name == "boolean" {
+                            v.text.to_ascii_uppercase()
+                        } else {
+                            v.text.clone()
+                        }
+                    })
+                    .collect();
+                params.push((param.name.to_ascii_uppercase(), param_values));
+            }
+            continue;
+        }
+
+        match name.as_str() {
+            "GEO" | "REQUEST-STATUS" => {}
+            _ => {
+                let (ty, value) = read_value(child)?;
+                value_type.get_or_insert(ty);
+                // Lists of TEXT values are kept in their escaped form
+                if ty == ValueType::Text && is_multi_valued(&name) {
+                    values.push(escape_text(&value));
+                } else {
+                    values.push(value);
+                }
+            }
+        }
+    }
+
+    let value = match name.as_str() {
+        "GEO" => format!(
+            "{};{}",
+            node.child_text("latitude").unwrap_or_default(),
+            node.child_text("longitude").unwrap_or_default()
+        ),
+        "REQUEST-STATUS" => {
+            let mut value = node.child_text("code").unwrap_or_default().to_owned();
+            for part in ["description", "data"] {
+                if let Some(text) = node.child_text(part) {
+                    value += ";";
+                    value += &escape_text(text);
+                }
+            }
+            value
+        }
+        _ => values.join(","),
+    };
+
+    if let Some(ty) = value_type {
+        if ty != ValueType::Unknown && ty != ValueType::default_for(&name) {
+            params.push(("VALUE".to_owned(), vec![ty.as_str().to_owned()]));
+        }
+    }
+
+    Ok(Property {
+        name,
+        params: if params.is_empty() {
+            None
+        } else {
+            Some(params)
+        },
+        value: Some(value),
+    })
+}
+
+fn read_value(node: &Node) -> Result<(ValueType, String)> {
+    let value = match node.name.as_str() {
+        "text" => (ValueType::Text, node.text.clone()),
+        "date" => (ValueType::Date, strip_xml_separators(&node.text)),
+        "date-time" => (ValueType::DateTime, strip_xml_separators(&node.text)),
+        "time" => (ValueType::Time, strip_xml_separators(&node.text)),
+        "utc-offset" => (ValueType::UtcOffset, strip_xml_separators(&node.text)),
+        "boolean" => (ValueType::Boolean, node.text.to_ascii_uppercase()),
+        "period" => {
+            let start = node
+                .child_text("start")
+                .ok_or_else(|| Error::new(ErrorKind::ParseError, "Period is missing its start"))?;
+            let end = if let Some(end) = node.child_text("end") {
+                strip_xml_separators(end)
+            } else if let Some(duration) = node.child_text("duration") {
+                duration.to_owned()
+            } else {
+                return Err(Error::new(
+                    ErrorKind::ParseError,
+                    "Period is missing its end or duration",
+                ));
+            };
+            (
+                ValueType::Period,
+                format!("{}/{}", strip_xml_separators(start), end),
+            )
+        }
+        "recur" => {
+            let mut parts: Vec<(String, Vec<String>)> = Vec::new();
+            for part in node.children.iter() {
+                let key = part.name.to_ascii_uppercase();
+                let value = if key == "UNTIL" {
+                    strip_xml_separators(&part.text)
+                } else {
+                    part.text.clone()
+                };
+
+                if let Some((_, values)) = parts.iter_mut().find(|(k, _)| k == &key) {
+                    values.push(value);
+                } else {
+                    parts.push((key, vec![value]));
+                }
+            }
+
+            let rule = parts
+                .into_iter()
+                .map(|(key, values)| format!("{}={}", key, values.join(",")))
+                .collect::<Vec<_>>()
+                .join(";");
+            (ValueType::Recur, rule)
+        }
+        name => {
+            let ty = ValueType::from_name(name).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::ParseError,
+                    &format!("Unknown xCal value type '{}'", name),
+                )
+            })?;
+            (ty, node.text.clone())
+        }
+    };
+
+    Ok(value)
+}
+
+fn invalid_value(kind: &str, value: &str) -> Error {
+    Error::new(
+        ErrorKind::SerializeError,
+        &format!("Invalid {} value '{}'", kind, value),
+    )
+}
+
+fn to_xml_date(value: &str) -> Result<String> {
+    if value.len() != 8 || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid_value("DATE", value));
+    }
+
+    Ok(format!(
+        "{}-{}-{}",
+        &value[0..4],
+        &value[4..6],
+        &value[6..8]
+    ))
+}
+
+fn to_xml_time(value: &str) -> Result<String> {
+    let (time, utc) = match value.strip_suffix('Z') {
+        Some(time) => (time, "Z"),
+        None => (value, ""),
+    };
+
+    if time.len() != 6 || !time.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid_value("TIME", value));
+    }
+
+    Ok(format!(
+        "{}:{}:{}{}",
+        &time[0..2],
+        &time[2..4],
+        &time[4..6],
+        utc
+    ))
+}
+
+fn to_xml_date_time(value: &str) -> Result<String> {
+    let (date, time) = value
+        .split_once('T')
+        .ok_or_else(|| invalid_value("DATE-TIME", value))?;
+
+    Ok(format!("{}T{}", to_xml_date(date)?, to_xml_time(time)?))
+}
+
+fn to_xml_date_or_date_time(value: &str) -> Result<String> {
+    if value.contains('T') {
+        to_xml_date_time(value)
+    } else {
+        to_xml_date(value)
+    }
+}
+
+fn to_xml_utc_offset(value: &str) -> Result<String> {
+    let (sign, offset) = value.split_at(value.len().min(1));
+
+    if !(sign == "+" || sign == "-")
+        || !(offset.len() == 4 || offset.len() == 6)
+        || !offset.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid_value("UTC-OFFSET", value));
+    }
+
+    let mut xml = format!("{}{}:{}", sign, &offset[0..2], &offset[2..4]);
+    if offset.len() == 6 {
+        xml += ":";
+        xml += &offset[4..6];
+    }
+
+    Ok(xml)
+}
+
+/// Strip the separators xCal adds to DATE, DATE-TIME, TIME and UTC-OFFSET values. The sign of
+/// an offset is kept as it is the first character.
+fn strip_xml_separators(value: &str) -> String {
+    let (first, rest) = value.split_at(value.len().min(1));
+    first.to_owned() + &rest.replace(|c: char| c == '-' || c == ':', "")
+}
+
+/// Minimal element tree of an XML document. xCal does not use attributes or mixed content, so
+/// every element either has children or text.
+struct Node {
+    name: String,
+    children: Vec<Node>,
+    text: String,
+}
+
+impl Node {
+    fn new(name: &[u8]) -> Result<Self> {
+        Ok(Node {
+            name: std::str::from_utf8(name)
+                .map_err(|e| Error::new(ErrorKind::ParseError, &e.to_string()))?
+                .to_owned(),
+            children: Vec::new(),
+            text: String::new(),
+        })
+    }
+
+    fn parse(input: &str) -> Result<Self> {
+        // Text is kept verbatim, as leading and trailing whitespace of TEXT values matters
+        let mut reader = Reader::from_str(input);
+
+        let mut stack: Vec<Node> = Vec::new();
+
+        loop {
+            match reader.read_event()? {
+                XmlEvent::Start(e) => stack.push(Node::new(e.local_name().as_ref())?),
+                XmlEvent::Empty(e) => {
+                    let node = Node::new(e.local_name().as_ref())?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => return Ok(node),
+                    }
+                }
+                XmlEvent::Text(t) => {
+                    if let Some(node) = stack.last_mut() {
+                        node.text += &t.unescape()?;
+                    }
+                }
+                XmlEvent::CData(t) => {
+                    if let Some(node) = stack.last_mut() {
+                        node.text += &String::from_utf8_lossy(&t.into_inner());
+                    }
+                }
+                XmlEvent::End(_) => {
+                    let mut node = stack.pop().unwrap();
+                    // Without mixed content, the text of elements with children is only the
+                    // whitespace between them
+                    if !node.children.is_empty() {
+                        node.text.clear();
+                    }
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => return Ok(node),
+                    }
+                }
+                XmlEvent::Eof => {
+                    return Err(Error::new(
+                        ErrorKind::ParseError,
+                        "Unexpected end of xCal document",
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.as_str())
+    }
+
+    fn components(&self) -> impl Iterator<Item = &Node> {
+        self.child("components")
+            .into_iter()
+            .flat_map(|components| components.children.iter())
+    }
+}
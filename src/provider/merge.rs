@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::Occurrence;
+
+pub type BoxedOccurrences<'a> = Box<dyn Iterator<Item = Occurrence<'a>> + 'a>;
+
+/// Lazily merges iterators of occurrences sorted by their begin into a single sorted iterator.
+/// Occurrences beginning at the same time are returned in the order of their sources.
+pub struct MergedOccurrences<'a> {
+    sources: Vec<BoxedOccurrences<'a>>,
+    /// The next occurrence of every source that is not exhausted yet
+    heads: Vec<Option<Occurrence<'a>>>,
+    queue: BinaryHeap<Reverse<(DateTime<Utc>, usize)>>,
+}
+
+impl<'a> MergedOccurrences<'a> {
+    pub fn new(sources: impl IntoIterator<Item = BoxedOccurrences<'a>>) -> Self {
+        let sources = sources.into_iter().collect::<Vec<_>>();
+        let mut merged = MergedOccurrences {
+            heads: sources.iter().map(|_| None).collect(),
+            queue: BinaryHeap::with_capacity(sources.len()),
+            sources,
+        };
+
+        for idx in 0..merged.sources.len() {
+            merged.advance(idx);
+        }
+        merged
+    }
+
+    fn advance(&mut self, idx: usize) {
+        self.heads[idx] = self.sources[idx].next();
+        if let Some(occurrence) = &self.heads[idx] {
+            self.queue.push(Reverse((occurrence.begin(), idx)));
+        }
+    }
+}
+
+impl<'a> Iterator for MergedOccurrences<'a> {
+    type Item = Occurrence<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, idx)) = self.queue.pop()?;
+        let occurrence = self.heads[idx].take();
+        self.advance(idx);
+        occurrence
+    }
+}
@@ -1,46 +1,38 @@
 use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use rrule::RRule;
-use std::default::Default;
-use std::ops::{Bound, RangeBounds};
+use std::ops::Bound;
 use std::path::Path;
 
 pub mod calendar;
 pub mod datetime;
 pub mod error;
+pub mod filter;
+pub mod merge;
 
 pub mod ical;
+pub mod properties;
 
 pub use calendar::*;
 pub use datetime::*;
 pub use error::*;
+pub use filter::EventFilter;
+pub use merge::MergedOccurrences;
+pub use properties::*;
 
 pub type Result<T> = std::result::Result<T, self::Error>;
 
-pub enum EventFilter {
-    InRange(Bound<NaiveDateTime>, Bound<NaiveDateTime>),
-}
-
-impl Default for EventFilter {
-    fn default() -> Self {
-        EventFilter::InRange(Bound::Unbounded, Bound::Unbounded)
-    }
-}
-
-impl EventFilter {
-    pub fn datetime_range<R: RangeBounds<NaiveDateTime>>(self, range: R) -> Self {
-        EventFilter::InRange(range.start_bound().cloned(), range.end_bound().cloned())
-    }
-}
-
 pub struct NewEvent<Tz: TimeZone> {
     pub begin: DateTime<Tz>,
     pub tz: Tz,
     pub end: Option<DateTime<Tz>>,
     pub duration: Option<Duration>,
+    pub allday: bool,
     pub title: Option<String>,
     pub description: Option<String>,
+    pub location: Option<String>,
     pub rrule: Option<RRule<rrule::Unvalidated>>,
+    pub alarms: Vec<Alarm>,
 }
 
 impl<Tz: TimeZone> NewEvent<Tz> {
@@ -51,9 +43,12 @@ impl<Tz: TimeZone> NewEvent<Tz> {
             tz,
             end: None,
             duration: None,
+            allday: false,
             title: None,
             description: None,
+            location: None,
             rrule: None,
+            alarms: Vec::new(),
         }
     }
     pub fn set_title(&mut self, title: &str) {
@@ -64,6 +59,18 @@ impl<Tz: TimeZone> NewEvent<Tz> {
         self.description = Some(description.to_string());
     }
 
+    pub fn set_location(&mut self, location: &str) {
+        self.location = Some(location.to_string());
+    }
+
+    pub fn set_allday(&mut self, allday: bool) {
+        self.allday = allday;
+    }
+
+    pub fn add_alarm(&mut self, alarm: Alarm) {
+        self.alarms.push(alarm);
+    }
+
     pub fn set_begin(&mut self, begin: NaiveDateTime) {
         self.begin = self.tz.from_local_datetime(&begin).earliest().unwrap();
     }
@@ -81,6 +88,29 @@ impl<Tz: TimeZone> NewEvent<Tz> {
     pub fn _set_repeat(&mut self, freq: rrule::Frequency, interval: u16) {
         self.rrule = Some(RRule::new(freq).interval(interval));
     }
+
+    /// The span of the (first occurrence of the) event. All-day events last whole days, and
+    /// events without end or duration are instants.
+    pub fn span(&self) -> TimeSpan<Tz> {
+        if self.allday {
+            let begin = self.begin.date_naive();
+            let end = if let Some(end) = &self.end {
+                Some(end.date_naive())
+            } else {
+                self.duration
+                    .filter(|duration| duration.num_days() > 1)
+                    .map(|duration| begin + Duration::days(duration.num_days()))
+            };
+
+            TimeSpan::Allday(begin, end, self.tz.clone())
+        } else if let Some(end) = &self.end {
+            TimeSpan::from_start_and_end(self.begin.clone(), end.clone())
+        } else if let Some(duration) = self.duration {
+            TimeSpan::from_start_and_duration(self.begin.clone(), duration)
+        } else {
+            TimeSpan::from_start(self.begin.clone())
+        }
+    }
 }
 
 pub trait Eventlike {
@@ -88,14 +118,61 @@ pub trait Eventlike {
     fn uid(&self) -> &str;
     fn summary(&self) -> &str;
     fn description(&self) -> Option<&str>;
+    /// Location of an alternative representation of the description, e.g., a web page.
+    fn description_uri(&self) -> Option<&str>;
+    fn location(&self) -> Option<&str>;
+    fn url(&self) -> Option<&str>;
+    fn categories(&self) -> Vec<String>;
+    fn status(&self) -> Option<EventStatus>;
+    fn transparency(&self) -> Transparency;
+    fn class(&self) -> Classification;
+    /// Priority from 1 (highest) to 9 (lowest), `None` if undefined
+    fn priority(&self) -> Option<u8>;
+    fn organizer(&self) -> Option<CalAddress>;
+    fn attendees(&self) -> Vec<Attendee>;
+    fn created(&self) -> Option<DateTime<Utc>>;
+    fn last_modified(&self) -> Option<DateTime<Utc>>;
+    fn alarms(&self) -> Vec<Alarm>;
+
+    fn is_cancelled(&self) -> bool {
+        self.status() == Some(EventStatus::Cancelled)
+    }
+
+    /// The attendee entry of the calendar user owning one of `addresses`.
+    fn attendee_for(&self, addresses: &[String]) -> Option<Attendee> {
+        self.attendees().into_iter().find(|attendee| {
+            addresses
+                .iter()
+                .any(|address| attendee.address.matches(address))
+        })
+    }
+
+    /// Whether the calendar user owning one of `addresses` declined the event.
+    fn is_declined_by(&self, addresses: &[String]) -> bool {
+        self.attendee_for(addresses).map_or(false, |attendee| {
+            attendee.status == ParticipationStatus::Declined
+        })
+    }
     fn occurrence_rule(&self) -> &OccurrenceRule<Tz>;
     fn tz(&self) -> &Tz;
     fn duration(&self) -> Duration;
+
+    /// Modified instances of a recurring event. They are events of their own, which the
+    /// occurrences of the event leave out.
+    fn overrides(&self) -> &[Self]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
 }
 
+#[derive(Clone)]
 pub struct Occurrence<'a> {
     pub span: TimeSpan<Utc>,
     pub event: &'a dyn Eventlike,
+    /// Name of the calendar containing the event
+    pub calendar: &'a str,
 }
 
 impl Occurrence<'_> {
@@ -112,6 +189,10 @@ impl Occurrence<'_> {
     }
 }
 
+fn any_event(_: &dyn Eventlike) -> bool {
+    true
+}
+
 pub trait Calendarlike {
     fn name(&self) -> &str;
     fn path(&self) -> &Path;
@@ -121,6 +202,27 @@ pub trait Calendarlike {
         begin: Bound<DateTime<Utc>>,
         end: Bound<DateTime<Utc>>,
     ) -> Vec<Occurrence<'a>>;
+    /// The occurrences overlapping the range between `begin` and `end`, lazily and sorted by
+    /// their begin. Unlike `events_in`, this may be used with an unbounded end.
+    fn occurrences_in<'a>(
+        &'a self,
+        begin: Bound<DateTime<Utc>>,
+        end: Bound<DateTime<Utc>>,
+    ) -> MergedOccurrences<'a> {
+        self.occurrences_where(begin, end, &any_event)
+    }
+    /// Like `occurrences_in`, but only of the events matching `predicate`. Events are filtered
+    /// before their occurrences are expanded, so an unbounded range only yields infinitely
+    /// many occurrences if a matching event recurs infinitely.
+    fn occurrences_where<'a>(
+        &'a self,
+        begin: Bound<DateTime<Utc>>,
+        end: Bound<DateTime<Utc>>,
+        predicate: &dyn Fn(&dyn Eventlike) -> bool,
+    ) -> MergedOccurrences<'a>;
+    /// All occurrences matching `filter`, sorted by their begin. If a matching event recurs
+    /// infinitely, the iterator is unbounded; see `query_bounds` for the horizon applied to
+    /// open-ended queries.
     fn filter_events<'a>(&'a self, filter: EventFilter) -> Vec<Occurrence<'a>>;
 }
 
@@ -0,0 +1,464 @@
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Error, ErrorKind, Result};
+
+/// Overall confirmation state of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventStatus {
+    Tentative,
+    Confirmed,
+    Cancelled,
+}
+
+impl FromStr for EventStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "TENTATIVE" => Ok(EventStatus::Tentative),
+            "CONFIRMED" => Ok(EventStatus::Confirmed),
+            "CANCELLED" => Ok(EventStatus::Cancelled),
+            _ => Err(Error::new(
+                ErrorKind::EventParse,
+                &format!("Invalid event status '{}'", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EventStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            EventStatus::Tentative => "TENTATIVE",
+            EventStatus::Confirmed => "CONFIRMED",
+            EventStatus::Cancelled => "CANCELLED",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Whether an event consumes time, i.e., makes its attendees busy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    Opaque,
+    Transparent,
+}
+
+impl Default for Transparency {
+    fn default() -> Self {
+        Transparency::Opaque
+    }
+}
+
+impl FromStr for Transparency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "OPAQUE" => Ok(Transparency::Opaque),
+            "TRANSPARENT" => Ok(Transparency::Transparent),
+            _ => Err(Error::new(
+                ErrorKind::EventParse,
+                &format!("Invalid time transparency '{}'", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Transparency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Transparency::Opaque => "OPAQUE",
+            Transparency::Transparent => "TRANSPARENT",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Access classification of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Classification {
+    Public,
+    Private,
+    Confidential,
+    Other(String),
+}
+
+impl Default for Classification {
+    fn default() -> Self {
+        Classification::Public
+    }
+}
+
+impl From<&str> for Classification {
+    fn from(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "PUBLIC" => Classification::Public,
+            "PRIVATE" => Classification::Private,
+            "CONFIDENTIAL" => Classification::Confidential,
+            other => Classification::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Classification::Public => "PUBLIC",
+            Classification::Private => "PRIVATE",
+            Classification::Confidential => "CONFIDENTIAL",
+            Classification::Other(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// A calendar user, e.g., the organizer of an event, given by an address like
+/// `mailto:jane@example.com` and an optional common name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalAddress {
+    pub address: String,
+    pub name: Option<String>,
+}
+
+impl CalAddress {
+    pub fn new(address: &str) -> Self {
+        CalAddress {
+            address: address.to_owned(),
+            name: None,
+        }
+    }
+
+    /// The email address if this is a `mailto:` address.
+    pub fn email(&self) -> Option<&str> {
+        let (scheme, email) = self.address.split_once(':')?;
+        if scheme.eq_ignore_ascii_case("mailto") {
+            Some(email)
+        } else {
+            None
+        }
+    }
+
+    /// Whether this address matches `other`, which may be given with or without `mailto:`.
+    pub fn matches(&self, other: &str) -> bool {
+        let other = match other.split_once(':') {
+            Some((scheme, email)) if scheme.eq_ignore_ascii_case("mailto") => email,
+            _ => other,
+        };
+
+        self.email()
+            .unwrap_or(&self.address)
+            .eq_ignore_ascii_case(other.trim())
+    }
+}
+
+impl fmt::Display for CalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = self.email().unwrap_or(&self.address);
+        match &self.name {
+            Some(name) => write!(f, "{} <{}>", name, address),
+            None => write!(f, "{}", address),
+        }
+    }
+}
+
+/// Participation role of an attendee.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Role {
+    Chair,
+    Required,
+    Optional,
+    NonParticipant,
+    Other(String),
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::Required
+    }
+}
+
+impl From<&str> for Role {
+    fn from(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "CHAIR" => Role::Chair,
+            "REQ-PARTICIPANT" => Role::Required,
+            "OPT-PARTICIPANT" => Role::Optional,
+            "NON-PARTICIPANT" => Role::NonParticipant,
+            other => Role::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Role::Chair => "CHAIR",
+            Role::Required => "REQ-PARTICIPANT",
+            Role::Optional => "OPT-PARTICIPANT",
+            Role::NonParticipant => "NON-PARTICIPANT",
+            Role::Other(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Participation status of an attendee of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParticipationStatus {
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+    Delegated,
+    Other(String),
+}
+
+impl Default for ParticipationStatus {
+    fn default() -> Self {
+        ParticipationStatus::NeedsAction
+    }
+}
+
+impl ParticipationStatus {
+    /// A short marker for the status, as shown next to attendees.
+    pub fn symbol(&self) -> char {
+        match self {
+            ParticipationStatus::NeedsAction => '?',
+            ParticipationStatus::Accepted => '✓',
+            ParticipationStatus::Declined => '✗',
+            ParticipationStatus::Tentative => '~',
+            ParticipationStatus::Delegated => '→',
+            ParticipationStatus::Other(_) => ' ',
+        }
+    }
+}
+
+impl From<&str> for ParticipationStatus {
+    fn from(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "NEEDS-ACTION" => ParticipationStatus::NeedsAction,
+            "ACCEPTED" => ParticipationStatus::Accepted,
+            "DECLINED" => ParticipationStatus::Declined,
+            "TENTATIVE" => ParticipationStatus::Tentative,
+            "DELEGATED" => ParticipationStatus::Delegated,
+            other => ParticipationStatus::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for ParticipationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ParticipationStatus::NeedsAction => "NEEDS-ACTION",
+            ParticipationStatus::Accepted => "ACCEPTED",
+            ParticipationStatus::Declined => "DECLINED",
+            ParticipationStatus::Tentative => "TENTATIVE",
+            ParticipationStatus::Delegated => "DELEGATED",
+            ParticipationStatus::Other(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Kind of calendar user, e.g., a person or a room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarUserType {
+    Individual,
+    Group,
+    Resource,
+    Room,
+    Unknown,
+    Other(String),
+}
+
+impl Default for CalendarUserType {
+    fn default() -> Self {
+        CalendarUserType::Individual
+    }
+}
+
+impl From<&str> for CalendarUserType {
+    fn from(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "INDIVIDUAL" => CalendarUserType::Individual,
+            "GROUP" => CalendarUserType::Group,
+            "RESOURCE" => CalendarUserType::Resource,
+            "ROOM" => CalendarUserType::Room,
+            "UNKNOWN" => CalendarUserType::Unknown,
+            other => CalendarUserType::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for CalendarUserType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CalendarUserType::Individual => "INDIVIDUAL",
+            CalendarUserType::Group => "GROUP",
+            CalendarUserType::Resource => "RESOURCE",
+            CalendarUserType::Room => "ROOM",
+            CalendarUserType::Unknown => "UNKNOWN",
+            CalendarUserType::Other(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// An attendee of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attendee {
+    pub address: CalAddress,
+    pub role: Role,
+    pub status: ParticipationStatus,
+    /// Whether a reply is expected from the attendee
+    pub rsvp: bool,
+    pub cutype: CalendarUserType,
+}
+
+impl Attendee {
+    pub fn new(address: CalAddress) -> Self {
+        Attendee {
+            address,
+            role: Role::default(),
+            status: ParticipationStatus::default(),
+            rsvp: false,
+            cutype: CalendarUserType::default(),
+        }
+    }
+}
+
+impl fmt::Display for Attendee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.status.symbol(), self.address)?;
+        match self.role {
+            Role::Chair => write!(f, " (chair)"),
+            Role::Optional => write!(f, " (optional)"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What an alarm does when it is triggered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlarmAction {
+    Display,
+    Audio,
+    Email,
+    Other(String),
+}
+
+impl Default for AlarmAction {
+    fn default() -> Self {
+        AlarmAction::Display
+    }
+}
+
+impl From<&str> for AlarmAction {
+    fn from(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "DISPLAY" => AlarmAction::Display,
+            "AUDIO" => AlarmAction::Audio,
+            "EMAIL" => AlarmAction::Email,
+            other => AlarmAction::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for AlarmAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AlarmAction::Display => "DISPLAY",
+            AlarmAction::Audio => "AUDIO",
+            AlarmAction::Email => "EMAIL",
+            AlarmAction::Other(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// The edge of an event a relative alarm trigger refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmRelation {
+    Start,
+    End,
+}
+
+impl Default for AlarmRelation {
+    fn default() -> Self {
+        AlarmRelation::Start
+    }
+}
+
+/// When an alarm is triggered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlarmTrigger {
+    /// Offset to the start or end of each occurrence, negative offsets trigger before
+    Relative {
+        offset: Duration,
+        related: AlarmRelation,
+    },
+    Absolute(DateTime<Utc>),
+}
+
+/// A reminder of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alarm {
+    pub action: AlarmAction,
+    pub trigger: AlarmTrigger,
+    /// Number of additional repetitions after the alarm was triggered first
+    pub repeat: u32,
+    /// Time between repetitions
+    pub interval: Option<Duration>,
+    pub description: Option<String>,
+}
+
+impl Alarm {
+    /// A displayed reminder `before` the start of an event.
+    pub fn before_start(before: Duration) -> Self {
+        Alarm {
+            action: AlarmAction::Display,
+            trigger: AlarmTrigger::Relative {
+                offset: -before,
+                related: AlarmRelation::Start,
+            },
+            repeat: 0,
+            interval: None,
+            description: None,
+        }
+    }
+
+    /// All times the alarm goes off for an occurrence from `begin` to `end`, including
+    /// repetitions.
+    pub fn trigger_times(&self, begin: DateTime<Utc>, end: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let first = match self.trigger {
+            AlarmTrigger::Relative {
+                offset,
+                related: AlarmRelation::Start,
+            } => begin + offset,
+            AlarmTrigger::Relative {
+                offset,
+                related: AlarmRelation::End,
+            } => end + offset,
+            AlarmTrigger::Absolute(at) => at,
+        };
+
+        let repetitions = match self.interval {
+            Some(interval) if interval > Duration::zero() => self.repeat,
+            _ => 0,
+        };
+
+        (0..=repetitions)
+            .map(|n| first + self.interval.unwrap_or_else(Duration::zero) * n as i32)
+            .collect()
+    }
+}
@@ -0,0 +1,293 @@
+//! Time spent in events, summed up by calendar, category, week or month, e.g., for billing.
+
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::str::FromStr;
+
+use crate::agenda::Agenda;
+use crate::exchange::selected_calendars;
+use crate::provider::{local_midnight, Error, ErrorKind, Eventlike, Occurrence, Result};
+
+/// Group of the events without category
+const NO_CATEGORY: &str = "(none)";
+
+/// Formats reports can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(ReportFormat::Text),
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(Error::new(
+                ErrorKind::ParseError,
+                &format!("Unknown format '{}'", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ReportFormat::Text => "text",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// What the time of events is summed up by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    Calendar,
+    Category,
+    /// ISO week of the local begin, e.g., "2024-W19"
+    Week,
+    /// Month of the local begin, e.g., "2024-05"
+    Month,
+}
+
+impl FromStr for Grouping {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "calendar" => Ok(Grouping::Calendar),
+            "category" => Ok(Grouping::Category),
+            "week" => Ok(Grouping::Week),
+            "month" => Ok(Grouping::Month),
+            _ => Err(Error::new(
+                ErrorKind::ParseError,
+                &format!(
+                    "Unknown grouping '{}', expected calendar, category, week or month",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Grouping::Calendar => "calendar",
+            Grouping::Category => "category",
+            Grouping::Week => "week",
+            Grouping::Month => "month",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl Grouping {
+    /// The groups the time of `occurrence` counts towards. Events with several categories
+    /// count towards each of them.
+    fn groups(&self, occurrence: &Occurrence) -> Vec<String> {
+        let date = occurrence.begin().with_timezone(&Local).date_naive();
+
+        match self {
+            Grouping::Calendar => vec![occurrence.calendar.to_owned()],
+            Grouping::Category => {
+                let mut categories = occurrence.event().categories();
+                categories.sort();
+                categories.dedup();
+                if categories.is_empty() {
+                    vec![NO_CATEGORY.to_owned()]
+                } else {
+                    categories
+                }
+            }
+            Grouping::Week => {
+                let week = date.iso_week();
+                vec![format!("{}-W{:02}", week.year(), week.week())]
+            }
+            Grouping::Month => vec![date.format("%Y-%m").to_string()],
+        }
+    }
+}
+
+/// Time spent in the events of a group. All-day events are counted separately, as their
+/// duration says little about the time spent.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReportRow {
+    pub group: String,
+    /// Number of occurrences with a time of day
+    pub events: usize,
+    /// Total duration of these occurrences
+    pub minutes: i64,
+    /// Number of all-day occurrences
+    pub allday_events: usize,
+    /// Total number of days of the all-day occurrences
+    pub allday_days: i64,
+}
+
+impl ReportRow {
+    fn new(group: &str) -> Self {
+        ReportRow {
+            group: group.to_owned(),
+            ..ReportRow::default()
+        }
+    }
+
+    fn add(&mut self, occurrence: &Occurrence) {
+        let duration = occurrence.span.duration();
+        if occurrence.span.is_allday() {
+            self.allday_events += 1;
+            self.allday_days += duration.num_days();
+        } else {
+            self.events += 1;
+            self.minutes += duration.num_minutes();
+        }
+    }
+}
+
+/// Format minutes as hours like "12:05".
+fn format_minutes(minutes: i64) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// The time spent in events between two days.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub grouping: Grouping,
+    /// Sorted by their group
+    pub rows: Vec<ReportRow>,
+    /// All occurrences, each counted once even if it belongs to several groups
+    pub total: ReportRow,
+}
+
+impl Report {
+    /// The time spent in the occurrences beginning on the local days `from` until `to` of the
+    /// calendars named `calendars`, or of all calendars if none is named. Cancelled events
+    /// and events declined by the calendar user owning one of `addresses` do not count.
+    pub fn compute(
+        agenda: &Agenda,
+        calendars: &[String],
+        grouping: Grouping,
+        from: NaiveDate,
+        to: NaiveDate,
+        addresses: &[String],
+    ) -> Result<Self> {
+        let begin = Utc.from_utc_datetime(&local_midnight(from));
+        let end = Utc.from_utc_datetime(&local_midnight(to.succ_opt().unwrap()));
+        let counts =
+            |event: &dyn Eventlike| !event.is_cancelled() && !event.is_declined_by(addresses);
+
+        let mut rows = BTreeMap::new();
+        let mut total = ReportRow::new("Total");
+        for calendar in selected_calendars(agenda, calendars)? {
+            let occurrences = calendar.as_calendar().occurrences_where(
+                Bound::Included(begin),
+                Bound::Excluded(end),
+                &counts,
+            );
+
+            // Occurrences that began earlier belong to an earlier report
+            for occurrence in occurrences.filter(|occurrence| occurrence.begin() >= begin) {
+                for group in grouping.groups(&occurrence) {
+                    rows.entry(group.clone())
+                        .or_insert_with(|| ReportRow::new(&group))
+                        .add(&occurrence);
+                }
+                total.add(&occurrence);
+            }
+        }
+
+        Ok(Report {
+            from,
+            to,
+            grouping,
+            rows: rows.into_values().collect(),
+            total,
+        })
+    }
+
+    /// The report as a table with a row per group and the total below.
+    pub fn to_table(&self) -> String {
+        let header = match self.grouping {
+            Grouping::Calendar => "Calendar",
+            Grouping::Category => "Category",
+            Grouping::Week => "Week",
+            Grouping::Month => "Month",
+        };
+        let width = self
+            .rows
+            .iter()
+            .map(|row| row.group.chars().count())
+            .chain([header.len(), self.total.group.len()])
+            .max()
+            .unwrap_or_default();
+        let line = |row: &ReportRow| {
+            format!(
+                "{:<width$}  {:>6}  {:>8}  {:>7}  {:>4}\n",
+                row.group,
+                row.events,
+                format_minutes(row.minutes),
+                row.allday_events,
+                row.allday_days,
+                width = width
+            )
+        };
+
+        let mut table = format!(
+            "Time by {} from {} to {}:\n",
+            self.grouping, self.from, self.to
+        );
+        table += &format!(
+            "{:<width$}  {:>6}  {:>8}  {:>7}  {:>4}\n",
+            header,
+            "Events",
+            "Hours",
+            "All-day",
+            "Days",
+            width = width
+        );
+        for row in self.rows.iter() {
+            table += &line(row);
+        }
+        table += &format!("{}\n", "-".repeat(width + 33));
+        table += &line(&self.total);
+
+        table
+    }
+
+    /// Write the report in `format`. CSV has a row per group and the total as last row.
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        let serialize_error = |message: String| Error::new(ErrorKind::SerializeError, &message);
+
+        match format {
+            ReportFormat::Text => Ok(self.to_table()),
+            ReportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for row in self.rows.iter().chain([&self.total]) {
+                    writer.serialize(row)?;
+                }
+                let bytes = writer
+                    .into_inner()
+                    .map_err(|e| serialize_error(e.to_string()))?;
+                String::from_utf8(bytes).map_err(|e| serialize_error(e.to_string()))
+            }
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| serialize_error(e.to_string()))
+            }
+        }
+    }
+}
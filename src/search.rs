@@ -0,0 +1,82 @@
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+use crate::provider::{Error, ErrorKind, Eventlike, Result};
+
+/// Prefix of search input that is a regular expression rather than plain text.
+pub const REGEX_PREFIX: &str = "re:";
+
+enum Pattern {
+    /// Lowercase text
+    Substring(String),
+    Regex(Regex),
+}
+
+/// Text to look for in the title, description, location and categories of events. Matching is
+/// case-insensitive.
+pub struct SearchQuery {
+    pattern: Pattern,
+    input: String,
+}
+
+impl SearchQuery {
+    pub fn substring(text: &str) -> Self {
+        SearchQuery {
+            pattern: Pattern::Substring(text.to_lowercase()),
+            input: text.to_owned(),
+        }
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::ParseError,
+                    &format!("Invalid regular expression '{}': {}", pattern, e),
+                )
+            })?;
+
+        Ok(SearchQuery {
+            pattern: Pattern::Regex(regex),
+            input: format!("{}{}", REGEX_PREFIX, pattern),
+        })
+    }
+
+    /// Parse search input, which is a regular expression if it starts with `re:` and plain text
+    /// otherwise.
+    pub fn parse(input: &str) -> Result<Self> {
+        match input.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => SearchQuery::regex(pattern),
+            None => Ok(SearchQuery::substring(input)),
+        }
+    }
+
+    pub fn matches_text(&self, text: &str) -> bool {
+        match &self.pattern {
+            Pattern::Substring(needle) => text.to_lowercase().contains(needle.as_str()),
+            Pattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+
+    pub fn matches(&self, event: &dyn Eventlike) -> bool {
+        self.matches_text(event.title())
+            || event
+                .description()
+                .map_or(false, |text| self.matches_text(text))
+            || event
+                .location()
+                .map_or(false, |text| self.matches_text(text))
+            || event
+                .categories()
+                .iter()
+                .any(|category| self.matches_text(category))
+    }
+}
+
+impl fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.input)
+    }
+}
@@ -0,0 +1,139 @@
+//! Free time slots for meetings within the working hours, found between busy periods.
+
+use chrono::{Datelike, Duration, Local, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::SlotsConfig;
+use crate::exchange::freebusy::{FreeBusy, Period};
+use crate::provider::{parse_duration, Error, ErrorKind, NewEvent, Result};
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Local time of day during which meetings may take place, e.g., "09:00-17:00".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkingHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl FromStr for WorkingHours {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::TimeParse,
+                &format!("Invalid working hours '{}', expected e.g. '09:00-17:00'", s),
+            )
+        };
+
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let hours = WorkingHours {
+            start: NaiveTime::parse_from_str(start.trim(), TIME_FORMAT)?,
+            end: NaiveTime::parse_from_str(end.trim(), TIME_FORMAT)?,
+        };
+
+        if hours.start < hours.end {
+            Ok(hours)
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl fmt::Display for WorkingHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT)
+        )
+    }
+}
+
+/// What makes a free slot suitable for a meeting.
+#[derive(Debug, Clone)]
+pub struct SlotOptions {
+    /// Length of the meeting
+    pub duration: Duration,
+    pub hours: WorkingHours,
+    /// Free time kept between the meeting and other events
+    pub buffer: Duration,
+    /// Whether meetings may take place on Saturdays and Sundays
+    pub weekends: bool,
+}
+
+impl SlotOptions {
+    pub fn from_config(config: &SlotsConfig, duration: Duration) -> Result<Self> {
+        Ok(SlotOptions {
+            duration,
+            hours: config.hours.parse()?,
+            buffer: parse_duration(&config.buffer)?,
+            weekends: config.weekends,
+        })
+    }
+}
+
+/// The free windows within the working hours of every day of `freebusy` that are long enough
+/// for a meeting including the buffers, sorted by their start. Windows exclude the buffers,
+/// so a meeting may take place anywhere within them.
+pub fn free_slots(freebusy: &FreeBusy, options: &SlotOptions) -> Vec<Period> {
+    let mut slots = Vec::new();
+    let local_time = |date: chrono::NaiveDate, time: NaiveTime| {
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .map(|datetime| datetime.with_timezone(&Utc))
+    };
+
+    let mut date = freebusy.start.with_timezone(&Local).date_naive();
+    let last = freebusy.end.with_timezone(&Local).date_naive();
+    while date <= last {
+        let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+        let window = local_time(date, options.hours.start).zip(local_time(date, options.hours.end));
+        date = date.succ_opt().unwrap();
+
+        let (start, end) = match window {
+            Some(window) if options.weekends || !weekend => window,
+            _ => continue,
+        };
+        let (mut free_from, end) = (start.max(freebusy.start), end.min(freebusy.end));
+
+        for busy in freebusy.busy.iter() {
+            let (busy_start, busy_end) = (busy.start - options.buffer, busy.end + options.buffer);
+            if busy_end <= free_from {
+                continue;
+            }
+            if busy_start >= end {
+                break;
+            }
+
+            if busy_start - free_from >= options.duration {
+                slots.push(Period {
+                    start: free_from,
+                    end: busy_start,
+                });
+            }
+            free_from = busy_end;
+        }
+
+        if end - free_from >= options.duration {
+            slots.push(Period {
+                start: free_from,
+                end,
+            });
+        }
+    }
+
+    slots
+}
+
+/// A meeting of `duration` at the start of `slot`, to be added to a calendar.
+pub fn slot_event(slot: &Period, duration: Duration) -> NewEvent<Tz> {
+    let mut event = NewEvent::new(slot.start.with_timezone(&chrono_tz::UTC));
+    event.set_duration(duration);
+    event
+}
@@ -1,9 +1,11 @@
+use chrono::NaiveDate;
+
 use crate::agenda::Agenda;
 use crate::config::Config;
 use crate::events::{Dispatcher, Event};
 use crate::provider::NewEvent;
 
-use super::{CalendarWindow, Context, EventWindow, EventWindowBehaviour, Mode};
+use super::{CalendarWindow, Context, EventWindowBehaviour, Mode, SidePane};
 
 use unsegen::base::{GraphemeCluster, Terminal};
 use unsegen::input::{
@@ -13,6 +15,7 @@ use unsegen::widget::*;
 
 use super::command::CommandParser;
 use super::insert::InsertParser;
+use super::search::SearchPrompt;
 
 pub struct App<'a> {
     config: &'a Config,
@@ -21,10 +24,15 @@ pub struct App<'a> {
 
 impl<'a> App<'a> {
     pub fn new(config: &'a Config, agenda: Agenda) -> App<'a> {
-        let context = Context::new(agenda);
+        let context = Context::new(agenda, config);
         App { config, context }
     }
 
+    /// Move the cursor to `date` and select the event with `uid` on it, if any.
+    pub fn focus(&mut self, date: NaiveDate, uid: Option<&str>) {
+        self.context.focus(date, uid);
+    }
+
     fn bottom_bar<'w>(&'w self) -> impl Widget + 'w {
         let spacer = " ".with_demand(|_| Demand2D {
             width: ColDemand::exact(1),
@@ -34,9 +42,17 @@ impl<'a> App<'a> {
         let mut layout = HLayout::new()
             .separator(GraphemeCluster::try_from(' ').unwrap())
             .widget(spacer);
-        if let mode @ (Mode::Command | Mode::Insert) = self.context.mode {
+        if let mode @ (Mode::Command | Mode::Insert | Mode::Search) = self.context.mode {
             layout = layout.widget(self.context.input_sink(mode).as_widget());
         }
+        if let Some(message) = self
+            .context
+            .last_error_message
+            .as_ref()
+            .or(self.context.status_message.as_ref())
+        {
+            layout = layout.widget(message.as_str());
+        }
 
         layout
     }
@@ -49,7 +65,7 @@ impl<'a> App<'a> {
             .widget(
                 HLayout::new()
                     .widget(CalendarWindow::new(&self.context))
-                    .widget(EventWindow::new(&self.context)),
+                    .widget(SidePane::new(&self.context)),
             )
             .widget(self.bottom_bar());
 
@@ -78,23 +94,39 @@ impl<'a> App<'a> {
                     Event::Input(input) => {
                         let num_events_of_current_day = self
                             .context
-                            .agenda()
-                            .events_of_day(&self.context.cursor().date_naive())
-                            .count();
+                            .visible_events_of_day(&self.context.cursor().date_naive())
+                            .len();
 
                         if input.matches(Key::Esc) {
                             self.context.mode = Mode::Normal;
+                            self.context.chosen_slot = None;
+                            self.context.unconfirmed_insert = None;
+                            self.context.stats = None;
                         } else {
                             match self.context.mode {
                                 Mode::Normal => {
                                     let _leftover = input
                                         .chain((Key::Char('q'), || run = false))
                                         .chain((Key::Char(':'), || {
+                                            self.context.clear_messages();
                                             self.context.mode = Mode::Command
                                         }))
                                         .chain((Key::Char('i'), || {
+                                            self.context.clear_messages();
                                             self.context.mode = Mode::Insert
                                         }))
+                                        .chain((Key::Char('/'), || {
+                                            self.context.clear_messages();
+                                            self.context.mode = Mode::Search
+                                        }))
+                                        .chain((Key::Char('n'), || {
+                                            self.context.clear_messages();
+                                            self.context.jump_to_search_hit(true)
+                                        }))
+                                        .chain((Key::Char('N'), || {
+                                            self.context.clear_messages();
+                                            self.context.jump_to_search_hit(false)
+                                        }))
                                         .chain(
                                             NavigateBehavior::new(&mut CursorBehaviour(
                                                 &mut self.context,
@@ -117,6 +149,10 @@ impl<'a> App<'a> {
                                 mode @ Mode::Insert => {
                                     let begin =
                                         self.context.cursor().with_timezone(&chrono_tz::UTC);
+                                    let new_event = self
+                                        .context
+                                        .slot_event()
+                                        .unwrap_or_else(|| NewEvent::new(begin));
 
                                     input
                                         .chain(
@@ -134,7 +170,7 @@ impl<'a> App<'a> {
                                         .chain(InsertParser::new(
                                             &mut self.context,
                                             &self.config,
-                                            NewEvent::new(begin),
+                                            new_event,
                                         ))
                                         .finish();
                                 }
@@ -155,6 +191,23 @@ impl<'a> App<'a> {
                                         .chain(CommandParser::new(&mut self.context, &self.config))
                                         .finish();
                                 }
+                                mode @ Mode::Search => {
+                                    input
+                                        .chain(
+                                            EditBehavior::new(self.context.input_sink_mut(mode))
+                                                .delete_forwards_on(Key::Delete)
+                                                .delete_backwards_on(Key::Backspace)
+                                                .left_on(Key::Left)
+                                                .right_on(Key::Right),
+                                        )
+                                        .chain(
+                                            ScrollBehavior::new(self.context.input_sink_mut(mode))
+                                                .backwards_on(Key::Up)
+                                                .forwards_on(Key::Down),
+                                        )
+                                        .chain(SearchPrompt::new(&mut self.context))
+                                        .finish();
+                                }
                             }
                         }
                     }
@@ -1,5 +1,5 @@
 use crate::provider::datetime::days_of_month;
-use chrono::{Datelike, Local, Month, NaiveDate};
+use chrono::{Datelike, Duration, Local, Month, NaiveDate};
 use num_traits::FromPrimitive;
 use std::fmt::Display;
 use std::fmt::Write;
@@ -13,6 +13,7 @@ pub struct DayCell<'a> {
     day_num: u8,
     selected: bool,
     is_today: bool,
+    has_conflicts: bool,
     theme: &'a Theme,
 }
 
@@ -25,6 +26,7 @@ impl<'a> DayCell<'a> {
             day_num,
             selected: false,
             is_today: false,
+            has_conflicts: false,
             theme,
         }
     }
@@ -46,12 +48,24 @@ impl<'a> DayCell<'a> {
         self.set_today(is_today);
         self
     }
+
+    fn set_conflicts(&mut self, has_conflicts: bool) {
+        self.has_conflicts = has_conflicts;
+    }
+
+    fn conflicts(mut self, has_conflicts: bool) -> Self {
+        self.set_conflicts(has_conflicts);
+        self
+    }
 }
 
 impl Display for DayCell<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Today's marker takes precedence over the one of conflicts
         let arg_today = if self.is_today {
             self.theme.today_day_char.unwrap_or(' ')
+        } else if self.has_conflicts {
+            self.theme.conflict_day_char.unwrap_or(' ')
         } else {
             ' '
         };
@@ -151,16 +165,34 @@ impl Widget for MonthPane<'_> {
         let is_selected_month = (self.context.cursor().month() == self.month.number_from_month())
             && (self.context.cursor().year() == self.year);
 
+        let first_day =
+            NaiveDate::from_ymd_opt(self.year, self.month.number_from_month(), 1).unwrap();
+        let conflict_days = self.context.conflict_days(
+            first_day,
+            first_day + Duration::days(self.num_days as i64 - 1),
+        );
+
         for (idx, cell) in (1..=self.num_days).map(|idx| (idx, DayCell::new(idx, &theme))) {
             let is_today = is_current_month && (idx as u32 == self.context.now().day());
             let is_selected = is_selected_month && (idx as u32 == self.context.cursor().day());
+            let has_conflicts = first_day
+                .with_day(idx as u32)
+                .map_or(false, |day| conflict_days.contains(&day));
 
-            let saved_style = if is_today || is_selected {
+            let saved_style = if is_today || is_selected || has_conflicts {
                 Some(cursor.get_style_modifier())
             } else {
                 None
             };
 
+            if has_conflicts {
+                cursor.apply_style_modifier(
+                    theme
+                        .conflict_day_style
+                        .format(theme.conflict_day_text_style),
+                );
+            }
+
             if is_today {
                 cursor
                     .apply_style_modifier(theme.today_day_style.format(theme.today_day_text_style));
@@ -171,7 +203,14 @@ impl Widget for MonthPane<'_> {
                     .apply_style_modifier(theme.focus_day_style.format(theme.focus_day_text_style));
             }
 
-            write!(&mut cursor, "{}", cell.select(is_selected).today(is_today)).unwrap();
+            write!(
+                &mut cursor,
+                "{}",
+                cell.select(is_selected)
+                    .today(is_today)
+                    .conflicts(has_conflicts)
+            )
+            .unwrap();
 
             if let Some(style) = saved_style {
                 cursor.set_style_modifier(style);
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use std::result::Result;
 use unsegen::input::*;
 
@@ -11,6 +12,10 @@ use nom::{
 
 use super::context::{Context, Mode};
 use crate::config::Config;
+use crate::exchange;
+use crate::notification::control::{self, Reply, Request};
+use crate::provider::{parse_duration, EventFilter, Eventlike, ParticipationStatus};
+use crate::report::Grouping;
 
 pub struct CommandParser<'a> {
     context: &'a mut Context,
@@ -120,7 +125,144 @@ pub enum Action {
     Repeatable(fn(&mut Context, u32) -> ActionResult),
 }
 
+/// Respond to the invitation to the selected event. The reply for the organizer is written
+/// into the temporary directory.
+fn respond(context: &mut Context, status: ParticipationStatus) -> ActionResult {
+    let fail = |message: String| Error::new(message, ErrorKind::Fail);
+
+    let uid = context
+        .selected_event()
+        .map(|occurrence| occurrence.event.uid().to_owned())
+        .ok_or_else(|| fail("No event selected".to_owned()))?;
+    let addresses = context.own_addresses().to_vec();
+
+    let reply = exchange::reply(context.agenda_mut(), &uid, &addresses, &status)
+        .map_err(|e| fail(e.to_string()))?;
+
+    let path = std::env::temp_dir().join(format!("{}-reply.ics", uid));
+    std::fs::write(&path, reply).map_err(|e| fail(e.to_string()))?;
+
+    context.status_message = Some(format!("Reply written to '{}'", path.display()));
+    Ok(())
+}
+
+/// Send a request about the selected occurrence to jk-notify.
+fn control_notify(
+    context: &mut Context,
+    request: impl FnOnce(String, DateTime<Utc>) -> Request,
+) -> ActionResult {
+    let fail = |message: String| Error::new(message, ErrorKind::Fail);
+
+    let request = context
+        .selected_event()
+        .map(|occurrence| request(occurrence.event.uid().to_owned(), occurrence.begin()))
+        .ok_or_else(|| fail("No event selected".to_owned()))?;
+    let path = control::default_path().ok_or_else(|| fail("No runtime directory".to_owned()))?;
+
+    match control::send(&path, &request) {
+        Ok(Reply::Ok { message }) => {
+            context.status_message = Some(message);
+            Ok(())
+        }
+        Ok(Reply::Error { message }) => Err(fail(message)),
+        Ok(Reply::Scheduled { .. }) => Err(fail("Unexpected reply of jk-notify".to_owned())),
+        Err(e) => Err(fail(format!("Could not reach jk-notify: {}", e))),
+    }
+}
+
 const COMMANDS: &[(&'static str, Action)] = &[
+    (
+        "snooze",
+        Action::_Arg(|c, snooze| {
+            control_notify(c, |uid, begin| Request::Snooze {
+                uid,
+                begin: Some(begin),
+                snooze,
+            })
+        }),
+    ),
+    (
+        "dismiss",
+        Action::_NoArg(|c| {
+            control_notify(c, |uid, begin| Request::Dismiss {
+                uid,
+                begin: Some(begin),
+            })
+        }),
+    ),
+    (
+        "accept",
+        Action::_NoArg(|c| respond(c, ParticipationStatus::Accepted)),
+    ),
+    (
+        "decline",
+        Action::_NoArg(|c| respond(c, ParticipationStatus::Declined)),
+    ),
+    (
+        "tentative",
+        Action::_NoArg(|c| respond(c, ParticipationStatus::Tentative)),
+    ),
+    (
+        "filter",
+        Action::_Arg(|c, query| {
+            let filter = EventFilter::parse(&query, c.saved_filters())
+                .map_err(|e| Error::new(e.to_string(), ErrorKind::Fail))?;
+            c.filter = Some(filter);
+            c.eventlist_index = 0;
+            c.status_message = Some(format!("Filter: {}", query));
+            Ok(())
+        }),
+    ),
+    (
+        "nofilter",
+        Action::_NoArg(|c| {
+            c.filter = None;
+            c.eventlist_index = 0;
+            Ok(())
+        }),
+    ),
+    (
+        "slots",
+        Action::_Arg(|c, duration| {
+            let fail = |e: crate::provider::Error| Error::new(e.to_string(), ErrorKind::Fail);
+            let duration = parse_duration(&duration).map_err(fail)?;
+            c.find_slots(duration).map_err(fail)
+        }),
+    ),
+    (
+        "slot",
+        Action::_Arg(|c, number| {
+            let chosen = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1))
+                .map_or(false, |index| c.choose_slot(index));
+            if chosen {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    format!("No slot number {}", number),
+                    ErrorKind::Fail,
+                ))
+            }
+        }),
+    ),
+    (
+        "stats",
+        Action::_Arg(|c, grouping| {
+            let fail = |e: crate::provider::Error| Error::new(e.to_string(), ErrorKind::Fail);
+            let grouping = grouping.trim().parse::<Grouping>().map_err(fail)?;
+            c.show_stats(grouping).map_err(fail)
+        }),
+    ),
+    (
+        "declined",
+        Action::_NoArg(|c| {
+            c.declined_events = c.declined_events.next();
+            c.eventlist_index = 0;
+            Ok(())
+        }),
+    ),
     (
         "gy",
         Action::Repeatable(|c, p| {
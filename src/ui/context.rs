@@ -1,7 +1,18 @@
 use chrono::prelude::*;
-use std::collections::BTreeMap;
+use chrono::Duration;
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use crate::agenda::Agenda;
+use crate::config::{Config, DeclinedEvents, SlotsConfig};
+use crate::conflicts::Conflict;
+use crate::exchange::freebusy::{FreeBusy, Period};
+use crate::provider::{
+    local_midnight, month_of, EventFilter, Eventlike, NewEvent, Occurrence, Result,
+};
+use crate::report::{Grouping, Report};
+use crate::search::SearchQuery;
+use crate::slots::{free_slots, slot_event, SlotOptions};
 
 use unsegen::base::style::*;
 use unsegen::widget::builtin::PromptLine;
@@ -11,6 +22,7 @@ pub enum Mode {
     Normal,
     Insert,
     Command,
+    Search,
 }
 
 #[derive(Clone, Debug)]
@@ -23,6 +35,9 @@ pub struct Theme {
     pub today_day_style: StyleModifier,
     pub today_day_text_style: TextFormatModifier,
     pub today_day_char: Option<char>,
+    pub conflict_day_style: StyleModifier,
+    pub conflict_day_text_style: TextFormatModifier,
+    pub conflict_day_char: Option<char>,
     pub month_header_style: StyleModifier,
     pub month_header_text_style: TextFormatModifier,
 }
@@ -38,6 +53,9 @@ impl Default for Theme {
             today_day_style: StyleModifier::default().invert(true),
             today_day_text_style: TextFormatModifier::default().italic(true),
             today_day_char: Some('*'),
+            conflict_day_style: StyleModifier::default().fg_color(Color::LightRed),
+            conflict_day_text_style: TextFormatModifier::default().bold(true),
+            conflict_day_char: Some('!'),
             month_header_style: StyleModifier::default().fg_color(Color::Yellow),
             month_header_text_style: TextFormatModifier::default(),
         }
@@ -50,21 +68,52 @@ pub struct Context {
     pub cursor: DateTime<Local>,
     pub eventlist_index: usize,
     pub last_error_message: Option<String>,
+    pub status_message: Option<String>,
+    /// The last search, repeated with `n` and `N`
+    pub search: Option<SearchQuery>,
+    /// Only events matching this filter are shown
+    pub filter: Option<EventFilter>,
+    /// Free slots found for a meeting of `slot_duration`
+    pub slots: Vec<Period>,
+    pub slot_duration: Duration,
+    /// Index of the slot new events are inserted into
+    pub chosen_slot: Option<usize>,
+    /// Insert line of an event conflicting with others, added once confirmed with Enter
+    pub unconfirmed_insert: Option<String>,
+    /// Statistics shown instead of the event list until closed with Esc
+    pub stats: Option<Report>,
+    pub declined_events: DeclinedEvents,
+    own_addresses: Vec<String>,
+    saved_filters: HashMap<String, String>,
+    slots_config: SlotsConfig,
     input_sinks: BTreeMap<Mode, PromptLine>,
     agenda: Agenda,
     now: DateTime<Local>,
 }
 
 impl Context {
-    pub fn new(calendar: Agenda) -> Self {
+    pub fn new(calendar: Agenda, config: &Config) -> Self {
         Context {
             mode: Mode::Normal,
             theme: Theme::default(),
             cursor: Local::now(),
             last_error_message: None,
+            status_message: None,
+            search: None,
+            filter: None,
+            slots: Vec::new(),
+            slot_duration: Duration::zero(),
+            chosen_slot: None,
+            unconfirmed_insert: None,
+            stats: None,
+            declined_events: config.declined_events,
+            own_addresses: config.addresses.clone(),
+            saved_filters: config.filters.clone(),
+            slots_config: config.slots.clone(),
             input_sinks: BTreeMap::from([
                 (Mode::Insert, PromptLine::with_prompt("> ".to_owned())),
                 (Mode::Command, PromptLine::with_prompt(":".to_owned())),
+                (Mode::Search, PromptLine::with_prompt("/".to_owned())),
             ]),
             eventlist_index: 0,
             agenda: calendar,
@@ -87,6 +136,217 @@ impl Context {
         &mut self.agenda
     }
 
+    pub fn own_addresses(&self) -> &[String] {
+        &self.own_addresses
+    }
+
+    pub fn saved_filters(&self) -> &HashMap<String, String> {
+        &self.saved_filters
+    }
+
+    /// Whether the user declined the event.
+    pub fn is_declined(&self, event: &dyn Eventlike) -> bool {
+        event.is_declined_by(&self.own_addresses)
+    }
+
+    /// All events of `date` that are to be shown, i.e., those matching the filter and without
+    /// declined events if these are hidden, sorted by their begin.
+    pub fn visible_events_of_day(&self, date: &NaiveDate) -> Vec<Occurrence<'_>> {
+        self.agenda
+            .events_of_day(date)
+            .filter(|occurrence| {
+                self.declined_events != DeclinedEvents::Hide || !self.is_declined(occurrence.event)
+            })
+            .filter(|occurrence| {
+                self.filter
+                    .as_ref()
+                    .map_or(true, |filter| filter.matches(occurrence))
+            })
+            .collect()
+    }
+
+    /// Conflicts between events on the local day `date`, whether they match the filter or not.
+    pub fn conflicts_of_day(&self, date: &NaiveDate) -> Vec<Conflict<'_>> {
+        let begin = local_midnight(*date);
+        let end = local_midnight(date.succ_opt().unwrap());
+
+        self.agenda
+            .conflicts_in(begin..end, &self.own_addresses)
+            .collect()
+    }
+
+    /// The local days from `first` to `last` with conflicting events.
+    pub fn conflict_days(&self, first: NaiveDate, last: NaiveDate) -> BTreeSet<NaiveDate> {
+        let begin = local_midnight(first);
+        let end = local_midnight(last.succ_opt().unwrap());
+
+        self.agenda
+            .conflicts_in(begin..end, &self.own_addresses)
+            .flat_map(|conflict| conflict.days())
+            .filter(|day| (first..=last).contains(day))
+            .collect()
+    }
+
+    /// The events that `new_event` would conflict with if it was added.
+    pub fn conflicts_with(&self, new_event: &NewEvent<Tz>) -> Vec<Occurrence<'_>> {
+        let span = new_event.span().with_tz(&Utc);
+        if span.begin() >= span.end() {
+            return Vec::new();
+        }
+
+        self.agenda
+            .busy_in(
+                span.begin().naive_utc()..span.end().naive_utc(),
+                &self.own_addresses,
+            )
+            .filter(|occurrence| occurrence.begin() < occurrence.end())
+            .collect()
+    }
+
+    /// The event selected in the event list of the cursor's day.
+    pub fn selected_event(&self) -> Option<Occurrence<'_>> {
+        self.visible_events_of_day(&self.cursor.date_naive())
+            .into_iter()
+            .nth(self.eventlist_index)
+    }
+
+    /// Move the cursor to `date`, keeping its time of day, and select the event with `uid` on
+    /// it, if any.
+    pub fn focus(&mut self, date: NaiveDate, uid: Option<&str>) {
+        if let Some(cursor) = Local
+            .from_local_datetime(&date.and_time(self.cursor.time()))
+            .earliest()
+        {
+            self.cursor = cursor;
+        }
+
+        if let Some(uid) = uid {
+            self.select_event(uid);
+        } else {
+            self.eventlist_index = 0;
+        }
+    }
+
+    /// Select the event with `uid` in the event list of the cursor's day.
+    fn select_event(&mut self, uid: &str) {
+        let index = self
+            .visible_events_of_day(&self.cursor.date_naive())
+            .iter()
+            .position(|occurrence| occurrence.event.uid() == uid);
+        self.eventlist_index = index.unwrap_or(0);
+    }
+
+    /// Move the cursor to the next occurrence after it matching the last search, or to the
+    /// previous one if not `forwards`.
+    pub fn jump_to_search_hit(&mut self, forwards: bool) {
+        let query = match &self.search {
+            Some(query) => query,
+            None => {
+                self.last_error_message = Some("No previous search".to_owned());
+                return;
+            }
+        };
+        let pattern = query.to_string();
+        let cursor = self.cursor.with_timezone(&Utc);
+
+        let hit = if forwards {
+            self.agenda
+                .search(query, cursor.naive_utc()..)
+                .find(|occurrence| occurrence.begin() > cursor)
+        } else {
+            self.agenda
+                .search(query, ..cursor.naive_utc())
+                .filter(|occurrence| occurrence.begin() < cursor)
+                .last()
+        }
+        .map(|occurrence| (occurrence.begin(), occurrence.event.uid().to_owned()));
+
+        match hit {
+            Some((begin, uid)) => {
+                self.cursor = begin.with_timezone(&Local);
+                self.select_event(&uid);
+                self.status_message = Some(format!("/{}", pattern));
+            }
+            None => {
+                self.last_error_message = Some(format!("Pattern not found: {}", pattern));
+            }
+        }
+    }
+
+    /// Find free slots for a meeting of `duration` in the week starting on the cursor's day
+    /// and choose the first one.
+    pub fn find_slots(&mut self, duration: Duration) -> Result<()> {
+        let options = SlotOptions::from_config(&self.slots_config, duration)?;
+        let from = self.cursor.date_naive();
+        let start = Utc.from_utc_datetime(&local_midnight(from)).max(Utc::now());
+        let end = Utc.from_utc_datetime(&local_midnight(from + Duration::days(7)));
+
+        let freebusy = FreeBusy::compute(&self.agenda, &[], start, end, &self.own_addresses)?;
+        self.slots = free_slots(&freebusy, &options);
+        self.slot_duration = duration;
+        self.chosen_slot = None;
+
+        if self.slots.is_empty() {
+            self.last_error_message = Some("No free slots".to_owned());
+        } else {
+            self.choose_slot(0);
+        }
+        Ok(())
+    }
+
+    /// Choose the slot with `index` for new events and move the cursor to it.
+    pub fn choose_slot(&mut self, index: usize) -> bool {
+        let slot = match self.slots.get(index) {
+            Some(slot) => *slot,
+            None => return false,
+        };
+
+        self.chosen_slot = Some(index);
+        self.cursor = slot.start.with_timezone(&Local);
+        self.eventlist_index = 0;
+        self.status_message = Some(format!(
+            "Slot {}/{}: {} (insert with 'i', choose another with ':slot N')",
+            index + 1,
+            self.slots.len(),
+            slot
+        ));
+        true
+    }
+
+    /// A new event in the chosen slot, if any.
+    pub fn slot_event(&self) -> Option<NewEvent<Tz>> {
+        self.chosen_slot
+            .and_then(|index| self.slots.get(index))
+            .map(|slot| slot_event(slot, self.slot_duration))
+    }
+
+    /// Forget the found slots, e.g., after an event was inserted into the chosen one, which is
+    /// no longer free then.
+    pub fn clear_slots(&mut self) {
+        self.slots.clear();
+        self.chosen_slot = None;
+    }
+
+    /// Show the time spent in the events of the cursor's month by `grouping`.
+    pub fn show_stats(&mut self, grouping: Grouping) -> Result<()> {
+        let (first, last) = month_of(self.cursor.date_naive());
+        self.stats = Some(Report::compute(
+            &self.agenda,
+            &[],
+            grouping,
+            first,
+            last,
+            &self.own_addresses,
+        )?);
+        Ok(())
+    }
+
+    /// Forget the messages of previous commands.
+    pub fn clear_messages(&mut self) {
+        self.last_error_message = None;
+        self.status_message = None;
+    }
+
     pub fn now(&self) -> &DateTime<Local> {
         &self.now
     }
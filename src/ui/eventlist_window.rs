@@ -4,9 +4,15 @@ use unsegen::base::*;
 use unsegen::input::Scrollable;
 use unsegen::widget::*;
 
+use crate::config::DeclinedEvents;
 use crate::provider::Occurrence;
 use crate::ui::Context;
 
+/// Terminals have no common strike-through attribute, so a combining overlay is used instead.
+fn strike_through(text: &str) -> String {
+    text.chars().flat_map(|c| [c, '\u{0336}']).collect()
+}
+
 enum Entry<'a> {
     Event(Occurrence<'a>),
     Time(DateTime<Local>),
@@ -25,7 +31,7 @@ impl Entry<'_> {
 impl Display for Entry<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Event(Occurrence { span, event }) => {
+            Self::Event(Occurrence { span, event, .. }) => {
                 let local_span = span.clone().with_tz(&Local {});
                 let time = if span.is_allday() {
                     "Allday".to_owned()
@@ -36,7 +42,11 @@ impl Display for Entry<'_> {
                         local_span.end().time().format("%H:%M")
                     )
                 };
-                write!(f, "{}: {}", time, event.summary())
+                if event.is_cancelled() {
+                    write!(f, "{}: {}", time, strike_through(event.summary()))
+                } else {
+                    write!(f, "{}: {}", time, event.summary())
+                }
             }
             Self::Time(dt) => f.pad(&format!("[{}]", dt.time().format("%H:%M"))),
             Self::Cursor(dt) => write!(f, " * {}", dt.time().format("%H:%M")),
@@ -63,10 +73,14 @@ impl Widget for EventWindow<'_> {
     }
 
     fn draw(&self, mut window: unsegen::base::Window, _hints: RenderingHints) {
+        let conflicts = self
+            .context
+            .conflicts_of_day(&self.context.cursor().date_naive());
+
         let mut events = self
             .context
-            .agenda()
-            .events_of_day(&self.context.cursor().date_naive())
+            .visible_events_of_day(&self.context.cursor().date_naive())
+            .into_iter()
             .map(Entry::Event)
             .chain([Entry::Cursor(self.context.cursor().clone())])
             .collect::<Vec<Entry>>();
@@ -76,7 +90,8 @@ impl Widget for EventWindow<'_> {
             events.push(Entry::Time(self.context.now().clone()))
         }
 
-        events.sort_unstable_by_key(|entry| entry.datetime());
+        // Stable, so that the order matches Context::selected_event
+        events.sort_by_key(|entry| entry.datetime());
 
         let width = window.get_width().raw_value() as usize;
 
@@ -86,20 +101,56 @@ impl Widget for EventWindow<'_> {
         let mut idx: usize = 0;
         for ev in events {
             match ev {
-                ev @ Entry::Event(..) => {
+                Entry::Event(occurrence) => {
                     let saved_style = cursor.get_style_modifier();
+                    let selected = idx == self.context.eventlist_index;
+                    let own = occurrence.event.attendee_for(self.context.own_addresses());
+                    let conflicting = conflicts
+                        .iter()
+                        .any(|conflict| conflict.involves(&occurrence));
 
-                    if idx == self.context.eventlist_index {
+                    if selected {
                         cursor.apply_style_modifier(StyleModifier::new().invert(true));
                     }
 
-                    if let Err(err) = write!(&mut cursor, "{}", ev) {
+                    if self.context.declined_events == DeclinedEvents::Dim
+                        && self.context.is_declined(occurrence.event)
+                    {
+                        cursor
+                            .apply_style_modifier(StyleModifier::new().fg_color(Color::LightBlack));
+                    }
+
+                    if conflicting {
+                        cursor.apply_style_modifier(StyleModifier::new().fg_color(Color::LightRed));
+                        let _ = write!(&mut cursor, "! ");
+                    }
+
+                    if let Some(own) = &own {
+                        let _ = write!(&mut cursor, "{} ", own.status.symbol());
+                    }
+
+                    let attendees = occurrence.event.attendees();
+                    let organizer = occurrence.event.organizer();
+
+                    if let Err(err) = write!(&mut cursor, "{}", Entry::Event(occurrence)) {
                         log::warn!("Error while writing event: {}", err);
                     }
 
                     cursor.fill_and_wrap_line();
-
                     cursor.set_style_modifier(saved_style);
+
+                    // Details of the selected event
+                    if selected {
+                        if let Some(organizer) = organizer {
+                            let _ = write!(&mut cursor, "    Organizer: {}", organizer);
+                            cursor.fill_and_wrap_line();
+                        }
+                        for attendee in attendees.iter() {
+                            let _ = write!(&mut cursor, "    {}", attendee);
+                            cursor.fill_and_wrap_line();
+                        }
+                    }
+
                     idx += 1;
                 }
                 time @ Entry::Time(_) => {
@@ -16,7 +16,7 @@ use unsegen::input::*;
 use super::command::ActionResult;
 use super::context::Context;
 use crate::config::Config;
-use crate::provider::NewEvent;
+use crate::provider::{parse_duration, Alarm, NewEvent};
 
 type InsertAction = fn(&mut NewEvent<Tz>, &str) -> ActionResult;
 
@@ -42,6 +42,11 @@ const INSERT_ACTIONS: phf::Map<&'static str, InsertAction> = phf_map! {
         // b.set_duration(duration);
         Ok(())
     },
+    "alarm" => |b, v| {
+        let before = parse_duration(v).or_else(|_| Err(nom::error::ParseError::from_error_kind(v.to_string(), nom::error::ErrorKind::Tag)))?;
+        b.add_alarm(Alarm::before_start(before));
+        Ok(())
+    },
     "end" => |b, v| {
         let dt = NaiveDateTime::parse_from_str(v, DATETIME_FORMAT).or_else(|_| Err(nom::error::ParseError::from_error_kind(v.to_string(), nom::error::ErrorKind::Tag)))?;
         b.set_end(dt);
@@ -86,7 +91,9 @@ impl<'a> InsertParser<'a> {
         }
     }
 
-    fn parse_line(&mut self, line: &str) -> Result<(), Error<String>> {
+    /// Parse `line` and add the event it describes. An event conflicting with others is only
+    /// added if `confirmed`, otherwise the line is kept until confirmed with another Enter.
+    fn parse_line(&mut self, line: &str, confirmed: bool) -> Result<(), Error<String>> {
         let (rest, found_key_values) = many1(Self::parse_key_value)(line)
             .or_else(|_| Err(ParseError::from_error_kind(line.into(), ErrorKind::Many1)))?;
 
@@ -102,21 +109,44 @@ impl<'a> InsertParser<'a> {
                 ))
             })?;
 
-        if let Some(calendar) = self.context.agenda_mut().calendar_by_name_mut(name) {
-            calendar
-                .add_event(self.new_event.take().unwrap())
-                .or_else(|e| {
-                    Err(Error::from_error_kind(
-                        format!("Could not add event: {}", e),
-                        ErrorKind::Fail,
-                    ))
-                })
-        } else {
-            Err(ParseError::from_error_kind(
-                format!("Calendar '{}' not found", name),
-                ErrorKind::Tag,
-            ))
+        let not_found = || -> Error<String> {
+            ParseError::from_error_kind(format!("Calendar '{}' not found", name), ErrorKind::Tag)
+        };
+        if self.context.agenda().calendar_by_name(name).is_none() {
+            return Err(not_found());
         }
+
+        let new_event = self.new_event.take().unwrap();
+        // Checked before the event is written, afterwards it would conflict with itself
+        let conflicts = self
+            .context
+            .conflicts_with(&new_event)
+            .iter()
+            .map(|occurrence| format!("'{}'", occurrence.event.summary()))
+            .collect::<Vec<_>>();
+
+        if !conflicts.is_empty() && !confirmed {
+            self.context.unconfirmed_insert = Some(line.to_owned());
+            self.context.status_message = Some(format!(
+                "Conflicts with {}, press Enter again to add the event anyway",
+                conflicts.join(", ")
+            ));
+            return Ok(());
+        }
+
+        let calendar = self
+            .context
+            .agenda_mut()
+            .calendar_by_name_mut(name)
+            .ok_or_else(not_found)?;
+        calendar.add_event(new_event).or_else(|e| {
+            Err(Error::from_error_kind(
+                format!("Could not add event: {}", e),
+                ErrorKind::Fail,
+            ))
+        })?;
+        self.context.clear_slots();
+        Ok(())
     }
 }
 
@@ -130,8 +160,13 @@ impl Behavior for InsertParser<'_> {
                         .input_sink_mut(super::Mode::Insert)
                         .finish_line()
                         .to_owned();
+                    // An empty line confirms the insert of a conflicting event
+                    let (line, confirmed) = match self.context.unconfirmed_insert.take() {
+                        Some(unconfirmed) if line.trim().is_empty() => (unconfirmed, true),
+                        _ => (line, false),
+                    };
 
-                    let res = self.parse_line(&line);
+                    let res = self.parse_line(&line, confirmed);
                     if let Err(e) = res {
                         self.context.last_error_message = Some(format!("{}", e));
                         log::error!("{}", e);
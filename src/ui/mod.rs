@@ -4,6 +4,8 @@ pub mod command;
 pub mod context;
 pub mod eventlist_window;
 pub mod insert;
+pub mod search;
+pub mod stats_window;
 
 pub use app::*;
 pub use calendar_window::*;
@@ -11,3 +13,5 @@ pub use command::*;
 pub use context::*;
 pub use eventlist_window::*;
 pub use insert::*;
+pub use search::*;
+pub use stats_window::*;
@@ -0,0 +1,41 @@
+use unsegen::input::*;
+
+use super::context::{Context, Mode};
+use crate::search::SearchQuery;
+
+/// Starts a search with the input of the `/` prompt and jumps to the first hit.
+pub struct SearchPrompt<'a> {
+    context: &'a mut Context,
+}
+
+impl<'a> SearchPrompt<'a> {
+    pub fn new(context: &'a mut Context) -> Self {
+        SearchPrompt { context }
+    }
+}
+
+impl Behavior for SearchPrompt<'_> {
+    fn input(self, input: Input) -> Option<Input> {
+        if let Event::Key(Key::Char('\n')) = input.event {
+            let line = self
+                .context
+                .input_sink_mut(Mode::Search)
+                .finish_line()
+                .to_owned();
+
+            if !line.is_empty() {
+                match SearchQuery::parse(&line) {
+                    Ok(query) => {
+                        self.context.search = Some(query);
+                        self.context.jump_to_search_hit(true);
+                    }
+                    Err(e) => self.context.last_error_message = Some(e.to_string()),
+                }
+            }
+            self.context.mode = Mode::Normal;
+            None
+        } else {
+            Some(input)
+        }
+    }
+}
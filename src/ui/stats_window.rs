@@ -0,0 +1,79 @@
+use std::fmt::Write;
+use unsegen::base::*;
+use unsegen::widget::*;
+
+use crate::report::Report;
+use crate::ui::{Context, EventWindow};
+
+/// Popup with the time spent in events, see `Context::show_stats`.
+pub struct StatsWindow<'a> {
+    report: &'a Report,
+    title_style: StyleModifier,
+}
+
+impl<'a> StatsWindow<'a> {
+    pub fn new(report: &'a Report, context: &'a Context) -> Self {
+        StatsWindow {
+            report,
+            title_style: context.theme.month_header_style,
+        }
+    }
+}
+
+impl Widget for StatsWindow<'_> {
+    fn space_demand(&self) -> Demand2D {
+        Demand2D {
+            width: ColDemand::at_least(10),
+            height: RowDemand::at_least(10),
+        }
+    }
+
+    fn draw(&self, mut window: Window, _hints: RenderingHints) {
+        let mut cursor = Cursor::new(&mut window);
+        let mut lines = self.report.to_table();
+        lines.push_str("(Esc to close)");
+
+        for (idx, line) in lines.lines().enumerate() {
+            let saved_style = cursor.get_style_modifier();
+            // Title line
+            if idx == 0 {
+                cursor.apply_style_modifier(self.title_style);
+            }
+            let _ = write!(&mut cursor, "{}", line);
+            cursor.fill_and_wrap_line();
+            cursor.set_style_modifier(saved_style);
+        }
+    }
+}
+
+/// The pane beside the calendar, which shows the events of the cursor's day unless statistics
+/// are shown.
+pub enum SidePane<'a> {
+    Events(EventWindow<'a>),
+    Stats(StatsWindow<'a>),
+}
+
+impl<'a> SidePane<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        match &context.stats {
+            Some(report) => SidePane::Stats(StatsWindow::new(report, context)),
+            None => SidePane::Events(EventWindow::new(context)),
+        }
+    }
+}
+
+impl Widget for SidePane<'_> {
+    fn space_demand(&self) -> Demand2D {
+        match self {
+            SidePane::Events(pane) => pane.space_demand(),
+            SidePane::Stats(pane) => pane.space_demand(),
+        }
+    }
+
+    fn draw(&self, window: Window, hints: RenderingHints) {
+        match self {
+            SidePane::Events(pane) => pane.draw(window, hints),
+            SidePane::Stats(pane) => pane.draw(window, hints),
+        }
+    }
+}
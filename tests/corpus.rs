@@ -0,0 +1,65 @@
+//! Round trips of the calendars in `tests/corpus` through the iCalendar parser and serializer.
+//! The files are in the form the serializer writes, so every one must be reproduced exactly.
+
+use jackal::provider::ical::{parse_calendars, ser};
+use std::fs;
+use std::path::Path;
+
+/// Maximum length of a content line in octets, excluding the line break
+const MAX_LINE_OCTETS: usize = 75;
+
+fn corpus() -> Vec<(String, String)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+
+    let mut files = fs::read_dir(dir)
+        .expect("corpus directory")
+        .map(|entry| entry.expect("corpus entry").path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ics"))
+        .map(|path| {
+            let text = fs::read_to_string(&path).expect("readable corpus file");
+            (path.display().to_string(), text)
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    assert!(!files.is_empty(), "Corpus is empty");
+    files
+}
+
+#[test]
+fn corpus_round_trips() {
+    for (path, text) in corpus() {
+        let calendars = parse_calendars(&text).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        let serialized = calendars
+            .iter()
+            .map(|calendar| ser::to_string(calendar).unwrap_or_else(|e| panic!("{}: {}", path, e)))
+            .collect::<String>();
+
+        assert_eq!(serialized, text, "{} changed in its round trip", path);
+    }
+}
+
+#[test]
+fn corpus_lines_are_folded_with_crlf() {
+    for (path, text) in corpus() {
+        let calendars = parse_calendars(&text).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        let serialized = ser::to_string(&calendars[0]).unwrap();
+
+        assert!(serialized.ends_with("\r\n"), "{}: missing final CRLF", path);
+        for line in serialized.split_terminator("\r\n") {
+            assert!(
+                !line.contains('\n'),
+                "{}: bare line feed in {:?}",
+                path,
+                line
+            );
+            assert!(
+                line.len() <= MAX_LINE_OCTETS,
+                "{}: line of {} octets: {:?}",
+                path,
+                line.len(),
+                line
+            );
+        }
+    }
+}
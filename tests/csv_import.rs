@@ -0,0 +1,232 @@
+//! Imports of CSV files into calendars: rows are mapped to events, written to the calendar
+//! directory and read back from there as any other event.
+
+use chrono::{DateTime, TimeZone, Utc};
+use jackal::agenda::Agenda;
+use jackal::config::{CalendarConfig, CollectionConfig, Config};
+use jackal::exchange::csv::{import, ColumnMapping, CsvOptions};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// A configuration with the empty calendar "Personal" in a fresh directory named after `test`.
+fn config(test: &str) -> (Config, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("jackal-csv-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("personal")).unwrap();
+
+    let mut config = Config::default();
+    config.collections = vec![CollectionConfig {
+        name: "local".to_owned(),
+        provider: "ical".to_owned(),
+        path: dir.clone(),
+        calendars: vec![CalendarConfig {
+            id: "personal".to_owned(),
+            name: "Personal".to_owned(),
+            notify: None,
+            quiet_hours: None,
+        }],
+    }];
+
+    (config, dir)
+}
+
+fn agenda(config: &Config) -> Agenda {
+    let (sender, _receiver) = mpsc::channel();
+    Agenda::from_config(config, &sender).unwrap()
+}
+
+/// Title, begin, end, location and description of an occurrence.
+type Summary = (
+    String,
+    DateTime<Utc>,
+    DateTime<Utc>,
+    Option<String>,
+    Option<String>,
+);
+
+fn summaries(agenda: &Agenda) -> Vec<Summary> {
+    let mut summaries = agenda
+        .events_in(..)
+        .map(|occurrence| {
+            let event = occurrence.event();
+            (
+                event.title().to_owned(),
+                occurrence.begin(),
+                occurrence.end(),
+                event.location().map(str::to_owned),
+                event.description().map(str::to_owned),
+            )
+        })
+        .collect::<Vec<_>>();
+    summaries.sort();
+    summaries
+}
+
+/// The text of the written event file with the summary `title`.
+fn event_file(calendar_dir: &Path, title: &str) -> String {
+    fs::read_dir(calendar_dir)
+        .unwrap()
+        .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+        .find(|text| text.contains(&format!("\r\nSUMMARY:{}\r\n", title)))
+        .unwrap_or_else(|| panic!("No event file for '{}'", title))
+}
+
+fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+}
+
+#[test]
+fn rows_are_mapped_by_header() {
+    let (config, dir) = config("header");
+    let mut agenda = agenda(&config);
+
+    let mut options = CsvOptions {
+        mapping: ColumnMapping::parse(
+            "title=Shift,start=From,end=Until,allday=All day,location=Where,description=Notes",
+        )
+        .unwrap(),
+        calendar: Some("Personal".to_owned()),
+        ..Default::default()
+    };
+    options.set_timezone("Europe/Berlin").unwrap();
+
+    let input = [
+        "Shift,From,Until,All day,Where,Notes",
+        "Early,2024-05-06 06:00,2024-05-06 14:00,,Ward 3,\"Handover at 13:30, room 2\"",
+        "On call,2024-05-07,2024-05-09,x,,\"Bring the \"\"red\"\" phone\"",
+        "Broken,not a date,,,,",
+        "Late,2024-05-06 14:00,2024-05-06 12:00,,,",
+    ]
+    .join("\n");
+
+    let result = import(&mut agenda, &options, &input).unwrap();
+    assert_eq!(result.imported, 2);
+    assert_eq!(
+        result.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+        vec![4, 5]
+    );
+
+    let imported = summaries(&agenda);
+    assert!(imported.contains(&(
+        "Early".to_owned(),
+        utc(2024, 5, 6, 4, 0),
+        utc(2024, 5, 6, 12, 0),
+        Some("Ward 3".to_owned()),
+        Some("Handover at 13:30, room 2".to_owned()),
+    )));
+
+    // The inclusive end date of the sheet becomes the exclusive DTEND of iCalendar
+    let on_call = event_file(&dir.join("personal"), "On call");
+    assert!(
+        on_call.contains("\r\nDTSTART;VALUE=DATE:20240507\r\n"),
+        "{}",
+        on_call
+    );
+    assert!(
+        on_call.contains("\r\nDTEND;VALUE=DATE:20240510\r\n"),
+        "{}",
+        on_call
+    );
+    assert!(
+        on_call.contains("\r\nDESCRIPTION:Bring the \"red\" phone\r\n"),
+        "{}",
+        on_call
+    );
+
+    // Reading the calendar from its directory yields the same events
+    assert_eq!(summaries(&self::agenda(&config)), imported);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rows_are_mapped_by_index_without_headers() {
+    let (config, dir) = config("index");
+    let mut agenda = agenda(&config);
+
+    let mut options = CsvOptions {
+        mapping: ColumnMapping::parse("title=1,start=2,duration=3,calendar=4").unwrap(),
+        has_headers: false,
+        ..Default::default()
+    };
+    options.set_delimiter(';').unwrap();
+    options.set_timezone("UTC").unwrap();
+
+    let input = [
+        "Standup;2024-05-06T09:00;15m;Personal",
+        "Review;2024-05-06T10:00;1h30m;Personal",
+        "Lunch;2024-05-06T12:00;1h;",
+        "Retro;2024-05-06T15:00;1h;Work",
+    ]
+    .join("\n");
+
+    let result = import(&mut agenda, &options, &input).unwrap();
+    assert_eq!(result.imported, 2);
+    assert_eq!(
+        result
+            .errors
+            .iter()
+            .map(|e| (e.line, e.error.message.clone().unwrap_or_default()))
+            .collect::<Vec<_>>(),
+        vec![
+            (3, "No calendar given for the event".to_owned()),
+            (4, "Calendar 'Work' not found".to_owned()),
+        ]
+    );
+
+    let imported = summaries(&agenda);
+    assert_eq!(
+        imported
+            .iter()
+            .map(|(title, begin, end, _, _)| (title.as_str(), *begin, *end))
+            .collect::<Vec<_>>(),
+        vec![
+            ("Review", utc(2024, 5, 6, 10, 0), utc(2024, 5, 6, 11, 30)),
+            ("Standup", utc(2024, 5, 6, 9, 0), utc(2024, 5, 6, 9, 15)),
+        ]
+    );
+    assert_eq!(summaries(&self::agenda(&config)), imported);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn invalid_mappings_are_rejected() {
+    assert!(ColumnMapping::parse("title").is_err());
+    assert!(ColumnMapping::parse("colour=1").is_err());
+    assert!(ColumnMapping::parse("title=0").is_err());
+
+    let (config, dir) = config("mapping");
+    let mut agenda = agenda(&config);
+
+    let options = CsvOptions {
+        mapping: ColumnMapping::parse("title=Name").unwrap(),
+        calendar: Some("Personal".to_owned()),
+        ..Default::default()
+    };
+    let error = import(
+        &mut agenda,
+        &options,
+        "Title,Start\nStandup,2024-05-06 09:00\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.message.as_deref(),
+        Some("Column 'Name' for title not found")
+    );
+
+    // The start is looked up by its field name unless it is mapped
+    let error = import(
+        &mut agenda,
+        &CsvOptions::default(),
+        "Title,Begin\nStandup,2024-05-06 09:00\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.message.as_deref(),
+        Some("No column is mapped to the start of events")
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
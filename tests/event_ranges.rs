@@ -0,0 +1,414 @@
+//! Property-based tests of the range semantics of event queries: an occurrence is part of a
+//! query if it shares any point in time with the queried range. All-day events are floating and
+//! cover the local days of their dates, so the tests run in a time zone other than UTC.
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use jackal::provider::ical::{parse_calendars, Event};
+use jackal::provider::{local_midnight, Eventlike, OccurrenceRule, TimeSpan};
+use rrule::RRuleSet;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Once;
+
+/// Local time zone of the tests. Its midnights differ from the UTC ones, and it changes to DST
+/// within the days the tests look at.
+const LOCAL_TZ: &str = "Europe/Berlin";
+
+/// Use `LOCAL_TZ` as local time zone. Called first by every test, so that all of them see it.
+fn set_local_tz() {
+    static SET: Once = Once::new();
+    SET.call_once(|| std::env::set_var("TZ", LOCAL_TZ));
+}
+
+/// Number of random cases per property.
+const CASES: usize = 2000;
+
+/// Small xorshift generator, so that failures are reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+
+    fn time(&mut self) -> DateTime<Utc> {
+        // Times within a few days on a coarse grid, so that bounds often coincide with begins
+        // and ends of occurrences
+        origin() + Duration::minutes(30 * self.below(48 * 6) as i64)
+    }
+
+    fn bound(&mut self) -> Bound<DateTime<Utc>> {
+        match self.below(3) {
+            0 => Bound::Unbounded,
+            1 => Bound::Included(self.time()),
+            _ => Bound::Excluded(self.time()),
+        }
+    }
+
+    fn span(&mut self) -> TimeSpan<Utc> {
+        let begin = self.time();
+        match self.below(4) {
+            0 => TimeSpan::from_start(begin),
+            1 => TimeSpan::from_start_and_duration(
+                begin,
+                Duration::minutes(30 * (1 + self.below(100)) as i64),
+            ),
+            2 => TimeSpan::from_start_and_end(
+                begin,
+                begin + Duration::minutes(30 * (1 + self.below(100)) as i64),
+            ),
+            _ => {
+                let date = begin.date_naive();
+                if self.below(2) == 0 {
+                    TimeSpan::allday(date, Utc)
+                } else {
+                    TimeSpan::allday_until(
+                        date,
+                        date + Duration::days(2 + self.below(3) as i64),
+                        Utc,
+                    )
+                }
+            }
+        }
+    }
+
+    fn rule(&mut self) -> OccurrenceRule<Utc> {
+        let span = self.span();
+        let rule = OccurrenceRule::Onetime(span.clone());
+        if self.below(2) == 0 {
+            return rule;
+        }
+
+        // Dates of all-day events recur as midnights in the time zone of the event
+        let (dtstart, freqs) = match &span {
+            TimeSpan::Allday(date, _, _) => {
+                (date.and_hms_opt(0, 0, 0).unwrap(), ["DAILY", "WEEKLY"])
+            }
+            _ => (span.begin().naive_utc(), ["HOURLY", "DAILY"]),
+        };
+        let rrule: RRuleSet = format!(
+            "DTSTART:{}\nRRULE:FREQ={};COUNT={}",
+            dtstart.format("%Y%m%dT%H%M%SZ"),
+            freqs[self.below(2) as usize],
+            1 + self.below(20)
+        )
+        .parse()
+        .unwrap();
+        rule.with_recurring(rrule)
+    }
+}
+
+fn origin() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 3, 24, 0, 0, 0).unwrap()
+}
+
+/// The beginning of the local day `days` after the one of `origin()`.
+fn local_day(days: i64) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&local_midnight(
+        origin().date_naive() + Duration::days(days),
+    ))
+}
+
+fn local_date(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
+
+/// Reference definition of the overlap: the earliest point of the span satisfying the lower
+/// bound also satisfies the upper bound. Spans do not contain their end, unless they have no
+/// duration.
+fn overlaps(
+    span: &TimeSpan<Utc>,
+    begin: &Bound<DateTime<Utc>>,
+    end: &Bound<DateTime<Utc>>,
+) -> bool {
+    let earliest = match begin {
+        Bound::Unbounded => span.begin(),
+        Bound::Included(begin) => span.begin().max(*begin),
+        Bound::Excluded(begin) => span.begin().max(*begin + Duration::seconds(1)),
+    };
+
+    let in_span = if span.begin() == span.end() {
+        earliest == span.begin()
+    } else {
+        earliest < span.end()
+    };
+    let in_range = match end {
+        Bound::Unbounded => true,
+        Bound::Included(end) => earliest <= *end,
+        Bound::Excluded(end) => earliest < *end,
+    };
+
+    in_span && in_range
+}
+
+fn begins(spans: impl Iterator<Item = TimeSpan<Utc>>) -> Vec<DateTime<Utc>> {
+    spans.map(|span| span.begin()).collect()
+}
+
+#[test]
+fn overlapping_occurrences_match_reference() {
+    set_local_tz();
+
+    let mut rng = Rng(0x5eed_1234_abcd_ef01);
+
+    for case in 0..CASES {
+        let rule = rng.rule();
+        let (begin, end) = (rng.bound(), rng.bound());
+
+        let expected = begins(rule.iter().filter(|span| overlaps(span, &begin, &end)));
+        let actual = begins(rule.iter_overlapping(begin, end));
+
+        assert_eq!(
+            actual,
+            expected,
+            "case {}: {:?}..{:?} of rule beginning at {}",
+            case,
+            begin,
+            end,
+            rule.first().begin()
+        );
+
+        let from = rng.time();
+        assert_eq!(
+            begins(rule.iter_from(from)),
+            begins(rule.iter().filter(|span| span.begin() >= from)),
+            "case {}: from {} of rule beginning at {}",
+            case,
+            from,
+            rule.first().begin()
+        );
+    }
+}
+
+#[test]
+fn span_overlap_matches_reference() {
+    set_local_tz();
+
+    let mut rng = Rng(0x0bad_cafe_f00d_0001);
+
+    for case in 0..CASES {
+        let span = rng.span();
+        let (begin, end) = (rng.bound(), rng.bound());
+
+        assert_eq!(
+            span.overlaps(&begin, &end),
+            overlaps(&span, &begin, &end),
+            "case {}: {:?}..{:?} of span {}-{}",
+            case,
+            begin,
+            end,
+            span.begin(),
+            span.end()
+        );
+    }
+}
+
+#[test]
+fn consecutive_days_cover_every_occurrence() {
+    set_local_tz();
+
+    let mut rng = Rng(0x1234_5678_9abc_def0);
+    let days = (0..8).map(local_day).collect::<Vec<_>>();
+    let end_of_days = local_day(8);
+
+    for case in 0..CASES {
+        let rule = rng.rule();
+
+        for span in rule.iter() {
+            // Every occurrence within the days shows up on each local day it touches
+            let touched = days
+                .iter()
+                .zip(days.iter().skip(1).chain(Some(&end_of_days)))
+                .filter(|(day, next)| {
+                    rule.iter_overlapping(Bound::Included(**day), Bound::Excluded(**next))
+                        .any(|other| other.begin() == span.begin())
+                })
+                .count() as i64;
+
+            let first = span.begin().max(days[0]);
+            let last = if span.begin() == span.end() {
+                span.begin()
+            } else {
+                span.end() - Duration::seconds(1)
+            }
+            .min(end_of_days - Duration::seconds(1));
+            let expected = if first > last {
+                0
+            } else {
+                (local_date(last) - local_date(first)).num_days() + 1
+            };
+
+            assert_eq!(
+                touched,
+                expected,
+                "case {}: occurrence {}-{}",
+                case,
+                span.begin(),
+                span.end()
+            );
+        }
+    }
+}
+
+#[test]
+fn multiday_allday_events_show_on_every_day() {
+    set_local_tz();
+
+    let begin = NaiveDate::from_ymd_opt(2023, 3, 24).unwrap();
+    let rule = OccurrenceRule::Onetime(TimeSpan::allday_until(
+        begin,
+        begin + Duration::days(3),
+        Utc,
+    ));
+
+    for day in 0..5 {
+        let shown = rule
+            .iter_overlapping(
+                Bound::Included(local_day(day)),
+                Bound::Excluded(local_day(day + 1)),
+            )
+            .count();
+
+        // DTEND of all-day events is exclusive
+        assert_eq!(shown, if day < 3 { 1 } else { 0 }, "day {}", day);
+    }
+}
+
+#[test]
+fn allday_events_cover_their_local_days_only() {
+    set_local_tz();
+
+    // Dates carry no time zone, so the event gets UTC, while its days begin at local midnight
+    let text = [
+        "BEGIN:VCALENDAR",
+        "PRODID:-//Example//NONSGML Test//EN",
+        "VERSION:2.0",
+        "BEGIN:VEVENT",
+        "UID:holidays",
+        "DTSTAMP:20230301T000000Z",
+        "DTSTART;VALUE=DATE:20230325",
+        "DTEND;VALUE=DATE:20230326",
+        "RRULE:FREQ=DAILY;COUNT=3",
+        "SUMMARY:Holidays",
+        "END:VEVENT",
+        "END:VCALENDAR",
+        "",
+    ]
+    .join("\r\n");
+    let ical = parse_calendars(&text).unwrap().remove(0);
+    let date = |span: &TimeSpan<chrono_tz::Tz>| match span {
+        TimeSpan::Allday(date, _, _) => *date,
+        _ => panic!("Not an all-day span"),
+    };
+    let event = Event::from_ical(Path::new("/calendar/holidays.ics"), ical).unwrap();
+
+    // The days before, of and after the change to DST on March 26
+    let shown = (0..6)
+        .map(|day| {
+            event
+                .occurrence_rule()
+                .iter_overlapping(
+                    Bound::Included(local_day(day)),
+                    Bound::Excluded(local_day(day + 1)),
+                )
+                .map(|span| date(&span).to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let dates = |dates: &[&str]| dates.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(
+        shown,
+        vec![
+            dates(&[]),
+            dates(&["2023-03-25"]),
+            dates(&["2023-03-26"]),
+            dates(&["2023-03-27"]),
+            dates(&[]),
+            dates(&[]),
+        ]
+    );
+
+    for span in event.occurrence_rule().iter() {
+        assert_eq!(span.begin().naive_utc(), local_midnight(date(&span)));
+        assert_eq!(
+            span.end().naive_utc(),
+            local_midnight(date(&span) + Duration::days(1))
+        );
+    }
+}
+
+#[test]
+fn events_at_midnight_belong_to_the_day_they_begin() {
+    set_local_tz();
+
+    let midnight = origin() + Duration::days(1);
+    let rule = OccurrenceRule::Onetime(TimeSpan::from_start_and_duration(
+        midnight,
+        Duration::hours(1),
+    ));
+
+    let day_before = rule
+        .iter_overlapping(Bound::Included(origin()), Bound::Excluded(midnight))
+        .count();
+    let day = rule
+        .iter_overlapping(
+            Bound::Included(midnight),
+            Bound::Excluded(midnight + Duration::days(1)),
+        )
+        .count();
+
+    assert_eq!((day_before, day), (0, 1));
+}
+
+#[test]
+fn seeking_into_recurring_events_matches_expansion() {
+    set_local_tz();
+
+    let tz = chrono_tz::Europe::Berlin;
+    let rules = [
+        "FREQ=DAILY;INTERVAL=3",
+        "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR",
+        "FREQ=MONTHLY",
+        "FREQ=MONTHLY;BYDAY=-1FR",
+        "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=29",
+        "FREQ=DAILY;UNTIL=20300101T000000Z",
+        "FREQ=WEEKLY;COUNT=500",
+    ];
+
+    // Queries alternate between far apart ranges, as done by several panes
+    let starts = [0, 2000, 40, 9000, 2010, 1, 9000, 5000]
+        .iter()
+        .map(|days| origin() + Duration::days(*days) + Duration::hours(5))
+        .collect::<Vec<_>>();
+
+    for rule in rules.iter() {
+        // Begins on the 31st in winter, so that months and DST changes are crossed
+        let first = tz.with_ymd_and_hms(2016, 1, 31, 9, 30, 0).unwrap();
+        let rrule: RRuleSet = format!("DTSTART;TZID=Europe/Berlin:20160131T093000\nRRULE:{}", rule)
+            .parse()
+            .unwrap();
+        let occurrences =
+            OccurrenceRule::Onetime(TimeSpan::from_start_and_duration(first, Duration::hours(1)))
+                .with_recurring(rrule.clone());
+
+        for start in starts.iter() {
+            let expected = (&rrule)
+                .into_iter()
+                .map(|dt| dt.with_timezone(&Utc))
+                .skip_while(|dt| dt < start)
+                .take(20)
+                .collect::<Vec<_>>();
+            let actual = occurrences
+                .iter_from(*start)
+                .map(|span| span.begin().with_timezone(&Utc))
+                .take(20)
+                .collect::<Vec<_>>();
+
+            assert_eq!(actual, expected, "{} from {}", rule, start);
+        }
+    }
+}
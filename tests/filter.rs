@@ -0,0 +1,162 @@
+//! Parsing of filter queries: the structure of the parsed filters and the errors of invalid
+//! queries.
+
+use chrono::Duration;
+use jackal::provider::filter::{Comparison, EventFilter};
+use std::collections::HashMap;
+
+fn saved(filters: &[(&str, &str)]) -> HashMap<String, String> {
+    filters
+        .iter()
+        .map(|(name, query)| (name.to_string(), query.to_string()))
+        .collect()
+}
+
+fn parse(query: &str) -> EventFilter {
+    EventFilter::parse(query, &HashMap::new())
+        .unwrap_or_else(|e| panic!("'{}' is invalid: {:?}", query, e))
+}
+
+/// The parsed filter in its debug representation, which shows its whole structure.
+fn structure(query: &str) -> String {
+    format!("{:?}", parse(query))
+}
+
+fn error(query: &str, saved: &HashMap<String, String>) -> String {
+    match EventFilter::parse(query, saved) {
+        Ok(filter) => panic!("'{}' is valid: {:?}", query, filter),
+        Err(e) => e.message.unwrap_or_default(),
+    }
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    assert_eq!(
+        structure("a or b and c"),
+        r#"Or(Text("a"), And(Text("b"), Text("c")))"#
+    );
+    assert_eq!(
+        structure("a and b or c"),
+        r#"Or(And(Text("a"), Text("b")), Text("c"))"#
+    );
+    assert_eq!(
+        structure("(a or b) and c"),
+        r#"And(Or(Text("a"), Text("b")), Text("c"))"#
+    );
+    assert_eq!(structure("not a or b"), r#"Or(Not(Text("a")), Text("b"))"#);
+}
+
+#[test]
+fn omitted_and_is_implied() {
+    assert_eq!(
+        structure("calendar:work category:review"),
+        structure("calendar:work and category:review")
+    );
+    assert_eq!(
+        structure("a b c"),
+        r#"And(And(Text("a"), Text("b")), Text("c"))"#
+    );
+    assert_eq!(
+        structure("a (b or c)"),
+        r#"And(Text("a"), Or(Text("b"), Text("c")))"#
+    );
+}
+
+#[test]
+fn not_negates_the_next_term() {
+    assert_eq!(
+        structure("not status:cancelled"),
+        r#"Not(Status(Some(Cancelled)))"#
+    );
+    assert_eq!(structure("not a b"), r#"And(Not(Text("a")), Text("b"))"#);
+    assert_eq!(structure("not (a b)"), r#"Not(And(Text("a"), Text("b")))"#);
+    assert_eq!(structure("not not a"), r#"Not(Not(Text("a")))"#);
+    error("not", &HashMap::new());
+}
+
+#[test]
+fn quoted_values_keep_spaces_and_parentheses() {
+    assert_eq!(
+        structure(r#"title:"Team Meeting""#),
+        r#"Title("team meeting")"#
+    );
+    assert_eq!(
+        structure(r#""a (b)" c"#),
+        r#"And(Text("a (b)"), Text("c"))"#
+    );
+    assert_eq!(
+        structure(r#"location:"" status:none"#),
+        r#"And(Location(""), Status(None))"#
+    );
+    error(r#"title:"unterminated"#, &HashMap::new());
+}
+
+#[test]
+fn words_containing_keywords_are_text() {
+    assert_eq!(structure("android"), r#"Text("android")"#);
+    assert_eq!(
+        structure("notes or orders"),
+        r#"Or(Text("notes"), Text("orders"))"#
+    );
+    assert_eq!(structure("Andes"), r#"Text("andes")"#);
+    assert_eq!(structure(r#""or""#), r#"Text("or")"#);
+    assert_eq!(structure("title:and"), r#"Title("and")"#);
+    error("a or", &HashMap::new());
+    error("and a", &HashMap::new());
+}
+
+#[test]
+fn saved_filters_are_resolved() {
+    let saved = saved(&[
+        ("work", "calendar:work"),
+        ("focus", "@work not category:meeting"),
+        ("self", "a or @self"),
+        ("ping", "@pong"),
+        ("pong", "@ping"),
+    ]);
+
+    let filter = EventFilter::parse("@focus or @work", &saved).unwrap();
+    assert_eq!(
+        format!("{:?}", filter),
+        r#"Or(And(Calendar("work"), Not(Category("meeting"))), Calendar("work"))"#
+    );
+
+    assert!(error("@self", &saved).contains("refers to itself"));
+    assert!(error("@ping", &saved).contains("refers to itself"));
+    assert_eq!(error("@unknown", &saved), "Unknown saved filter 'unknown'");
+}
+
+#[test]
+fn comparisons() {
+    assert!(matches!(
+        parse("priority<=2"),
+        EventFilter::Priority(Comparison::LessOrEqual, 2)
+    ));
+    assert!(matches!(
+        parse("priority:1"),
+        EventFilter::Priority(Comparison::Equal, 1)
+    ));
+    match parse("duration>1h30m") {
+        EventFilter::Duration(Comparison::Greater, duration) => {
+            assert_eq!(duration, Duration::minutes(90))
+        }
+        filter => panic!("Not a duration filter: {:?}", filter),
+    }
+
+    let none = HashMap::new();
+    assert_eq!(
+        error("title<x", &none),
+        "'title' can only be compared with ':'"
+    );
+    assert_eq!(
+        error("after>2024-01-01", &none),
+        "'after' can only be compared with ':'"
+    );
+    assert_eq!(error("priority<high", &none), "Invalid priority 'high'");
+    assert_eq!(
+        error("duration>soon", &none),
+        "Invalid duration 'soon', expected e.g. '1h30m', '10 minutes' or 'PT15M'"
+    );
+    assert_eq!(error("colour:red", &none), "Unknown filter 'colour'");
+    error("priority>", &none);
+}
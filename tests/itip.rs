@@ -0,0 +1,305 @@
+//! Processing of iTIP (RFC 5546) scheduling messages: requests, replies and cancellations are
+//! applied to the stored events unless they are older than these.
+
+use jackal::config::CalendarConfig;
+use jackal::provider::ical::calendar::from_dir;
+use jackal::provider::ical::itip::Outcome;
+use jackal::provider::ical::value::param_value;
+use jackal::provider::ical::{parse_calendars, Calendar, Event};
+use jackal::provider::Eventlike;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+/// An empty calendar in a fresh directory named after `test`.
+fn calendar(test: &str) -> (Calendar, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("jackal-itip-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let config = CalendarConfig {
+        id: test.to_owned(),
+        name: test.to_owned(),
+        notify: None,
+        quiet_hours: None,
+    };
+    let (sender, _receiver) = mpsc::channel();
+
+    (from_dir(&dir, &config, &sender).unwrap(), dir)
+}
+
+/// Process a message with `method` and the event entries `events`, given by their properties.
+fn process(calendar: &mut Calendar, method: &str, events: &[&[&str]]) -> Vec<Outcome> {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "PRODID:-//Example//NONSGML Scheduling//EN".to_owned(),
+        "VERSION:2.0".to_owned(),
+        format!("METHOD:{}", method),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.extend(event.iter().map(|line| line.to_string()));
+        lines.push("END:VEVENT".to_owned());
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    let text = lines.join("\r\n") + "\r\n";
+    let message = parse_calendars(&text).unwrap().remove(0);
+    calendar.process_itip(message).unwrap()
+}
+
+fn value(event: &Event, name: &str) -> Option<String> {
+    event
+        .ical_event()
+        .properties
+        .iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.value.clone())
+}
+
+/// Titles of the modified instances stored with the event.
+fn overrides(event: &Event) -> Vec<String> {
+    event.as_ical().events[1..]
+        .iter()
+        .filter_map(|e| e.properties.iter().find(|p| p.name == "SUMMARY"))
+        .filter_map(|p| p.value.clone())
+        .collect()
+}
+
+fn partstat(event: &Event, address: &str) -> Option<String> {
+    event
+        .ical_event()
+        .properties
+        .iter()
+        .find(|p| p.name == "ATTENDEE" && p.value.as_deref() == Some(address))
+        .and_then(|p| param_value(p, "PARTSTAT"))
+        .map(str::to_owned)
+}
+
+fn request(uid: &str, sequence: u32, dtstamp: &str, title: &str) -> Vec<String> {
+    vec![
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", dtstamp),
+        format!("SEQUENCE:{}", sequence),
+        "DTSTART:20240108T090000Z".to_owned(),
+        "DTEND:20240108T100000Z".to_owned(),
+        format!("SUMMARY:{}", title),
+        "ORGANIZER:mailto:john@example.com".to_owned(),
+        "ATTENDEE;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:jane@example.com".to_owned(),
+    ]
+}
+
+fn process_request(
+    calendar: &mut Calendar,
+    uid: &str,
+    sequence: u32,
+    dtstamp: &str,
+    title: &str,
+) -> Vec<Outcome> {
+    let event = request(uid, sequence, dtstamp, title);
+    let event = event.iter().map(String::as_str).collect::<Vec<_>>();
+    process(calendar, "REQUEST", &[&event])
+}
+
+#[test]
+fn requests_add_and_update_events_by_sequence() {
+    let (mut calendar, dir) = calendar("sequence");
+    let uid = "sequence@example.com";
+
+    let outcome = process_request(&mut calendar, uid, 0, "20240101T080000Z", "Planning");
+    assert_eq!(outcome, vec![Outcome::Added(uid.to_owned())]);
+
+    let outcome = process_request(
+        &mut calendar,
+        uid,
+        2,
+        "20240102T080000Z",
+        "Planning (moved)",
+    );
+    assert_eq!(outcome, vec![Outcome::Updated(uid.to_owned())]);
+
+    // A late delivery of an earlier revision
+    let outcome = process_request(&mut calendar, uid, 1, "20240103T080000Z", "Planning (old)");
+    assert_eq!(outcome, vec![Outcome::Outdated(uid.to_owned())]);
+
+    let stored = calendar.get(uid).unwrap();
+    assert_eq!(stored.title(), "Planning (moved)");
+    assert_eq!(value(stored, "SEQUENCE").as_deref(), Some("2"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn requests_with_equal_sequence_are_ordered_by_dtstamp() {
+    let (mut calendar, dir) = calendar("dtstamp");
+    let uid = "dtstamp@example.com";
+
+    process_request(&mut calendar, uid, 1, "20240102T080000Z", "Planning");
+
+    // A resend of an older message of the same revision
+    let outcome = process_request(&mut calendar, uid, 1, "20240101T080000Z", "Planning (old)");
+    assert_eq!(outcome, vec![Outcome::Outdated(uid.to_owned())]);
+    assert_eq!(calendar.get(uid).unwrap().title(), "Planning");
+
+    let outcome = process_request(&mut calendar, uid, 1, "20240103T080000Z", "Planning (new)");
+    assert_eq!(outcome, vec![Outcome::Updated(uid.to_owned())]);
+    assert_eq!(calendar.get(uid).unwrap().title(), "Planning (new)");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn requests_for_single_instances() {
+    let (mut calendar, dir) = calendar("instance");
+    let uid = "instance@example.com";
+
+    let mut master = request(uid, 0, "20240101T080000Z", "Standup");
+    master.push("RRULE:FREQ=DAILY;COUNT=10".to_owned());
+    let master = master.iter().map(String::as_str).collect::<Vec<_>>();
+    process(&mut calendar, "REQUEST", &[&master]);
+
+    let instance = |sequence: u32, title: &str| {
+        vec![
+            format!("UID:{}", uid),
+            "DTSTAMP:20240102T080000Z".to_owned(),
+            format!("SEQUENCE:{}", sequence),
+            "RECURRENCE-ID:20240110T090000Z".to_owned(),
+            "DTSTART:20240110T140000Z".to_owned(),
+            "DTEND:20240110T150000Z".to_owned(),
+            format!("SUMMARY:{}", title),
+        ]
+    };
+
+    let moved = instance(1, "Standup (afternoon)");
+    let moved = moved.iter().map(String::as_str).collect::<Vec<_>>();
+    let outcome = process(&mut calendar, "REQUEST", &[&moved]);
+    assert_eq!(outcome, vec![Outcome::Updated(uid.to_owned())]);
+
+    let stored = calendar.get(uid).unwrap();
+    assert_eq!(stored.title(), "Standup");
+    assert_eq!(overrides(stored), vec!["Standup (afternoon)"]);
+
+    let old = instance(0, "Standup (old)");
+    let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+    let outcome = process(&mut calendar, "REQUEST", &[&old]);
+    assert_eq!(outcome, vec![Outcome::Outdated(uid.to_owned())]);
+    assert_eq!(
+        overrides(calendar.get(uid).unwrap()),
+        vec!["Standup (afternoon)"]
+    );
+
+    // Cancelling the instance drops its modification and excludes it from the rule
+    let outcome = process(
+        &mut calendar,
+        "CANCEL",
+        &[&[
+            &format!("UID:{}", uid),
+            "DTSTAMP:20240103T080000Z",
+            "SEQUENCE:1",
+            "RECURRENCE-ID:20240110T090000Z",
+        ]],
+    );
+    assert_eq!(outcome, vec![Outcome::Cancelled(uid.to_owned())]);
+
+    let stored = calendar.get(uid).unwrap();
+    assert!(overrides(stored).is_empty());
+    assert_eq!(value(stored, "EXDATE").as_deref(), Some("20240110T090000Z"));
+    assert_eq!(value(stored, "STATUS"), None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cancellations() {
+    let (mut calendar, dir) = calendar("cancel");
+    let uid = "cancel@example.com";
+    process_request(&mut calendar, uid, 2, "20240101T080000Z", "Planning");
+
+    let cancel = |sequence: &str| {
+        vec![
+            format!("UID:{}", uid),
+            "DTSTAMP:20240102T080000Z".to_owned(),
+            format!("SEQUENCE:{}", sequence),
+        ]
+    };
+
+    let old = cancel("1");
+    let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+    let outcome = process(&mut calendar, "CANCEL", &[&old]);
+    assert_eq!(outcome, vec![Outcome::Outdated(uid.to_owned())]);
+    assert_eq!(value(calendar.get(uid).unwrap(), "STATUS"), None);
+
+    let current = cancel("3");
+    let current = current.iter().map(String::as_str).collect::<Vec<_>>();
+    let outcome = process(&mut calendar, "CANCEL", &[&current]);
+    assert_eq!(outcome, vec![Outcome::Cancelled(uid.to_owned())]);
+
+    let stored = calendar.get(uid).unwrap();
+    assert_eq!(value(stored, "STATUS").as_deref(), Some("CANCELLED"));
+    assert_eq!(value(stored, "SEQUENCE").as_deref(), Some("3"));
+
+    let outcome = process(
+        &mut calendar,
+        "CANCEL",
+        &[&["UID:unknown@example.com", "DTSTAMP:20240102T080000Z"]],
+    );
+    assert_eq!(
+        outcome,
+        vec![Outcome::Unknown("unknown@example.com".to_owned())]
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn replies_update_the_participation_of_attendees() {
+    let (mut calendar, dir) = calendar("reply");
+    let uid = "reply@example.com";
+    process_request(&mut calendar, uid, 1, "20240101T080000Z", "Planning");
+
+    let reply = |sequence: u32, dtstamp: &str, partstat: &str| {
+        vec![
+            format!("UID:{}", uid),
+            format!("DTSTAMP:{}", dtstamp),
+            format!("SEQUENCE:{}", sequence),
+            "ORGANIZER:mailto:john@example.com".to_owned(),
+            format!("ATTENDEE;PARTSTAT={}:mailto:jane@example.com", partstat),
+        ]
+    };
+    let mut process_reply = |sequence, dtstamp, partstat| {
+        let reply = reply(sequence, dtstamp, partstat);
+        let reply = reply.iter().map(String::as_str).collect::<Vec<_>>();
+        process(&mut calendar, "REPLY", &[&reply])
+    };
+
+    let outcome = process_reply(1, "20240105T080000Z", "ACCEPTED");
+    assert_eq!(outcome, vec![Outcome::Updated(uid.to_owned())]);
+
+    // Replies sent before the latest one, or to an earlier revision, do not count
+    let outcome = process_reply(1, "20240104T080000Z", "DECLINED");
+    assert_eq!(outcome, vec![Outcome::Outdated(uid.to_owned())]);
+    let outcome = process_reply(0, "20240106T080000Z", "DECLINED");
+    assert_eq!(outcome, vec![Outcome::Outdated(uid.to_owned())]);
+
+    let stored = calendar.get(uid).unwrap();
+    assert_eq!(
+        partstat(stored, "mailto:jane@example.com").as_deref(),
+        Some("ACCEPTED")
+    );
+    let attendee = stored
+        .ical_event()
+        .properties
+        .iter()
+        .find(|p| p.name == "ATTENDEE")
+        .unwrap();
+    assert_eq!(param_value(attendee, "RSVP"), None);
+
+    let outcome = process_reply(1, "20240107T080000Z", "TENTATIVE");
+    assert_eq!(outcome, vec![Outcome::Updated(uid.to_owned())]);
+    assert_eq!(
+        partstat(calendar.get(uid).unwrap(), "mailto:jane@example.com").as_deref(),
+        Some("TENTATIVE")
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
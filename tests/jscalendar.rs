@@ -0,0 +1,235 @@
+//! Conversions between iCalendar events and JSCalendar (RFC 8984) `Event` objects: converting
+//! an event to JSCalendar and back must keep everything JSCalendar is able to represent.
+
+use jackal::exchange::jscalendar::{self, JsEvent, Trigger};
+use jackal::provider::ical::{parse_calendars, ser, Event};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Join content lines with CRLF as the serializer does.
+fn lines(lines: &[&str]) -> String {
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+fn event(text: &str) -> Event {
+    let mut calendars = parse_calendars(text).expect("valid iCalendar");
+    assert_eq!(calendars.len(), 1);
+
+    Event::from_ical(Path::new("/calendar/event.ics"), calendars.remove(0)).expect("valid event")
+}
+
+/// Convert a JSCalendar event to iCalendar and back.
+fn round_trip(js_event: &JsEvent) -> JsEvent {
+    let calendar = jscalendar::to_ical(js_event).expect("convertible to iCalendar");
+    let text = ser::to_string(&calendar).expect("serializable to iCalendar");
+
+    jscalendar::from_event(&event(&text))
+}
+
+fn to_value(js_event: &JsEvent) -> Value {
+    serde_json::to_value(js_event).unwrap()
+}
+
+#[test]
+fn all_day_event() {
+    let js_event = jscalendar::from_event(&event(&lines(&[
+        "BEGIN:VCALENDAR",
+        "PRODID:-//JACKAL//NONSGML Calendar//EN",
+        "VERSION:2.0",
+        "BEGIN:VEVENT",
+        "UID:holiday@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "DTSTART;VALUE=DATE:20240101",
+        "DTEND;VALUE=DATE:20240103",
+        "SUMMARY:New Year",
+        "TRANSP:TRANSPARENT",
+        "END:VEVENT",
+        "END:VCALENDAR",
+    ])));
+
+    assert_eq!(
+        to_value(&js_event),
+        json!({
+            "@type": "Event",
+            "uid": "holiday@example.com",
+            "title": "New Year",
+            "start": "2024-01-01T00:00:00",
+            "duration": "P2D",
+            "showWithoutTime": true,
+            "freeBusyStatus": "free",
+        })
+    );
+
+    let text = ser::to_string(&jscalendar::to_ical(&js_event).unwrap()).unwrap();
+    assert!(
+        text.contains("\r\nDTSTART;VALUE=DATE:20240101\r\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("\r\nDTEND;VALUE=DATE:20240103\r\n"),
+        "{}",
+        text
+    );
+
+    assert_eq!(to_value(&round_trip(&js_event)), to_value(&js_event));
+}
+
+#[test]
+fn recurring_event_with_overrides_and_exclusions() {
+    let js_event = jscalendar::from_event(&event(&lines(&[
+        "BEGIN:VCALENDAR",
+        "PRODID:-//JACKAL//NONSGML Calendar//EN",
+        "VERSION:2.0",
+        "BEGIN:VEVENT",
+        "UID:standup@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "DTSTART;TZID=Europe/Berlin:20240108T090000",
+        "DTEND;TZID=Europe/Berlin:20240108T091500",
+        "RRULE:FREQ=DAILY;UNTIL=20240131T080000Z",
+        "EXDATE;TZID=Europe/Berlin:20240110T090000",
+        "SUMMARY:Standup",
+        "BEGIN:VALARM",
+        "ACTION:DISPLAY",
+        "DESCRIPTION:Standup",
+        "TRIGGER:-PT10M",
+        "END:VALARM",
+        "END:VEVENT",
+        "BEGIN:VEVENT",
+        "UID:standup@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "RECURRENCE-ID;TZID=Europe/Berlin:20240115T090000",
+        "DTSTART;TZID=Europe/Berlin:20240115T100000",
+        "DTEND;TZID=Europe/Berlin:20240115T101500",
+        "SUMMARY:Standup (moved)",
+        "BEGIN:VALARM",
+        "ACTION:DISPLAY",
+        "DESCRIPTION:Standup",
+        "TRIGGER:-PT10M",
+        "END:VALARM",
+        "END:VEVENT",
+        "END:VCALENDAR",
+    ])));
+
+    assert_eq!(js_event.start, "2024-01-08T09:00:00");
+    assert_eq!(js_event.time_zone.as_deref(), Some("Europe/Berlin"));
+    assert_eq!(js_event.duration.as_deref(), Some("PT15M"));
+
+    assert_eq!(js_event.recurrence_rules.len(), 1);
+    let rule = &js_event.recurrence_rules[0];
+    assert_eq!(rule.frequency, "daily");
+    assert_eq!(rule.until.as_deref(), Some("2024-01-31T09:00:00"));
+
+    assert_eq!(js_event.alerts.len(), 1);
+    match &js_event.alerts["1"].trigger {
+        Trigger::OffsetTrigger {
+            offset,
+            relative_to,
+        } => {
+            assert_eq!(offset, "-PT10M");
+            assert_eq!(relative_to.as_deref(), None);
+        }
+        trigger => panic!("Unexpected trigger {:?}", trigger),
+    }
+
+    assert_eq!(
+        serde_json::to_value(&js_event.recurrence_overrides).unwrap(),
+        json!({
+            "2024-01-10T09:00:00": { "excluded": true },
+            "2024-01-15T09:00:00": {
+                "start": "2024-01-15T10:00:00",
+                "title": "Standup (moved)",
+            },
+        })
+    );
+
+    let text = ser::to_string(&jscalendar::to_ical(&js_event).unwrap()).unwrap();
+    assert!(
+        text.contains("\r\nEXDATE;TZID=Europe/Berlin:20240110T090000\r\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("\r\nRECURRENCE-ID;TZID=Europe/Berlin:20240115T090000\r\n"),
+        "{}",
+        text
+    );
+
+    assert_eq!(to_value(&round_trip(&js_event)), to_value(&js_event));
+}
+
+#[test]
+fn participants() {
+    let js_event: JsEvent = serde_json::from_value(json!({
+        "@type": "Event",
+        "uid": "planning@example.com",
+        "title": "Planning",
+        "start": "2024-03-04T10:00:00",
+        "timeZone": "Europe/Berlin",
+        "duration": "PT1H",
+        "participants": {
+            "1": {
+                "@type": "Participant",
+                "name": "Jane Doe",
+                "email": "jane@example.com",
+                "sendTo": { "imip": "mailto:jane@example.com" },
+                "roles": { "attendee": true },
+                "participationStatus": "needs-action",
+                "expectReply": true,
+            },
+            "2": {
+                "@type": "Participant",
+                "name": "Max Mustermann",
+                "email": "max@example.com",
+                "sendTo": { "imip": "mailto:max@example.com" },
+                "roles": { "attendee": true, "optional": true },
+                "participationStatus": "accepted",
+            },
+            "3": {
+                "@type": "Participant",
+                "name": "John Doe",
+                "email": "john@example.com",
+                "sendTo": { "imip": "mailto:john@example.com" },
+                "roles": { "owner": true },
+            },
+        },
+        "status": "confirmed",
+        "sequence": 2,
+    }))
+    .unwrap();
+
+    let text = ser::to_string(&jscalendar::to_ical(&js_event).unwrap()).unwrap();
+    let unfolded = text.replace("\r\n ", "");
+    for line in [
+        "ORGANIZER;CN=John Doe:mailto:john@example.com",
+        "ATTENDEE;CN=Jane Doe;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:jane@example.com",
+        "ATTENDEE;CN=Max Mustermann;ROLE=OPT-PARTICIPANT;PARTSTAT=ACCEPTED:mailto:max@example.com",
+    ] {
+        assert!(
+            unfolded.contains(&format!("\r\n{}\r\n", line)),
+            "{} missing in {}",
+            line,
+            text
+        );
+    }
+
+    assert_eq!(to_value(&round_trip(&js_event)), to_value(&js_event));
+}
+
+#[test]
+fn groups_and_single_events_are_parsed() {
+    let js_event = JsEvent {
+        title: Some("Review".to_owned()),
+        ..jscalendar::from_str(
+            r#"{"@type": "Event", "uid": "review", "start": "2024-01-08T09:00:00"}"#,
+        )
+        .unwrap()
+        .remove(0)
+    };
+
+    let group = jscalendar::to_string(&[js_event.clone()]).unwrap();
+    let parsed = jscalendar::from_str(&group).unwrap();
+
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(to_value(&parsed[0]), to_value(&js_event));
+}
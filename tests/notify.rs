@@ -0,0 +1,391 @@
+//! Scheduling of reminders by jk-notify: every alarm of an occurrence is due on its own, events
+//! without alarms fall back to the global headsup time and dismissed or snoozed occurrences,
+//! which are persisted across restarts, are honored.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jackal::agenda::Agenda;
+use jackal::config::{BackendConfig, CalendarConfig, CollectionConfig, Config};
+use jackal::notification::schedule::{due_reminders, max_alarm_offset};
+use jackal::notification::{
+    backend, NotificationState, OccurrenceKey, Reminder, Response, SnoozeOption,
+};
+use jackal::provider::{Alarm, AlarmAction, AlarmRelation, AlarmTrigger};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+const EVENTS: &[(&str, &[&str])] = &[
+    (
+        "meeting",
+        &[
+            "DTSTART:20240108T090000Z",
+            "DTEND:20240108T100000Z",
+            "SUMMARY:Meeting",
+            "BEGIN:VALARM",
+            "ACTION:DISPLAY",
+            "TRIGGER:-PT15M",
+            "END:VALARM",
+            "BEGIN:VALARM",
+            "ACTION:AUDIO",
+            "TRIGGER;RELATED=END:PT0S",
+            "END:VALARM",
+            "BEGIN:VALARM",
+            "ACTION:EMAIL",
+            "TRIGGER:-PT1H",
+            "REPEAT:2",
+            "DURATION:PT10M",
+            "DESCRIPTION:Prepare the slides",
+            "END:VALARM",
+        ],
+    ),
+    (
+        "standup",
+        &[
+            "DTSTART:20240108T093000Z",
+            "DTEND:20240108T094500Z",
+            "RRULE:FREQ=DAILY;COUNT=3",
+            "SUMMARY:Standup",
+            "BEGIN:VALARM",
+            "ACTION:DISPLAY",
+            "TRIGGER:-PT5M",
+            "END:VALARM",
+            "BEGIN:VALARM",
+            "ACTION:DISPLAY",
+            "TRIGGER;VALUE=DATE-TIME:20240107T200000Z",
+            "END:VALARM",
+        ],
+    ),
+    (
+        "lunch",
+        &[
+            "DTSTART:20240108T120000Z",
+            "DTEND:20240108T130000Z",
+            "SUMMARY:Lunch",
+        ],
+    ),
+    (
+        "cancelled",
+        &[
+            "DTSTART:20240109T150000Z",
+            "DTEND:20240109T160000Z",
+            "SUMMARY:Cancelled",
+            "STATUS:CANCELLED",
+            "BEGIN:VALARM",
+            "ACTION:DISPLAY",
+            "TRIGGER:-PT15M",
+            "END:VALARM",
+        ],
+    ),
+];
+
+/// A configuration with a calendar holding `EVENTS` in a fresh directory named after `test`.
+fn config(test: &str) -> (Config, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("jackal-notify-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("personal")).unwrap();
+
+    for (uid, lines) in EVENTS {
+        let mut text = vec![
+            "BEGIN:VCALENDAR".to_owned(),
+            "PRODID:-//Example//NONSGML Test//EN".to_owned(),
+            "VERSION:2.0".to_owned(),
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:{}", uid),
+            "DTSTAMP:20240101T000000Z".to_owned(),
+        ];
+        text.extend(lines.iter().map(|line| line.to_string()));
+        text.push("END:VEVENT".to_owned());
+        text.push("END:VCALENDAR".to_owned());
+
+        let path = dir.join("personal").join(format!("{}.ics", uid));
+        fs::write(path, text.join("\r\n") + "\r\n").unwrap();
+    }
+
+    let mut config = Config::default();
+    config.collections = vec![CollectionConfig {
+        name: "local".to_owned(),
+        provider: "ical".to_owned(),
+        path: dir.clone(),
+        calendars: vec![CalendarConfig {
+            id: "personal".to_owned(),
+            name: "Personal".to_owned(),
+            notify: None,
+            quiet_hours: None,
+        }],
+    }];
+
+    (config, dir)
+}
+
+fn agenda(config: &Config) -> Agenda {
+    let (sender, _receiver) = mpsc::channel();
+    Agenda::from_config(config, &sender).unwrap()
+}
+
+fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+}
+
+/// Time, event title and action of the reminders due in `window`.
+fn due(
+    agenda: &Agenda,
+    state: &NotificationState,
+    window: std::ops::Range<DateTime<Utc>>,
+) -> Vec<(DateTime<Utc>, String, AlarmAction)> {
+    let lookaround = max_alarm_offset(agenda);
+    due_reminders(agenda, state, Duration::minutes(10), lookaround, window)
+        .into_iter()
+        .map(|reminder| {
+            let title = reminder.occurrence.event().title().to_owned();
+            (reminder.time, title, reminder.action)
+        })
+        .collect()
+}
+
+fn reminder(
+    time: DateTime<Utc>,
+    title: &str,
+    action: AlarmAction,
+) -> (DateTime<Utc>, String, AlarmAction) {
+    (time, title.to_owned(), action)
+}
+
+#[test]
+fn alarms_are_parsed() {
+    let (config, dir) = config("parse");
+    let agenda = agenda(&config);
+
+    let meeting = agenda
+        .first_occurrences()
+        .find(|occurrence| occurrence.event().uid() == "meeting")
+        .unwrap();
+    assert_eq!(
+        meeting.event().alarms(),
+        vec![
+            Alarm::before_start(Duration::minutes(15)),
+            Alarm {
+                action: AlarmAction::Audio,
+                trigger: AlarmTrigger::Relative {
+                    offset: Duration::zero(),
+                    related: AlarmRelation::End,
+                },
+                repeat: 0,
+                interval: None,
+                description: None,
+            },
+            Alarm {
+                action: AlarmAction::Email,
+                trigger: AlarmTrigger::Relative {
+                    offset: Duration::hours(-1),
+                    related: AlarmRelation::Start,
+                },
+                repeat: 2,
+                interval: Some(Duration::minutes(10)),
+                description: Some("Prepare the slides".to_owned()),
+            },
+        ]
+    );
+
+    // The repetitions of the email reminder reach furthest from their occurrence
+    assert_eq!(max_alarm_offset(&agenda), Duration::minutes(80));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn every_alarm_is_due_on_its_own() {
+    let (config, dir) = config("alarms");
+    let agenda = agenda(&config);
+    let state = NotificationState::default();
+
+    assert_eq!(
+        due(
+            &agenda,
+            &state,
+            utc(2024, 1, 7, 0, 0)..utc(2024, 1, 11, 0, 0)
+        ),
+        vec![
+            // Absolute alarms go off once for the whole recurring event
+            reminder(utc(2024, 1, 7, 20, 0), "Standup", AlarmAction::Display),
+            reminder(utc(2024, 1, 8, 8, 0), "Meeting", AlarmAction::Email),
+            reminder(utc(2024, 1, 8, 8, 10), "Meeting", AlarmAction::Email),
+            reminder(utc(2024, 1, 8, 8, 20), "Meeting", AlarmAction::Email),
+            reminder(utc(2024, 1, 8, 8, 45), "Meeting", AlarmAction::Display),
+            reminder(utc(2024, 1, 8, 9, 25), "Standup", AlarmAction::Display),
+            reminder(utc(2024, 1, 8, 10, 0), "Meeting", AlarmAction::Audio),
+            // Events without alarms are reminded of the headsup time before they begin
+            reminder(utc(2024, 1, 8, 11, 50), "Lunch", AlarmAction::Display),
+            reminder(utc(2024, 1, 9, 9, 25), "Standup", AlarmAction::Display),
+            reminder(utc(2024, 1, 10, 9, 25), "Standup", AlarmAction::Display),
+        ]
+    );
+
+    // Alarms are due in the window they go off in, even if their occurrence is outside of it
+    assert_eq!(
+        due(
+            &agenda,
+            &state,
+            utc(2024, 1, 8, 8, 5)..utc(2024, 1, 8, 8, 50)
+        ),
+        vec![
+            reminder(utc(2024, 1, 8, 8, 10), "Meeting", AlarmAction::Email),
+            reminder(utc(2024, 1, 8, 8, 20), "Meeting", AlarmAction::Email),
+            reminder(utc(2024, 1, 8, 8, 45), "Meeting", AlarmAction::Display),
+        ]
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dismissed_and_snoozed_occurrences() {
+    let (config, dir) = config("state");
+    let agenda = agenda(&config);
+
+    let mut state = NotificationState::default();
+    state.dismiss(OccurrenceKey {
+        uid: "meeting".to_owned(),
+        begin: utc(2024, 1, 8, 9, 0),
+    });
+    // Both alarms of the first standup went off before the snooze ends
+    state.snooze(
+        OccurrenceKey {
+            uid: "standup".to_owned(),
+            begin: utc(2024, 1, 8, 9, 30),
+        },
+        utc(2024, 1, 8, 9, 35),
+    );
+
+    assert_eq!(
+        due(
+            &agenda,
+            &state,
+            utc(2024, 1, 7, 0, 0)..utc(2024, 1, 11, 0, 0)
+        ),
+        vec![
+            reminder(utc(2024, 1, 8, 9, 35), "Standup", AlarmAction::Display),
+            reminder(utc(2024, 1, 8, 11, 50), "Lunch", AlarmAction::Display),
+            reminder(utc(2024, 1, 9, 9, 25), "Standup", AlarmAction::Display),
+            reminder(utc(2024, 1, 10, 9, 25), "Standup", AlarmAction::Display),
+        ]
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn state_is_persisted_and_pruned() {
+    let dir = std::env::temp_dir().join(format!("jackal-notify-persist-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("jackal").join("notify-state.json");
+
+    let meeting = OccurrenceKey {
+        uid: "meeting".to_owned(),
+        begin: utc(2024, 1, 8, 9, 0),
+    };
+    let standup = OccurrenceKey {
+        uid: "standup".to_owned(),
+        begin: utc(2024, 1, 9, 9, 30),
+    };
+
+    // A missing state file results in an empty state, which creates it on saving
+    let mut state = NotificationState::load(&path);
+    assert!(!state.is_dismissed(&meeting));
+    state.snooze(meeting.clone(), utc(2024, 1, 8, 8, 50));
+    state.snooze(standup.clone(), utc(2024, 1, 9, 9, 30));
+    state.dismiss(meeting.clone());
+    state.save().unwrap();
+
+    let mut state = NotificationState::load(&path);
+    assert!(state.is_dismissed(&meeting));
+    assert_eq!(state.snoozed_until(&meeting), None);
+    assert!(!state.is_dismissed(&standup));
+    assert_eq!(state.snoozed_until(&standup), Some(utc(2024, 1, 9, 9, 30)));
+
+    assert!(!state.prune(utc(2024, 1, 8, 9, 0)));
+    assert!(state.prune(utc(2024, 1, 9, 0, 0)));
+    assert!(!state.is_dismissed(&meeting));
+    assert_eq!(state.snoozed_until(&standup), Some(utc(2024, 1, 9, 9, 30)));
+
+    // An invalid state file is ignored
+    fs::write(&path, "{").unwrap();
+    let state = NotificationState::load(&path);
+    assert_eq!(state.snoozed_until(&standup), None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn snooze_options_are_parsed() {
+    let parse = |s: &str| s.parse::<SnoozeOption>().unwrap();
+
+    assert_eq!(parse("5m"), SnoozeOption::For(Duration::minutes(5)));
+    assert_eq!(
+        parse("10 minutes"),
+        SnoozeOption::For(Duration::minutes(10))
+    );
+    assert_eq!(
+        parse("1 hour 30 min"),
+        SnoozeOption::For(Duration::minutes(90))
+    );
+    assert_eq!(parse("until start"), SnoozeOption::UntilStart);
+    assert_eq!(parse("Start"), SnoozeOption::UntilStart);
+    assert_eq!(
+        parse("1 minute before"),
+        SnoozeOption::BeforeStart(Duration::minutes(1))
+    );
+    assert_eq!(
+        parse("2h before start"),
+        SnoozeOption::BeforeStart(Duration::hours(2))
+    );
+
+    let err = "1 fortnight".parse::<SnoozeOption>().unwrap_err();
+    assert!(err.to_string().contains("10 minutes"), "{}", err);
+    assert!("-5m before".parse::<SnoozeOption>().is_err());
+
+    // Snoozing until before the start is relative to the event, other snoozes to now
+    let now = utc(2024, 1, 8, 8, 45);
+    let begin = utc(2024, 1, 8, 9, 0);
+    assert_eq!(
+        parse("1 minute before").until(now, begin),
+        utc(2024, 1, 8, 8, 59)
+    );
+    assert_eq!(
+        parse("10 minutes").until(now, begin),
+        utc(2024, 1, 8, 8, 55)
+    );
+}
+
+#[test]
+fn backends_without_answers_do_not_dismiss() {
+    let (config, dir) = config("backends");
+    let agenda = agenda(&config);
+    let meeting = agenda
+        .first_occurrences()
+        .find(|occurrence| occurrence.event().uid() == "meeting")
+        .unwrap();
+    let reminder = Reminder::new(&meeting, AlarmAction::Display);
+
+    let path = dir.join("reminders.jsonl");
+    let backends = [
+        BackendConfig::Json {
+            path: Some(path.clone()),
+        },
+        BackendConfig::Command {
+            command: "test \"$JK_UID\" = meeting".to_owned(),
+        },
+    ];
+    for config in backends.iter() {
+        let response = backend::from_config(config).notify(&reminder, &[]).unwrap();
+        assert_eq!(response, Response::Delivered);
+    }
+
+    let line = fs::read_to_string(path).unwrap();
+    assert!(
+        line.starts_with(r#"{"kind":"reminder","uid":"meeting","#),
+        "{}",
+        line
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
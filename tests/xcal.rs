@@ -0,0 +1,109 @@
+//! Round trips of calendars through their xCal (RFC 6321) representation: converting the text
+//! of a calendar to xCal and back must reproduce it exactly.
+
+use jackal::provider::ical::{parse_calendars, ser, xcal};
+
+/// Convert iCalendar text to xCal and back to iCalendar text.
+fn round_trip(text: &str) -> String {
+    let calendars = parse_calendars(text).expect("valid iCalendar");
+    assert_eq!(calendars.len(), 1);
+
+    let xml = xcal::to_string(&calendars[0]).expect("serializable to xCal");
+    let calendars = xcal::from_str(&xml).expect("valid xCal");
+    assert_eq!(calendars.len(), 1);
+
+    ser::to_string(&calendars[0]).expect("serializable to iCalendar")
+}
+
+/// Join content lines with CRLF as the serializer does.
+fn lines(lines: &[&str]) -> String {
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+#[test]
+fn text_whitespace_is_kept() {
+    let text = lines(&[
+        "BEGIN:VCALENDAR",
+        "PRODID:-//JACKAL//NONSGML Calendar//EN",
+        "VERSION:2.0",
+        "BEGIN:VEVENT",
+        "UID:whitespace@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "DTSTART:20240108T090000Z",
+        "SUMMARY:  Indented title",
+        "DESCRIPTION:\\n  Bring notes\\, please \\n\\n",
+        "LOCATION:Room 1\\; 2nd floor",
+        "END:VEVENT",
+        "END:VCALENDAR",
+    ]);
+
+    assert_eq!(round_trip(&text), text);
+}
+
+#[test]
+fn multi_valued_properties_and_parameters() {
+    let text = lines(&[
+        "BEGIN:VCALENDAR",
+        "PRODID:-//JACKAL//NONSGML Calendar//EN",
+        "VERSION:2.0",
+        "BEGIN:VEVENT",
+        "UID:review@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "DTSTART;TZID=Europe/Berlin:20240108T090000",
+        "DTEND;TZID=Europe/Berlin:20240108T100000",
+        "RRULE:FREQ=WEEKLY;COUNT=6;BYDAY=MO,TH",
+        "EXDATE;TZID=Europe/Berlin:20240111T090000,20240118T090000",
+        "CATEGORIES:work,travel\\, abroad",
+        "ORGANIZER;CN=John Doe:mailto:john@example.com",
+        "ATTENDEE;CN=\"Doe, Jane\";ROLE=REQ-PARTICIPANT;RSVP=TRUE:mailto:jane@example.com",
+        "SUMMARY:Review",
+        "END:VEVENT",
+        "BEGIN:VEVENT",
+        "UID:holiday@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "DTSTART;VALUE=DATE:20240101",
+        "SUMMARY:New Year",
+        "END:VEVENT",
+        "END:VCALENDAR",
+    ]);
+
+    assert_eq!(round_trip(&text), text);
+}
+
+#[test]
+fn alarms_and_recurrence_overrides() {
+    let text = lines(&[
+        "BEGIN:VCALENDAR",
+        "PRODID:-//JACKAL//NONSGML Calendar//EN",
+        "VERSION:2.0",
+        "BEGIN:VEVENT",
+        "UID:standup@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "DTSTART;TZID=Europe/Berlin:20240108T090000",
+        "DTEND;TZID=Europe/Berlin:20240108T091500",
+        "RRULE:FREQ=DAILY;UNTIL=20240131T080000Z",
+        "SUMMARY:Standup",
+        "BEGIN:VALARM",
+        "ACTION:DISPLAY",
+        "DESCRIPTION:Standup in 10 minutes",
+        "TRIGGER;RELATED=START:-PT10M",
+        "END:VALARM",
+        "BEGIN:VALARM",
+        "ACTION:DISPLAY",
+        "DESCRIPTION:Standup",
+        "TRIGGER;VALUE=DATE-TIME:20240108T075500Z",
+        "END:VALARM",
+        "END:VEVENT",
+        "BEGIN:VEVENT",
+        "UID:standup@example.com",
+        "DTSTAMP:20240101T080000Z",
+        "RECURRENCE-ID;TZID=Europe/Berlin:20240115T090000",
+        "DTSTART;TZID=Europe/Berlin:20240115T100000",
+        "DTEND;TZID=Europe/Berlin:20240115T101500",
+        "SUMMARY:Standup (moved)",
+        "END:VEVENT",
+        "END:VCALENDAR",
+    ]);
+
+    assert_eq!(round_trip(&text), text);
+}